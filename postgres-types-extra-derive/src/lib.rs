@@ -0,0 +1,188 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use std::collections::HashSet;
+use syn::{Data, DeriveInput, Fields, LitStr, Type, parse_macro_input};
+
+/// Implements `postgres_types_extra::pg_hstore_record::HstoreRecord`, see the trait for
+/// the attributes
+#[proc_macro_derive(HstoreRecord, attributes(hstore))]
+pub fn derive_hstore_record(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+struct Field {
+    ident: syn::Ident,
+    ty: Type,
+    key: String,
+    flatten: bool,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let mut deny_unknown_keys = false;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("hstore"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("deny_unknown_keys") {
+                deny_unknown_keys = true;
+                Ok(())
+            } else {
+                Err(meta.error("unknown hstore attribute"))
+            }
+        })?;
+    }
+
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "HstoreRecord can only be derived for structs",
+        ));
+    };
+    let Fields::Named(named) = &data.fields else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "HstoreRecord needs a struct with named fields",
+        ));
+    };
+
+    let fields = named
+        .named
+        .iter()
+        .map(parse_field)
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    // Keys of flattened fields are only known once their type is, the generated `KEYS`
+    // checks those when it is evaluated
+    let mut seen = HashSet::new();
+    for field in fields.iter().filter(|field| !field.flatten) {
+        if !seen.insert(&field.key) {
+            return Err(syn::Error::new_spanned(
+                &field.ident,
+                format!("duplicate hstore key \"{}\"", field.key),
+            ));
+        }
+    }
+
+    let record = quote!(::postgres_types_extra::pg_hstore_record);
+    let hstore = quote!(::postgres_types_extra::pg_hstore::PgHstore);
+
+    let mut keys = Vec::new();
+    let mut writes = Vec::new();
+    let mut reads = Vec::new();
+    for Field {
+        ident,
+        ty,
+        key,
+        flatten,
+    } in &fields
+    {
+        if *flatten {
+            keys.push(quote!(#record::RecordKey::Flattened(
+                <#ty as #record::HstoreRecord>::KEYS
+            )));
+            writes.push(quote!(#record::HstoreRecord::write_pairs(&self.#ident, hstore);));
+            reads.push(quote!(#ident: <#ty as #record::HstoreRecord>::read_pairs(hstore)?));
+        } else {
+            keys.push(quote!(#record::RecordKey::Field(#key)));
+            writes.push(quote!(#record::write_field(hstore, #key, &self.#ident);));
+            reads.push(quote!(#ident: #record::read_field(hstore, #key)?));
+        }
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    // Generic records are only checked once they are used with a type
+    let check = if input.generics.params.is_empty() {
+        quote!(const _: &[#record::RecordKey] = <#name as #record::HstoreRecord>::KEYS;)
+    } else {
+        quote!()
+    };
+
+    Ok(quote! {
+        impl #impl_generics #record::HstoreRecord for #name #ty_generics #where_clause {
+            const DENY_UNKNOWN_KEYS: bool = #deny_unknown_keys;
+
+            const KEYS: &'static [#record::RecordKey] = #record::unique_keys(&[#(#keys),*]);
+
+            // Using KEYS evaluates it, so duplicate keys fail to compile
+            fn write_pairs(&self, hstore: &mut #hstore) {
+                let _: &[#record::RecordKey] = Self::KEYS;
+                #(#writes)*
+            }
+
+            fn read_pairs(
+                hstore: &#hstore,
+            ) -> ::std::result::Result<Self, ::std::boxed::Box<dyn ::std::error::Error>> {
+                let _: &[#record::RecordKey] = Self::KEYS;
+                ::std::result::Result::Ok(Self { #(#reads,)* })
+            }
+        }
+
+        #check
+    })
+}
+
+fn parse_field(field: &syn::Field) -> syn::Result<Field> {
+    let ident = field.ident.clone().expect("named field");
+    let mut key = None;
+    let mut flatten = false;
+
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("hstore"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                key = Some(meta.value()?.parse::<LitStr>()?.value());
+                Ok(())
+            } else if meta.path.is_ident("flatten") {
+                flatten = true;
+                Ok(())
+            } else {
+                Err(meta.error("unknown hstore attribute"))
+            }
+        })?;
+    }
+
+    if flatten && key.is_some() {
+        return Err(syn::Error::new_spanned(
+            &ident,
+            "a flattened field has no key to rename",
+        ));
+    }
+    if !flatten && !is_option(&field.ty) {
+        return Err(syn::Error::new_spanned(
+            &field.ty,
+            "HstoreRecord fields must be Option<T>, or marked #[hstore(flatten)]",
+        ));
+    }
+
+    let key = key.unwrap_or_else(|| ident.to_string().trim_start_matches("r#").to_string());
+    Ok(Field {
+        ident,
+        ty: field.ty.clone(),
+        key,
+        flatten,
+    })
+}
+
+// A NULL value has to go somewhere, so plain fields must be Option
+fn is_option(ty: &Type) -> bool {
+    let Type::Path(path) = ty else {
+        return false;
+    };
+    path.qself.is_none()
+        && path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Option")
+}
@@ -2,6 +2,10 @@ pub mod pg_box;
 pub mod pg_cidr;
 pub mod pg_circle;
 pub mod pg_hstore;
+pub mod pg_hstore_functions;
+pub mod pg_hstore_json;
+pub mod pg_hstore_parser;
+pub mod pg_hstore_record;
 pub mod pg_interval;
 pub mod pg_line;
 pub mod pg_lsn;
@@ -14,6 +18,26 @@ pub mod pg_range_formatter;
 pub mod pg_snapshot;
 pub mod pg_time_tz;
 pub mod pg_tsquery;
+pub mod pg_tsquery_analyze;
+pub mod pg_tsquery_builder;
+pub mod pg_tsquery_compare;
+pub mod pg_tsquery_construct;
+pub mod pg_tsquery_lucene;
+pub mod pg_tsquery_match;
+pub mod pg_tsquery_parser;
+pub mod pg_tsquery_rewrite;
+pub mod pg_tsquery_tree;
 pub mod pg_tsvector;
+pub mod pg_tsvector_config;
+pub mod pg_tsvector_dictionaries;
+pub mod pg_tsvector_functions;
+pub mod pg_tsvector_headline;
+pub mod pg_tsvector_index;
+pub mod pg_tsvector_ispell;
+pub mod pg_tsvector_json;
+pub mod pg_tsvector_parser;
+pub mod pg_tsvector_rank;
+pub mod pg_tsvector_stat;
+pub mod pg_tsvector_tokenizer;
 pub mod pg_xid;
 pub mod pg_xml;
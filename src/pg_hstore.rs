@@ -1,3 +1,4 @@
+use crate::pg_tsvector_rank::pg_qsort;
 use bytes::{Buf, BufMut};
 use core::str;
 use postgres_types::{FromSql, IsNull, ToSql, Type, to_sql_checked};
@@ -41,18 +42,59 @@ impl<'a> FromSql<'a> for PgHstore {
     }
 }
 
+impl PgHstore {
+    /// The pairs in the order the server stores them: shorter keys first, keys of the
+    /// same length bytewise
+    pub fn server_order(&self) -> Vec<(&String, &Option<String>)> {
+        let mut pairs: Vec<_> = self.0.iter().collect();
+        pairs.sort_by(|(a, _), (b, _)| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+        pairs
+    }
+
+    /// Drops the pairs with a duplicate key like hstoreUniquePairs in hstore_io.c: the
+    /// pairs are sorted in server order with the server's quicksort and the first pair
+    /// of each key is kept. The sort isn't stable, so that is only sure to be the first
+    /// pair given for fewer than 7 pairs.
+    pub(crate) fn from_pairs(mut pairs: Vec<(String, Option<String>)>) -> PgHstore {
+        pg_qsort(&mut pairs, &mut |(a, _), (b, _)| {
+            a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+        });
+
+        let mut hstore = PgHstore::default();
+        for (key, value) in pairs {
+            hstore.0.entry(key).or_insert(value);
+        }
+        hstore
+    }
+}
+
+// Quotes a key or value, escaping quotes and backslashes (cpw in hstore_io.c)
+fn write_quoted(f: &mut Formatter<'_>, s: &str) -> std::fmt::Result {
+    f.write_str("\"")?;
+    for c in s.chars() {
+        if matches!(c, '"' | '\\') {
+            f.write_str("\\")?;
+        }
+        write!(f, "{c}")?;
+    }
+    f.write_str("\"")
+}
+
+/// Same output as `hstore_out`, e.g. `"a"=>"1", "key"=>NULL`
 impl Display for PgHstore {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let mut hstore_arr = Vec::new();
-
-        for key in self.0.keys() {
-            if let Some(Some(val)) = self.0.get(key) {
-                hstore_arr.push(format!("\"{key}\"=>\"{val}\""));
+        for (i, (key, value)) in self.server_order().into_iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write_quoted(f, key)?;
+            f.write_str("=>")?;
+            match value {
+                Some(value) => write_quoted(f, value)?,
+                None => f.write_str("NULL")?,
             }
         }
 
-        write!(f, "{}", hstore_arr.join(", "))?;
-
         Ok(())
     }
 }
@@ -0,0 +1,266 @@
+use crate::pg_hstore::PgHstore;
+use std::error::Error;
+
+// Functions that list pairs return them in the order the server keeps them in, see
+// PgHstore::server_order, so the results compare equal to the SQL ones.
+impl PgHstore {
+    /// Same as `hstore -> key`, `None` if the key is missing or its value is NULL
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key)?.as_deref()
+    }
+
+    /// Same as `hstore -> keys`, the values in the order of the keys
+    pub fn get_all(&self, keys: &[&str]) -> Vec<Option<&str>> {
+        keys.iter().map(|key| self.get(key)).collect()
+    }
+
+    /// Same as `hstore ? key` and `exist(hstore, key)`, true for keys with a NULL value too
+    pub fn exists(&self, key: &str) -> bool {
+        self.0.contains_key(key)
+    }
+
+    /// Same as `hstore ?& keys`, true for no keys at all
+    pub fn exists_all(&self, keys: &[&str]) -> bool {
+        keys.iter().all(|key| self.exists(key))
+    }
+
+    /// Same as `hstore ?| keys`, false for no keys at all
+    pub fn exists_any(&self, keys: &[&str]) -> bool {
+        keys.iter().any(|key| self.exists(key))
+    }
+
+    /// Same as `hstore @> other`: every pair of `other` is in this one, NULL values
+    /// count as equal
+    pub fn contains(&self, other: &PgHstore) -> bool {
+        other
+            .0
+            .iter()
+            .all(|(key, value)| self.0.get(key) == Some(value))
+    }
+
+    /// Same as `hstore <@ other`
+    pub fn contained_by(&self, other: &PgHstore) -> bool {
+        other.contains(self)
+    }
+
+    /// Same as `hstore || other`, the values of `other` win for keys found in both
+    pub fn concat(&self, other: &PgHstore) -> PgHstore {
+        let mut pairs = self.0.clone();
+        pairs.extend(
+            other
+                .0
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone())),
+        );
+        PgHstore(pairs)
+    }
+
+    /// Same as `hstore - key` and `delete(hstore, key)`
+    pub fn delete(&self, key: &str) -> PgHstore {
+        self.delete_all(&[key])
+    }
+
+    /// Same as `hstore - keys`
+    pub fn delete_all(&self, keys: &[&str]) -> PgHstore {
+        self.filter_pairs(|key, _| !keys.contains(&key))
+    }
+
+    /// Same as `hstore - other`: removes the pairs that are in `other` with the same
+    /// value, keys with another value are kept
+    pub fn delete_pairs(&self, other: &PgHstore) -> PgHstore {
+        self.filter_pairs(|key, value| other.0.get(key) != Some(value))
+    }
+
+    /// Same as `slice(hstore, keys)`: only the given keys, missing ones are ignored
+    pub fn slice(&self, keys: &[&str]) -> PgHstore {
+        self.filter_pairs(|key, _| keys.contains(&key))
+    }
+
+    fn filter_pairs<F>(&self, keep: F) -> PgHstore
+    where
+        F: Fn(&str, &Option<String>) -> bool,
+    {
+        PgHstore(
+            self.0
+                .iter()
+                .filter(|(key, value)| keep(key, value))
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+        )
+    }
+
+    /// Same as `akeys(hstore)`
+    pub fn akeys(&self) -> Vec<&str> {
+        self.each().into_iter().map(|(key, _)| key).collect()
+    }
+
+    /// Same as `avals(hstore)`
+    pub fn avals(&self) -> Vec<Option<&str>> {
+        self.each().into_iter().map(|(_, value)| value).collect()
+    }
+
+    /// Same as `each(hstore)`, the rows as key and value
+    pub fn each(&self) -> Vec<(&str, Option<&str>)> {
+        self.server_order()
+            .into_iter()
+            .map(|(key, value)| (key.as_str(), value.as_deref()))
+            .collect()
+    }
+
+    /// Same as `hstore_to_array(hstore)` and `%% hstore`: keys and values alternating
+    pub fn to_array(&self) -> Vec<Option<&str>> {
+        self.each()
+            .into_iter()
+            .flat_map(|(key, value)| [Some(key), value])
+            .collect()
+    }
+
+    /// Same as `hstore_to_matrix(hstore)` and `%# hstore`: one `{key, value}` row per pair
+    pub fn to_matrix(&self) -> Vec<[Option<&str>; 2]> {
+        self.each()
+            .into_iter()
+            .map(|(key, value)| [Some(key), value])
+            .collect()
+    }
+
+    /// Same as `hstore(keys, values)`. Without values, the SQL NULL array, every value is
+    /// NULL. Duplicate keys are dropped like `hstore_in` does, see PgHstore::from_pairs.
+    pub fn from_arrays(
+        keys: &[&str],
+        values: Option<&[Option<&str>]>,
+    ) -> Result<PgHstore, Box<dyn Error>> {
+        if let Some(values) = values
+            && values.len() != keys.len()
+        {
+            return Err("Invalid hstore: arrays must have same bounds".into());
+        }
+
+        let pairs = keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| {
+                let value = values.and_then(|values| values[i]).map(str::to_string);
+                (key.to_string(), value)
+            })
+            .collect();
+
+        Ok(PgHstore::from_pairs(pairs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pg_hstore::PgHstore;
+    use std::str::FromStr;
+
+    fn hstore(input: &str) -> PgHstore {
+        PgHstore::from_str(input).unwrap()
+    }
+
+    // Expected values captured from PostgreSQL 15
+    #[test]
+    fn lookups_like_the_server() {
+        let h = hstore("a=>1, b=>NULL, c=>3");
+        assert_eq!(h.get("a"), Some("1"));
+        assert_eq!(h.get("b"), None);
+        assert_eq!(h.get("z"), None);
+        assert_eq!(
+            h.get_all(&["c", "b", "z", "a"]),
+            [Some("3"), None, None, Some("1")]
+        );
+
+        assert!(h.exists("b"));
+        assert!(!h.exists("z"));
+        assert!(h.exists_all(&["a", "b"]));
+        assert!(!h.exists_all(&["a", "z"]));
+        assert!(h.exists_all(&[]));
+        assert!(PgHstore::default().exists_all(&[]));
+        assert!(h.exists_any(&["z", "b"]));
+        assert!(!h.exists_any(&["y", "z"]));
+        assert!(!h.exists_any(&[]));
+    }
+
+    // (left, right, left @> right) captured from PostgreSQL 15
+    const CONTAINS: &[(&str, &str, bool)] = &[
+        ("a=>1, b=>NULL", "b=>NULL", true),
+        ("a=>1, b=>NULL", "b=>1", false),
+        (r#"a=>1, b=>"NULL""#, "b=>NULL", false),
+        ("a=>1", "b=>NULL", false),
+        ("a=>1", "", true),
+    ];
+
+    #[test]
+    fn contains_like_the_server() {
+        for (left, right, expected) in CONTAINS {
+            let (left, right) = (hstore(left), hstore(right));
+            assert_eq!(left.contains(&right), *expected, "{left} @> {right}");
+            assert_eq!(right.contained_by(&left), *expected, "{right} <@ {left}");
+        }
+    }
+
+    // (hstore, operation, result::text) captured from PostgreSQL 15
+    #[test]
+    fn edits_like_the_server() {
+        let h = hstore("a=>1, b=>2, c=>3");
+        let cases = [
+            (
+                hstore("a=>1, b=>2").concat(&hstore("b=>NULL, c=>3")),
+                r#""a"=>"1", "b"=>NULL, "c"=>"3""#,
+            ),
+            (h.delete("b"), r#""a"=>"1", "c"=>"3""#),
+            (h.delete_all(&["a", "c", "z"]), r#""b"=>"2""#),
+            (
+                hstore("a=>1, b=>NULL, c=>NULL, d=>4")
+                    .delete_pairs(&hstore("a=>2, b=>NULL, c=>x, d=>4")),
+                r#""a"=>"1", "c"=>NULL"#,
+            ),
+            (
+                hstore("a=>1, b=>NULL, c=>3").slice(&["b", "c", "z"]),
+                r#""b"=>NULL, "c"=>"3""#,
+            ),
+        ];
+
+        for (result, expected) in cases {
+            assert_eq!(result.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn lists_like_the_server() {
+        let h = hstore("aa=>1, b=>NULL, c=>3");
+        assert_eq!(h.akeys(), ["b", "c", "aa"]);
+        assert_eq!(h.avals(), [None, Some("3"), Some("1")]);
+        assert_eq!(h.each(), [("b", None), ("c", Some("3")), ("aa", Some("1"))]);
+
+        let h = hstore("aa=>1, b=>NULL");
+        assert_eq!(h.to_array(), [Some("b"), None, Some("aa"), Some("1")]);
+        assert_eq!(h.to_matrix(), [[Some("b"), None], [Some("aa"), Some("1")]]);
+    }
+
+    #[test]
+    fn from_arrays_like_the_server() {
+        let h = PgHstore::from_arrays(&["a", "b"], Some(&[Some("1"), None])).unwrap();
+        assert_eq!(h.to_string(), r#""a"=>"1", "b"=>NULL"#);
+
+        let h = PgHstore::from_arrays(&["a", "b"], None).unwrap();
+        assert_eq!(h.to_string(), r#""a"=>NULL, "b"=>NULL"#);
+
+        let error = PgHstore::from_arrays(&["a", "b"], Some(&[Some("1")])).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid hstore: arrays must have same bounds"
+        );
+
+        let h = PgHstore::from_arrays(&["a", "a", "b"], Some(&[Some("1"), Some("2"), Some("3")]))
+            .unwrap();
+        assert_eq!(h.to_string(), r#""a"=>"1", "b"=>"3""#);
+
+        let keys = ["b", "h", "f", "d", "d", "f", "d", "g", "f", "c", "g"];
+        let values = ["0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "10"].map(Some);
+        let h = PgHstore::from_arrays(&keys, Some(&values)).unwrap();
+        assert_eq!(
+            h.to_string(),
+            r#""b"=>"0", "c"=>"9", "d"=>"6", "f"=>"5", "g"=>"10", "h"=>"1""#
+        );
+    }
+}
@@ -0,0 +1,311 @@
+use crate::pg_hstore::PgHstore;
+use crate::pg_tsvector_json::{number_text, numeric_text};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::error::Error;
+
+impl PgHstore {
+    /// Same as `hstore_to_json(hstore)` and `hstore_to_jsonb(hstore)`: every value is a
+    /// string, or null. The keys are in the order of the serde_json map, use
+    /// [`PgHstore::to_json_text`] for the order the server lists them in.
+    pub fn to_json(&self) -> Value {
+        self.to_json_with(|value| Value::String(value.to_string()))
+    }
+
+    /// Same as `hstore_to_json_loose(hstore)`: values that are valid JSON numbers become
+    /// numbers, `t` and `f` become booleans. Numbers keep their text like in `json`, so
+    /// `1.5e3` stays `1.5e3`, with the `arbitrary_precision` feature; without it they
+    /// are parsed into serde_json numbers and those out of the range of f64 stay strings.
+    pub fn to_json_loose(&self) -> Value {
+        self.to_json_with(|value| loose_value(value, |number| number.to_string()))
+    }
+
+    /// Same as `hstore_to_jsonb_loose(hstore)`: like [`PgHstore::to_json_loose`], but
+    /// numbers are written the way `jsonb` prints them, so `1.5e3` becomes `1500`.
+    pub fn to_jsonb_loose(&self) -> Value {
+        self.to_json_with(|value| loose_value(value, numeric_text))
+    }
+
+    /// The text of `hstore_to_json(hstore)` and `hstore_to_jsonb(hstore)` as the server
+    /// writes it, with the keys in server order
+    pub fn to_json_text(&self) -> String {
+        self.json_text(json_string)
+    }
+
+    /// The text of `hstore_to_json_loose(hstore)` as the server writes it
+    pub fn to_json_loose_text(&self) -> String {
+        self.json_text(|value| loose_text(value, |number| number.to_string()))
+    }
+
+    /// The text of `hstore_to_jsonb_loose(hstore)` as the server writes it
+    pub fn to_jsonb_loose_text(&self) -> String {
+        self.json_text(|value| loose_text(value, numeric_text))
+    }
+
+    fn to_json_with<F>(&self, convert: F) -> Value
+    where
+        F: Fn(&str) -> Value,
+    {
+        let object: Map<String, Value> = self
+            .server_order()
+            .into_iter()
+            .map(|(key, value)| {
+                let value = value.as_deref().map_or(Value::Null, &convert);
+                (key.clone(), value)
+            })
+            .collect();
+
+        Value::Object(object)
+    }
+
+    // The pairs separated by `, ` and the keys by `: ` from the values, like
+    // hstore_to_json and JsonbToCString do
+    fn json_text<F>(&self, convert: F) -> String
+    where
+        F: Fn(&str) -> String,
+    {
+        let pairs: Vec<String> = self
+            .server_order()
+            .into_iter()
+            .map(|(key, value)| {
+                let value = value.as_deref().map_or("null".to_string(), &convert);
+                format!("{}: {value}", json_string(key))
+            })
+            .collect();
+
+        format!("{{{}}}", pairs.join(", "))
+    }
+
+    /// The pairs of a flat JSON object, the text of the values like `jsonb_each_text`
+    /// returns them: null becomes NULL, booleans are written out and numbers are written
+    /// the way `jsonb` prints them, so `1e3` becomes `1000`. Keeping the scale of `1.50`
+    /// needs the `arbitrary_precision` feature. Arrays and objects as values are an error.
+    pub fn from_json(json: &Value) -> Result<PgHstore, Box<dyn Error>> {
+        let Value::Object(object) = json else {
+            return Err("Invalid hstore: expected a JSON object".into());
+        };
+
+        let mut pairs = BTreeMap::new();
+        for (key, value) in object {
+            let value = match value {
+                Value::Null => None,
+                Value::String(s) => Some(s.clone()),
+                Value::Bool(b) => Some(b.to_string()),
+                Value::Number(n) => Some(numeric_text(&number_text(n))),
+                Value::Array(_) | Value::Object(_) => {
+                    return Err(format!(
+                        "Invalid hstore: value of key \"{key}\" is nested, only scalar values are allowed"
+                    )
+                    .into());
+                }
+            };
+            pairs.insert(key.clone(), value);
+        }
+
+        Ok(PgHstore(pairs))
+    }
+}
+
+impl From<&PgHstore> for Value {
+    fn from(hstore: &PgHstore) -> Self {
+        hstore.to_json()
+    }
+}
+
+impl TryFrom<&Value> for PgHstore {
+    type Error = Box<dyn Error>;
+
+    fn try_from(json: &Value) -> Result<Self, Self::Error> {
+        PgHstore::from_json(json)
+    }
+}
+
+// A value of the loose conversions: `t` and `f` are booleans and valid JSON numbers are
+// numbers, written by `number`
+fn loose_value<F>(value: &str, number: F) -> Value
+where
+    F: Fn(&str) -> String,
+{
+    match value {
+        "t" => Value::Bool(true),
+        "f" => Value::Bool(false),
+        _ if is_json_number(value) => json_number(number(value)),
+        _ => Value::String(value.to_string()),
+    }
+}
+
+fn loose_text<F>(value: &str, number: F) -> String
+where
+    F: Fn(&str) -> String,
+{
+    match value {
+        "t" => "true".to_string(),
+        "f" => "false".to_string(),
+        _ if is_json_number(value) => number(value),
+        _ => json_string(value),
+    }
+}
+
+// The number keeps the text as is with the `arbitrary_precision` feature, parsing it would
+// write `1E3` as `1e+3`
+#[cfg(feature = "arbitrary_precision")]
+fn json_number(text: String) -> Value {
+    Value::Number(serde_json::Number::from_string_unchecked(text))
+}
+
+// Numbers out of the range of f64, like `1e400`, stay strings
+#[cfg(not(feature = "arbitrary_precision"))]
+fn json_number(text: String) -> Value {
+    match serde_json::from_str(&text) {
+        Ok(number) => Value::Number(number),
+        Err(_) => Value::String(text),
+    }
+}
+
+// Whether the value is exactly a JSON number, without surrounding whitespace and whatever
+// its size (IsValidJsonNumber in json.c)
+fn is_json_number(value: &str) -> bool {
+    fn digits(s: &str) -> usize {
+        s.len() - s.trim_start_matches(|c: char| c.is_ascii_digit()).len()
+    }
+
+    let rest = value.strip_prefix('-').unwrap_or(value);
+    let int = digits(rest);
+    if int == 0 || (int > 1 && rest.starts_with('0')) {
+        return false;
+    }
+
+    let mut rest = &rest[int..];
+    if let Some(frac) = rest.strip_prefix('.') {
+        let len = digits(frac);
+        if len == 0 {
+            return false;
+        }
+        rest = &frac[len..];
+    }
+    if let Some(exponent) = rest.strip_prefix(['e', 'E']) {
+        let exponent = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+        let len = digits(exponent);
+        if len == 0 {
+            return false;
+        }
+        rest = &exponent[len..];
+    }
+
+    rest.is_empty()
+}
+
+// A quoted JSON string, escaped like escape_json does
+fn json_string(s: &str) -> String {
+    Value::String(s.to_string()).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pg_hstore::PgHstore;
+    use std::str::FromStr;
+
+    const HSTORE: &str = r#"big=>123456789012345678901234567890, m=>12.10, e=>1.5e3, t=>t,
+        f=>f, x=>-0, y=>01, z=>"1 ", aa=>NULL, ccc=>1E+2, b=>-1.0e-2"#;
+
+    // hstore_to_json, hstore_to_json_loose and hstore_to_jsonb_loose captured from
+    // PostgreSQL 15
+    const JSON: &str = r#"{"b": "-1.0e-2", "e": "1.5e3", "f": "f", "m": "12.10", "t": "t", "x": "-0", "y": "01", "z": "1 ", "aa": null, "big": "123456789012345678901234567890", "ccc": "1E+2"}"#;
+    const JSON_LOOSE: &str = r#"{"b": -1.0e-2, "e": 1.5e3, "f": false, "m": 12.10, "t": true, "x": -0, "y": "01", "z": "1 ", "aa": null, "big": 123456789012345678901234567890, "ccc": 1E+2}"#;
+    const JSONB_LOOSE: &str = r#"{"b": -0.010, "e": 1500, "f": false, "m": 12.10, "t": true, "x": 0, "y": "01", "z": "1 ", "aa": null, "big": 123456789012345678901234567890, "ccc": 100}"#;
+
+    // The object written with the keys in server order
+    #[cfg(feature = "arbitrary_precision")]
+    fn server_text(json: &serde_json::Value) -> String {
+        let mut pairs: Vec<_> = json.as_object().unwrap().iter().collect();
+        pairs.sort_by(|(a, _), (b, _)| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+
+        let pairs: Vec<String> = pairs
+            .into_iter()
+            .map(|(key, value)| format!("{}: {value}", serde_json::Value::from(key.as_str())))
+            .collect();
+        format!("{{{}}}", pairs.join(", "))
+    }
+
+    #[test]
+    fn to_json_like_the_server() {
+        let hstore = PgHstore::from_str(HSTORE).unwrap();
+        assert_eq!(hstore.to_json_text(), JSON);
+        assert_eq!(hstore.to_json_loose_text(), JSON_LOOSE);
+        assert_eq!(hstore.to_jsonb_loose_text(), JSONB_LOOSE);
+
+        // hstore_to_json(hstore('k"\', E'a\x01/\n\t é'))
+        let pairs = [("k\"\\".to_string(), Some("a\u{1}/\n\t é".to_string()))];
+        let escaped = PgHstore(pairs.into_iter().collect());
+        assert_eq!(escaped.to_json_text(), r#"{"k\"\\": "a\u0001/\n\t é"}"#);
+    }
+
+    #[test]
+    #[cfg(feature = "arbitrary_precision")]
+    fn to_json_values_like_the_server() {
+        let hstore = PgHstore::from_str(HSTORE).unwrap();
+        assert_eq!(server_text(&hstore.to_json()), JSON);
+        assert_eq!(server_text(&hstore.to_json_loose()), JSON_LOOSE);
+        assert_eq!(server_text(&hstore.to_jsonb_loose()), JSONB_LOOSE);
+    }
+
+    #[test]
+    #[cfg(feature = "arbitrary_precision")]
+    fn from_json_keeps_exact_numbers() {
+        let json = r#"{"a": 1e3, "b": 1.50, "c": true, "d": -0.0e1, "e": null, "f": "x"}"#;
+        let hstore = PgHstore::from_json(&serde_json::from_str(json).unwrap()).unwrap();
+        assert_eq!(
+            hstore.to_string(),
+            r#""a"=>"1000", "b"=>"1.50", "c"=>"true", "d"=>"0", "e"=>NULL, "f"=>"x""#
+        );
+    }
+
+    #[test]
+    fn from_json_like_jsonb_each_text() {
+        let json = r#"{"a": 1e3, "b": 1.5, "c": true, "e": null, "f": "x", "g": -7}"#;
+        let hstore = PgHstore::from_json(&serde_json::from_str(json).unwrap()).unwrap();
+        assert_eq!(
+            hstore.to_string(),
+            r#""a"=>"1000", "b"=>"1.5", "c"=>"true", "e"=>NULL, "f"=>"x", "g"=>"-7""#
+        );
+
+        let errors = [
+            (
+                r#"{"a": "x", "b": [1]}"#,
+                r#"Invalid hstore: value of key "b" is nested, only scalar values are allowed"#,
+            ),
+            (
+                r#"{"c": {"d": 1}}"#,
+                r#"Invalid hstore: value of key "c" is nested, only scalar values are allowed"#,
+            ),
+            ("[1]", "Invalid hstore: expected a JSON object"),
+        ];
+        for (json, expected) in errors {
+            let error = PgHstore::from_json(&serde_json::from_str(json).unwrap()).unwrap_err();
+            assert_eq!(error.to_string(), expected, "{json}");
+        }
+    }
+
+    // hstore_to_json_loose and hstore_to_jsonb_loose captured from PostgreSQL 15
+    #[test]
+    fn loose_numbers_like_the_server() {
+        let hstore = PgHstore::from_str("a=>1e400, b=>1.e1, c=>.5, d=>1e, e=>-, f=>00").unwrap();
+        let strings = r#""b": "1.e1", "c": ".5", "d": "1e", "e": "-", "f": "00""#;
+        assert_eq!(
+            hstore.to_json_loose_text(),
+            format!(r#"{{"a": 1e400, {strings}}}"#)
+        );
+        assert_eq!(
+            hstore.to_jsonb_loose_text(),
+            format!(r#"{{"a": 1{}, {strings}}}"#, "0".repeat(400))
+        );
+
+        // f64 can't hold it, so without arbitrary_precision it stays a string
+        let loose = hstore.to_json_loose();
+        #[cfg(feature = "arbitrary_precision")]
+        assert_eq!(loose["a"].to_string(), "1e400");
+        #[cfg(not(feature = "arbitrary_precision"))]
+        assert_eq!(loose["a"], serde_json::Value::from("1e400"));
+        assert_eq!(loose["b"], serde_json::Value::from("1.e1"));
+    }
+}
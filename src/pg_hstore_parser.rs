@@ -0,0 +1,194 @@
+use crate::pg_hstore::PgHstore;
+use std::error::Error;
+use std::iter::Peekable;
+use std::str::CharIndices;
+use std::str::FromStr;
+
+/// Parses the text format of hstore like `hstore_in`.
+///
+/// Keys and values are either double quoted or unquoted, in both a backslash escapes
+/// the next character. An unquoted `NULL` in any case is a null value. Whitespace
+/// around keys, values, `=>` and commas is ignored. Of duplicate keys the server keeps
+/// the one its unstable sort puts first, which is the first one for fewer than 7 pairs.
+///
+/// ```
+/// use postgres_types_extra::pg_hstore::PgHstore;
+/// use std::str::FromStr;
+///
+/// let hstore = PgHstore::from_str(r#"a=>1, "b c"=>NULL, a=>2"#)?;
+/// assert_eq!(hstore.to_string(), r#""a"=>"1", "b c"=>NULL"#);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+impl FromStr for PgHstore {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            chars: s.char_indices().peekable(),
+        };
+        let mut pairs = Vec::new();
+
+        // parse_hstore in hstore_io.c
+        while let Some((key, _)) = parser.token(false)? {
+            parser.skip_whitespace();
+            parser.expect('=')?;
+            parser.expect('>')?;
+
+            let (value, quoted) = parser
+                .token(true)?
+                .ok_or("Invalid hstore: unexpected end of string")?;
+            let value = if !quoted && value.eq_ignore_ascii_case("null") {
+                None
+            } else {
+                Some(value)
+            };
+            pairs.push((key, value));
+
+            parser.skip_whitespace();
+            match parser.chars.next() {
+                None => break,
+                Some((_, ',')) => {}
+                Some((pos, c)) => return Err(syntax_error(pos, c)),
+            }
+        }
+
+        Ok(PgHstore::from_pairs(pairs))
+    }
+}
+
+struct Parser<'a> {
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|(_, c)| is_space(*c)).is_some() {}
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), Box<dyn Error>> {
+        match self.chars.next() {
+            Some((_, c)) if c == expected => Ok(()),
+            Some((pos, c)) => Err(syntax_error(pos, c)),
+            None => Err("Invalid hstore: unexpected end of string".into()),
+        }
+    }
+
+    fn escaped(&mut self) -> Result<char, Box<dyn Error>> {
+        self.chars
+            .next()
+            .map(|(_, c)| c)
+            .ok_or_else(|| "Invalid hstore: unexpected end of string".into())
+    }
+
+    // A key or value and whether it was quoted, `None` at the end of the input. An
+    // unquoted key ends at `=`, an unquoted value at a comma after its first character
+    // (get_val in hstore_io.c).
+    fn token(&mut self, value: bool) -> Result<Option<(String, bool)>, Box<dyn Error>> {
+        self.skip_whitespace();
+
+        let mut token = String::new();
+        match self.chars.peek().copied() {
+            None => return Ok(None),
+            Some((_, '"')) => {
+                self.chars.next();
+                loop {
+                    match self.chars.next() {
+                        None => return Err("Invalid hstore: unexpected end of string".into()),
+                        Some((_, '\\')) => token.push(self.escaped()?),
+                        Some((_, '"')) => return Ok(Some((token, true))),
+                        Some((_, c)) => token.push(c),
+                    }
+                }
+            }
+            Some((pos, '=')) if !value => return Err(syntax_error(pos, '=')),
+            Some(_) => {}
+        }
+
+        while let Some((_, c)) = self.chars.peek().copied() {
+            match c {
+                '=' if !value => break,
+                ',' if value && !token.is_empty() => break,
+                c if is_space(c) => {
+                    self.chars.next();
+                    break;
+                }
+                '\\' => {
+                    self.chars.next();
+                    token.push(self.escaped()?);
+                }
+                c => {
+                    self.chars.next();
+                    token.push(c);
+                }
+            }
+        }
+
+        Ok(Some((token, false)))
+    }
+}
+
+fn syntax_error(pos: usize, c: char) -> Box<dyn Error> {
+    format!("Invalid hstore: syntax error near \"{c}\" at position {pos}").into()
+}
+
+// scanner_isspace in scansup.c
+fn is_space(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n' | '\r' | '\x0b' | '\x0c')
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pg_hstore::PgHstore;
+    use std::str::FromStr;
+
+    // (input, input::hstore) captured from PostgreSQL 15
+    const CASES: &[(&str, &str)] = &[
+        ("a=>1,b=>2", r#""a"=>"1", "b"=>"2""#),
+        (" a => 1 , b => 2 ", r#""a"=>"1", "b"=>"2""#),
+        ("a=>,b=>1", r#""a"=>",b=>1""#),
+        ("a=>,", r#""a"=>",""#),
+        (",a=>1", r#"",a"=>"1""#),
+        ("a=>1,,b=>2", r#""a"=>"1", ",b"=>"2""#),
+        ("a=>b=>c", r#""a"=>"b=>c""#),
+        (r"a=>\,b", r#""a"=>",b""#),
+        (r"a\=b=>c\,d", r#""a=b"=>"c,d""#),
+        (
+            r#""a b"=>"c\"d", e=>NULL, f=>"NULL""#,
+            r#""e"=>NULL, "f"=>"NULL", "a b"=>"c\"d""#,
+        ),
+        ("a=>null,b=>NuLl", r#""a"=>NULL, "b"=>NULL"#),
+        ("a=>1, a=>2", r#""a"=>"1""#),
+        (
+            "b=>0,h=>1,f=>2,d=>3,d=>4,f=>5,d=>6,g=>7,f=>8,c=>9,g=>10",
+            r#""b"=>"0", "c"=>"9", "d"=>"6", "f"=>"5", "g"=>"10", "h"=>"1""#,
+        ),
+        ("k=>v,", r#""k"=>"v""#),
+    ];
+
+    // Inputs the server rejects
+    const ERRORS: &[&str] = &[
+        "a=>",
+        "a=1",
+        "=>1",
+        r#""a=>1"#,
+        "a=>1 b=>2",
+        r#"a=>"1"x"#,
+        r"a=>\",
+        r#""a"=>"",b=>x y"#,
+    ];
+
+    #[test]
+    fn parses_like_hstore_in() {
+        for (input, expected) in CASES {
+            let hstore = PgHstore::from_str(input).unwrap();
+            assert_eq!(hstore.to_string(), *expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn rejects_what_the_server_rejects() {
+        for input in ERRORS {
+            assert!(PgHstore::from_str(input).is_err(), "{input}");
+        }
+    }
+}
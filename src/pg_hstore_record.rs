@@ -0,0 +1,225 @@
+use crate::pg_hstore::PgHstore;
+use std::error::Error;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// Derives [`HstoreRecord`] for a struct with named `Option<T>` fields, where `T`
+/// implements `FromStr` and `Display`. Needs the `derive` feature.
+///
+/// - `#[hstore(rename = "key")]` on a field uses another key than the field name, two
+///   fields with the same key don't compile
+/// - `#[hstore(flatten)]` on a field of a type that is itself a record reads and writes
+///   its keys next to the others. A key that is used twice across flattened fields
+///   doesn't compile either, for generic structs once they are used with a type
+/// - `#[hstore(deny_unknown_keys)]` on the struct makes [`HstoreRecord::from_hstore`]
+///   reject keys that are not fields
+///
+/// ```
+/// use postgres_types_extra::pg_hstore::PgHstore;
+/// use postgres_types_extra::pg_hstore_record::HstoreRecord;
+/// use std::str::FromStr;
+///
+/// #[derive(Debug, PartialEq, HstoreRecord)]
+/// struct Limits {
+///     max_rows: Option<u32>,
+/// }
+///
+/// #[derive(Debug, PartialEq, HstoreRecord)]
+/// #[hstore(deny_unknown_keys)]
+/// struct Settings {
+///     #[hstore(rename = "page-size")]
+///     page_size: Option<u32>,
+///     theme: Option<String>,
+///     #[hstore(flatten)]
+///     limits: Limits,
+/// }
+///
+/// let hstore = PgHstore::from_str(r#""page-size"=>"20", theme=>NULL, max_rows=>"7""#)?;
+/// let settings = Settings::from_hstore(&hstore)?;
+/// assert_eq!(settings.page_size, Some(20));
+/// assert_eq!(settings.limits, Limits { max_rows: Some(7) });
+/// assert_eq!(settings.to_hstore(), hstore);
+///
+/// let unknown = PgHstore::from_str("theme=>dark, color=>red")?;
+/// assert!(Settings::from_hstore(&unknown).is_err());
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+///
+/// ```compile_fail
+/// # use postgres_types_extra::pg_hstore_record::HstoreRecord;
+/// #[derive(HstoreRecord)]
+/// struct Limits {
+///     max_rows: Option<u32>,
+/// }
+///
+/// #[derive(HstoreRecord)]
+/// struct Settings {
+///     max_rows: Option<u32>,
+///     #[hstore(flatten)]
+///     limits: Limits,
+/// }
+/// ```
+#[cfg(feature = "derive")]
+pub use postgres_types_extra_derive::HstoreRecord;
+
+/// A struct stored as hstore pairs, one key per field, like `hstore(record)` and
+/// `populate_record(record, hstore)` convert a row type.
+///
+/// A `None` field is written as a NULL value, a missing key or a NULL value is read as
+/// `None`.
+pub trait HstoreRecord: Sized {
+    /// Whether [`HstoreRecord::from_hstore`] rejects keys that are not fields.
+    /// `populate_record` ignores them, which is the default.
+    const DENY_UNKNOWN_KEYS: bool = false;
+
+    /// The keys of the fields in declaration order. The derived implementation checks
+    /// at compile time that no key is used twice, see [`unique_keys`].
+    const KEYS: &'static [RecordKey];
+
+    /// The keys of the fields, including those of flattened fields
+    fn keys() -> Vec<&'static str> {
+        let mut keys = Vec::new();
+        collect_keys(Self::KEYS, &mut keys);
+        keys
+    }
+
+    /// Adds the pairs of the fields to `hstore`
+    fn write_pairs(&self, hstore: &mut PgHstore);
+
+    /// Reads the fields from `hstore`, other keys are ignored
+    fn read_pairs(hstore: &PgHstore) -> Result<Self, Box<dyn Error>>;
+
+    /// Same as `hstore(record)`
+    fn to_hstore(&self) -> PgHstore {
+        let mut hstore = PgHstore::default();
+        self.write_pairs(&mut hstore);
+        hstore
+    }
+
+    /// Same as `populate_record(NULL::record, hstore)`, unknown keys are an error if
+    /// [`HstoreRecord::DENY_UNKNOWN_KEYS`] is set
+    fn from_hstore(hstore: &PgHstore) -> Result<Self, Box<dyn Error>> {
+        if Self::DENY_UNKNOWN_KEYS {
+            Self::from_hstore_strict(hstore)
+        } else {
+            Self::read_pairs(hstore)
+        }
+    }
+
+    /// Like [`HstoreRecord::from_hstore`], but every key must be a field
+    fn from_hstore_strict(hstore: &PgHstore) -> Result<Self, Box<dyn Error>> {
+        let keys = Self::keys();
+        if let Some(key) = hstore.0.keys().find(|key| !keys.contains(&key.as_str())) {
+            return Err(format!("Invalid hstore: unknown key \"{key}\"").into());
+        }
+
+        Self::read_pairs(hstore)
+    }
+}
+
+/// A key of [`HstoreRecord::KEYS`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordKey {
+    /// The key of a field
+    Field(&'static str),
+    /// The keys of a flattened field
+    Flattened(&'static [RecordKey]),
+}
+
+fn collect_keys(keys: &[RecordKey], out: &mut Vec<&'static str>) {
+    for key in keys {
+        match key {
+            RecordKey::Field(key) => out.push(key),
+            RecordKey::Flattened(keys) => collect_keys(keys, out),
+        }
+    }
+}
+
+/// Returns `keys`, panics if a key is used twice. The derived implementations call it
+/// to initialize [`HstoreRecord::KEYS`], which turns the panic into a compile error.
+pub const fn unique_keys(keys: &'static [RecordKey]) -> &'static [RecordKey] {
+    let len = key_count(keys);
+    let mut i = 0;
+    while i < len {
+        let mut j = 0;
+        while j < i {
+            if str_eq(key_at(keys, i), key_at(keys, j)) {
+                panic!("duplicate hstore key");
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    keys
+}
+
+const fn key_count(keys: &[RecordKey]) -> usize {
+    let mut count = 0;
+    let mut i = 0;
+    while i < keys.len() {
+        count += match keys[i] {
+            RecordKey::Field(_) => 1,
+            RecordKey::Flattened(keys) => key_count(keys),
+        };
+        i += 1;
+    }
+    count
+}
+
+// The key at `index` in the order of HstoreRecord::keys
+const fn key_at(keys: &[RecordKey], mut index: usize) -> &'static str {
+    let mut i = 0;
+    loop {
+        match keys[i] {
+            RecordKey::Field(key) if index == 0 => return key,
+            RecordKey::Field(_) => index -= 1,
+            RecordKey::Flattened(keys) => {
+                let count = key_count(keys);
+                if index < count {
+                    return key_at(keys, index);
+                }
+                index -= count;
+            }
+        }
+        i += 1;
+    }
+}
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+#[doc(hidden)]
+pub fn write_field<T: Display>(hstore: &mut PgHstore, key: &str, value: &Option<T>) {
+    hstore
+        .0
+        .insert(key.to_string(), value.as_ref().map(T::to_string));
+}
+
+#[doc(hidden)]
+pub fn read_field<T>(hstore: &PgHstore, key: &str) -> Result<Option<T>, Box<dyn Error>>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    hstore
+        .get(key)
+        .map(|value| {
+            value.parse().map_err(|e| {
+                format!("Invalid hstore: value of key \"{key}\" is not valid: {e}").into()
+            })
+        })
+        .transpose()
+}
@@ -72,12 +72,12 @@ use std::error::Error;
 use std::io::{BufRead, Cursor};
 use std::{fmt, str};
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct PgTsQuery {
     pub entries: Vec<Entry>,
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug, PartialOrd)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, PartialOrd, Hash)]
 pub enum Operators {
     Not = 1,
     And = 2,
@@ -85,6 +85,18 @@ pub enum Operators {
     Phrase = 4,
 }
 
+impl Operators {
+    /// Binding strength of the operator (tsearch_op_priority in tsquery.c)
+    pub fn priority(self) -> i32 {
+        match self {
+            Operators::Not => 4,
+            Operators::Phrase => 3,
+            Operators::And => 2,
+            Operators::Or => 1,
+        }
+    }
+}
+
 impl From<Operators> for i8 {
     fn from(val: Operators) -> Self {
         match val {
@@ -110,14 +122,14 @@ impl TryFrom<i8> for Operators {
     }
 }
 
-#[derive(Clone, Debug, Copy, PartialEq)]
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Hash)]
 pub struct Operator {
     pub operator: Operators,
     pub distance: Option<i16>,
 }
 
 #[allow(dead_code)]
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Value {
     pub weight: u8,
     pub text: String,
@@ -125,7 +137,22 @@ pub struct Value {
     pub distance: i16,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+impl Value {
+    pub fn new(text: impl Into<String>, weight: u8, prefix: bool) -> Self {
+        let text = text.into();
+        // Same as what the binary decoder stores: length of the operand plus its terminator
+        let distance = i16::try_from(text.len() + 1).unwrap_or(i16::MAX);
+
+        Value {
+            weight,
+            text,
+            prefix: prefix.into(),
+            distance,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Entry {
     Operator(Operator),
     Value(Value),
@@ -171,8 +198,8 @@ impl<'a> FromSql<'a> for PgTsQuery {
 
 impl fmt::Display for PgTsQuery {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", infix_string(self.entries.clone()))?;
-        Ok(())
+        // An empty query prints as an empty string, same as the server
+        f.write_str(&infix(&self.entries))
     }
 }
 
@@ -257,123 +284,186 @@ impl TryFrom<&[u8]> for PgTsQuery {
             }
         }
 
+        if tree_depth(entries.iter().rev().map(Entry::arity)) > MAX_QUERY_DEPTH {
+            return Err("Invalid tsquery: stack depth limit exceeded".into());
+        }
+
         Ok(PgTsQuery { entries })
     }
 }
 
-fn infix_string(mut entries: Vec<Entry>) -> String {
-    // println!("{:?}", entries);
-    let mut stack: Vec<String> = Vec::new();
-    let len = entries.len().to_u32().unwrap();
-    let mut priority = 0;
-
-    for num in 0..len {
-        match entries.pop() {
-            Some(entry) => match entry {
-                Entry::Operator(op) => match op.operator {
-                    Operators::Not => {
-                        if let Some(operand) = stack.pop() {
-                            stack.push(format!("!{operand}"));
-                            priority = 1;
-                        }
-                    }
-                    Operators::And => {
-                        if let (Some(right), Some(left)) = (stack.pop(), stack.pop()) {
-                            match 2.cmp(&priority) {
-                                std::cmp::Ordering::Less => {
-                                    // println!("Less");
-                                    stack.push(format!("{left} & {right}"));
-                                }
-                                std::cmp::Ordering::Equal => {
-                                    // println!("Equal");
-
-                                    stack.push(format!("{left} & {right}"));
-                                }
-                                std::cmp::Ordering::Greater => {
-                                    // println!("Greater");
-
-                                    if num == len - 1 {
-                                        stack.push(format!("{left} & {right}"));
-                                    } else {
-                                        stack.push(format!("({left} & {right})"));
-                                    }
-                                }
-                            }
-                            priority = 2;
-                        }
-                    }
-                    Operators::Or => {
-                        if let (Some(right), Some(left)) = (stack.pop(), stack.pop()) {
-                            match 3.cmp(&priority) {
-                                std::cmp::Ordering::Less => {
-                                    stack.push(format!("{left} | {right}"));
-                                }
-                                std::cmp::Ordering::Equal => {
-                                    stack.push(format!("{left} | {right}"));
-                                }
-                                std::cmp::Ordering::Greater => {
-                                    if num == len - 1 {
-                                        stack.push(format!("{left} | {right}"));
-                                    } else {
-                                        stack.push(format!("({left} | {right})"));
-                                    }
-                                }
-                            }
-                            priority = 3;
-                        }
-                    }
+/// Deepest query tree that is accepted when parsing or decoding a query, counting the
+/// operands as a level. The server has no fixed limit but fails with "stack depth limit
+/// exceeded" once its recursion uses up `max_stack_depth`, at around ten thousand
+/// levels. The functions working on [`crate::pg_tsquery_tree::TsQueryNode`] recurse as
+/// well and need to stay within the 2 MB stack of a spawned thread, so deeper trees are
+/// rejected.
+pub const MAX_QUERY_DEPTH: usize = 1000;
+
+/// Depth of the tree formed by nodes with the given number of operands, in reverse polish
+/// order. Missing operands count as nothing.
+pub(crate) fn tree_depth(arities: impl Iterator<Item = usize>) -> usize {
+    let mut stack: Vec<usize> = Vec::new();
+    for arity in arities {
+        let depth = (0..arity)
+            .map(|_| stack.pop().unwrap_or(0))
+            .max()
+            .unwrap_or(0);
+        stack.push(depth + 1);
+    }
+
+    stack.into_iter().max().unwrap_or(0)
+}
+
+impl Entry {
+    // Number of operands of the entry
+    pub(crate) fn arity(&self) -> usize {
+        match self {
+            Entry::Value(_) => 0,
+            Entry::Operator(op) if op.operator == Operators::Not => 1,
+            Entry::Operator(_) => 2,
+        }
+    }
+}
+
+enum Step {
+    /// Print the entry at the current position
+    Node {
+        parent_priority: i32,
+        right_phrase: bool,
+    },
+    /// Combine the printed operands of a NOT
+    Not { parenthesize: bool },
+    /// Combine the printed right and left operand of a binary operator
+    Binary {
+        operator: Operator,
+        parent_priority: i32,
+        right_phrase: bool,
+    },
+}
 
-                    Operators::Phrase => {
-                        if let (Some(right), Some(left)) = (stack.pop(), stack.pop()) {
-                            let op_str = match op.distance {
-                                Some(1) | None => "<->".to_string(),
-                                Some(distance) => format!("<{distance}>"),
-                            };
-                            stack.push(format!("{left} {op_str} {right}"));
-                            priority = 4;
-                        }
+/// Prints the entries the same way `infix()` in tsquery.c does.
+///
+/// Entries are stored as operator, right operand, left operand, so the right operand is
+/// printed first and appended after the left one. An explicit stack is used instead of
+/// recursion so that deep queries can't overflow the stack.
+fn infix(entries: &[Entry]) -> String {
+    let mut pos = 0;
+    let mut steps = vec![Step::Node {
+        parent_priority: -1,
+        right_phrase: false,
+    }];
+    let mut printed: Vec<String> = Vec::new();
+
+    while let Some(step) = steps.pop() {
+        match step {
+            Step::Node {
+                parent_priority,
+                right_phrase,
+            } => {
+                let Some(entry) = entries.get(pos) else {
+                    printed.push(String::new());
+                    continue;
+                };
+                pos += 1;
+
+                match entry {
+                    Entry::Value(val) => printed.push(operand(val)),
+                    Entry::Operator(op) if op.operator == Operators::Not => {
+                        let priority = op.operator.priority();
+                        steps.push(Step::Not {
+                            parenthesize: priority < parent_priority,
+                        });
+                        steps.push(Step::Node {
+                            parent_priority: priority,
+                            right_phrase: false,
+                        });
                     }
-                },
-                Entry::Value(val) => {
-                    // println!("{:?}", val);
-                    if val.prefix > 0 {
-                        stack.push(format!("'{}':*", val.text));
-                    } else if let Some(weight) = weight_to_string(val.weight) {
-                        stack.push(format!("'{}':{}", val.text, weight));
-                    } else {
-                        stack.push(format!("'{}'", val.text));
+                    Entry::Operator(op) => {
+                        let priority = op.operator.priority();
+                        steps.push(Step::Binary {
+                            operator: *op,
+                            parent_priority,
+                            right_phrase,
+                        });
+                        steps.push(Step::Node {
+                            parent_priority: priority,
+                            right_phrase: false,
+                        });
+                        steps.push(Step::Node {
+                            parent_priority: priority,
+                            right_phrase: op.operator == Operators::Phrase,
+                        });
                     }
                 }
-            },
-            None => {
-                eprintln!("No entry after popped!");
+            }
+            Step::Not { parenthesize } => {
+                let operand = printed.pop().unwrap_or_default();
+                printed.push(if parenthesize {
+                    format!("( !{operand} )")
+                } else {
+                    format!("!{operand}")
+                });
+            }
+            Step::Binary {
+                operator,
+                parent_priority,
+                right_phrase,
+            } => {
+                let left = printed.pop().unwrap_or_default();
+                let right = printed.pop().unwrap_or_default();
+                let is_phrase = operator.operator == Operators::Phrase;
+
+                let op_str = match operator.operator {
+                    Operators::Or => "|".to_string(),
+                    Operators::And => "&".to_string(),
+                    _ => match operator.distance.unwrap_or(1) {
+                        1 => "<->".to_string(),
+                        distance => format!("<{distance}>"),
+                    },
+                };
+
+                // phrase operator depends on order
+                printed.push(
+                    if operator.operator.priority() < parent_priority || (is_phrase && right_phrase)
+                    {
+                        format!("( {left} {op_str} {right} )")
+                    } else {
+                        format!("{left} {op_str} {right}")
+                    },
+                );
             }
         }
     }
 
-    stack.join(" ").to_string()
+    printed.pop().unwrap_or_default()
 }
 
-fn weight_to_string(weight: u8) -> Option<&'static str> {
-    match weight {
-        0 => None,
-        8 => Some("A"),
-        4 => Some("B"),
-        2 => Some("C"),
-        1 => Some("D"),
-        12 => Some("AB"),
-        10 => Some("AC"),
-        9 => Some("AD"),
-        6 => Some("BC"),
-        5 => Some("BD"),
-        3 => Some("CD"),
-        14 => Some("ABC"),
-        13 => Some("ABD"),
-        11 => Some("ACD"),
-        7 => Some("BCD"),
-        15 => Some("ABCD"),
-        _ => None,
+// Quoted operand text followed by its prefix flag and weights
+fn operand(val: &Value) -> String {
+    let mut out = String::with_capacity(val.text.len() + 8);
+    out.push('\'');
+    for c in val.text.chars() {
+        if c == '\'' || c == '\\' {
+            out.push(c);
+        }
+        out.push(c);
+    }
+    out.push('\'');
+
+    if val.weight != 0 || val.prefix != 0 {
+        out.push(':');
+        if val.prefix != 0 {
+            out.push('*');
+        }
+        for (flag, letter) in [(8, 'A'), (4, 'B'), (2, 'C'), (1, 'D')] {
+            if val.weight & flag != 0 {
+                out.push(letter);
+            }
+        }
     }
+
+    out
 }
 
 impl ToSql for PgTsQuery {
@@ -426,3 +516,90 @@ impl ToSql for PgTsQuery {
 
     to_sql_checked!();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::PgTsQuery;
+    use std::str::FromStr;
+
+    // (input, SELECT input::tsquery::text) captured from PostgreSQL 15
+    const CORPUS: &[(&str, &str)] = &[
+        ("'foo':*AB", "'foo':*AB"),
+        ("'it''s' & 'back\\\\slash'", "'it''s' & 'back\\\\slash'"),
+        ("'a b' & c", "'a b' & 'c'"),
+        ("a:B & b:*", "'a':B & 'b':*"),
+        ("!(a <-> b)", "!( 'a' <-> 'b' )"),
+        ("!!a", "!!'a'"),
+        ("a <-> (b | c)", "'a' <-> ( 'b' | 'c' )"),
+        ("(a <2> b) <-> c", "'a' <2> 'b' <-> 'c'"),
+        ("a <-> b <-> c", "'a' <-> 'b' <-> 'c'"),
+        ("a <-> (b <-> c)", "'a' <-> ( 'b' <-> 'c' )"),
+        ("(a & b) <-> c", "( 'a' & 'b' ) <-> 'c'"),
+        ("a & b | c & d", "'a' & 'b' | 'c' & 'd'"),
+        ("(a | b) & (c | d)", "( 'a' | 'b' ) & ( 'c' | 'd' )"),
+        ("a | b & !c", "'a' | 'b' & !'c'"),
+        ("!a | (b & c) <3> d", "!'a' | ( 'b' & 'c' ) <3> 'd'"),
+    ];
+
+    #[test]
+    fn prints_like_tsqueryout() {
+        for (input, expected) in CORPUS {
+            let query = PgTsQuery::from_str(input).unwrap();
+            assert_eq!(query.to_string(), *expected, "input: {input}");
+        }
+    }
+
+    #[test]
+    fn deep_queries() {
+        use super::{Entry, MAX_QUERY_DEPTH, Operator, Operators, Value};
+        use crate::pg_tsvector::PgTsVector;
+        use crate::pg_tsvector_config::TextSearchConfig;
+        use crate::pg_tsvector_rank::RankNormalization;
+        use postgres_types::{ToSql, Type};
+
+        let chain = |n: usize, op: &str| {
+            let words: Vec<String> = (0..n).map(|i| format!("w{i}")).collect();
+            words.join(op)
+        };
+
+        // the deepest query that is accepted works everywhere
+        let vector = PgTsVector::from_str("w0:1 w1:2 w2:3").unwrap();
+        for op in [" & ", " <-> "] {
+            let query = PgTsQuery::from_str(&chain(MAX_QUERY_DEPTH, op)).unwrap();
+            assert!(query.to_string().starts_with(&format!("'w0'{op}'w1'{op}")));
+            assert!(query.cmp(&query.clone()).is_eq());
+            assert!(!query.matches(&vector));
+            assert!(vector.rank(&query, RankNormalization::default()) > 0.0);
+            let target = PgTsQuery::from_str("w1").unwrap();
+            let rewritten = query.rewrite(&target, &PgTsQuery::from_str("x").unwrap());
+            assert_eq!(rewritten.entries.len(), query.entries.len());
+        }
+
+        let too_deep = PgTsQuery::from_str(&chain(MAX_QUERY_DEPTH + 1, " & ")).unwrap_err();
+        assert_eq!(too_deep.message, "stack depth limit exceeded");
+        assert!(PgTsQuery::from_str(&chain(10_000, " & ")).is_err());
+
+        // queries built or decoded by other means still print, but don't become trees
+        let mut entries = vec![
+            Entry::Operator(Operator {
+                operator: Operators::Not,
+                distance: None,
+            });
+            100_000
+        ];
+        entries.push(Entry::Value(Value::new("a", 0, false)));
+        let query = PgTsQuery { entries };
+        assert_eq!(query.to_string().len(), 100_003);
+        assert!(query.to_tree().is_err());
+        assert!(query.cmp(&query.clone()).is_eq());
+
+        let mut bytes = bytes::BytesMut::new();
+        query.to_sql(&Type::TEXT, &mut bytes).unwrap();
+        assert!(PgTsQuery::try_from(&bytes[..]).is_err());
+
+        let words = chain(20_000, " ");
+        let query = PgTsQuery::plainto_tsquery(&TextSearchConfig::simple(), &words);
+        assert_eq!(query.entries.len(), 39_999);
+        assert!(query.to_string().ends_with(" & 'w19999'"));
+    }
+}
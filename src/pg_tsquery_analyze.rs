@@ -0,0 +1,313 @@
+use crate::pg_tsquery::{Operators, PgTsQuery, Value};
+use crate::pg_tsquery_construct::remove_stopwords;
+use crate::pg_tsquery_tree::TsQueryNode;
+
+impl PgTsQuery {
+    /// Number of operands and operators, same as `numnode()`
+    pub fn numnode(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Same as `querytree()`: the part of the query that can be looked up in an index.
+    ///
+    /// Negated branches are removed, `T` is returned when nothing is left and an empty
+    /// string for an empty query.
+    pub fn querytree(&self) -> String {
+        if self.entries.is_empty() {
+            return String::new();
+        }
+
+        match self.indexable() {
+            Some(query) => query.to_string(),
+            None => "T".to_string(),
+        }
+    }
+
+    /// The query with all non-indexable (negated) branches removed
+    /// (clean_NOT in tsquery_cleanup.c), `None` when nothing is left
+    pub fn indexable(&self) -> Option<PgTsQuery> {
+        let root = self.to_tree().ok()??;
+        clean_not(&root).map(PgTsQuery::from)
+    }
+
+    /// Whether every match of the query must contain at least one of its operands
+    /// (tsquery_requires_match in tsginidx.c).
+    ///
+    /// When this is false, a GIN index can't narrow down the rows and the server falls
+    /// back to scanning the whole index.
+    pub fn requires_match(&self) -> bool {
+        match self.to_tree() {
+            Ok(Some(root)) => requires_match(&root),
+            _ => false,
+        }
+    }
+
+    /// Removes `!!` pairs, which never change the result
+    pub fn remove_double_negation(&self) -> PgTsQuery {
+        self.map_tree(remove_double_negation)
+    }
+
+    /// Rebuilds chains of AND and OR into the left-deep shape the parser produces,
+    /// so `a & (b & c)` becomes `a & b & c`
+    pub fn flatten(&self) -> PgTsQuery {
+        self.map_tree(flatten)
+    }
+
+    /// Double negation removal followed by flattening
+    pub fn normalize(&self) -> PgTsQuery {
+        self.map_tree(|node| flatten(remove_double_negation(node)))
+    }
+
+    /// Whether nothing would be left of the query once stop words are removed,
+    /// in which case the server ignores it. An empty query counts as stop words only.
+    pub fn is_stopword_only<F>(&self, mut is_stopword: F) -> bool
+    where
+        F: FnMut(&str) -> bool,
+    {
+        self.remove_stopwords(|lexeme| is_stopword(lexeme))
+            .entries
+            .is_empty()
+    }
+
+    /// Removes operands that are stop words, widening phrase distances for every removed
+    /// word like `to_tsquery` does (cleanup_tsquery_stopwords in tsquery_cleanup.c)
+    pub fn remove_stopwords<F>(&self, mut is_stopword: F) -> PgTsQuery
+    where
+        F: FnMut(&str) -> bool,
+    {
+        match self.validate() {
+            Ok(()) => {
+                remove_stopwords(&self.entries, &mut |value: &Value| is_stopword(&value.text))
+            }
+            Err(_) => PgTsQuery { entries: vec![] },
+        }
+    }
+
+    // Malformed queries are returned unchanged
+    fn map_tree<F>(&self, f: F) -> PgTsQuery
+    where
+        F: FnOnce(TsQueryNode) -> TsQueryNode,
+    {
+        match self.to_tree() {
+            Ok(Some(root)) => f(root).into(),
+            _ => self.clone(),
+        }
+    }
+}
+
+impl TsQueryNode {
+    /// Operands of a chain of `operator`, e.g. `[a, b, c]` for `a & (b & c)` with AND.
+    /// Only AND and OR chains are flattened, other nodes are returned as is.
+    pub fn flatten_chain(&self, operator: Operators) -> Vec<&TsQueryNode> {
+        let mut operands = Vec::new();
+        let mut pending = vec![self];
+
+        while let Some(node) = pending.pop() {
+            match node {
+                TsQueryNode::And(left, right) if operator == Operators::And => {
+                    pending.push(right);
+                    pending.push(left);
+                }
+                TsQueryNode::Or(left, right) if operator == Operators::Or => {
+                    pending.push(right);
+                    pending.push(left);
+                }
+                _ => operands.push(node),
+            }
+        }
+
+        operands
+    }
+}
+
+fn clean_not(node: &TsQueryNode) -> Option<TsQueryNode> {
+    match node {
+        TsQueryNode::Term(_) => Some(node.clone()),
+        TsQueryNode::Not(_) => None,
+        // both branches are needed to find every match
+        TsQueryNode::Or(left, right) => Some(TsQueryNode::or(clean_not(left)?, clean_not(right)?)),
+        TsQueryNode::And(left, right) | TsQueryNode::Phrase { left, right, .. } => {
+            match (clean_not(left), clean_not(right)) {
+                (Some(left), Some(right)) => Some(match node {
+                    TsQueryNode::Phrase { distance, .. } => {
+                        TsQueryNode::phrase(left, right, *distance)
+                    }
+                    _ => TsQueryNode::and(left, right),
+                }),
+                (left, right) => left.or(right),
+            }
+        }
+    }
+}
+
+fn requires_match(node: &TsQueryNode) -> bool {
+    match node {
+        TsQueryNode::Term(_) => true,
+        // Assume there are no required matches underneath a NOT
+        TsQueryNode::Not(_) => false,
+        TsQueryNode::And(left, right) | TsQueryNode::Phrase { left, right, .. } => {
+            requires_match(left) || requires_match(right)
+        }
+        TsQueryNode::Or(left, right) => requires_match(left) && requires_match(right),
+    }
+}
+
+fn remove_double_negation(node: TsQueryNode) -> TsQueryNode {
+    node.transform(&mut |node| match node {
+        TsQueryNode::Not(operand) => match *operand {
+            TsQueryNode::Not(inner) => *inner,
+            operand => TsQueryNode::negate(operand),
+        },
+        node => node,
+    })
+}
+
+fn flatten(node: TsQueryNode) -> TsQueryNode {
+    node.transform(&mut |node| {
+        let operator = match node {
+            TsQueryNode::And(..) => Operators::And,
+            TsQueryNode::Or(..) => Operators::Or,
+            _ => return node,
+        };
+
+        let operands: Vec<TsQueryNode> =
+            node.flatten_chain(operator).into_iter().cloned().collect();
+
+        operands
+            .into_iter()
+            .reduce(|left, right| match operator {
+                Operators::And => TsQueryNode::and(left, right),
+                _ => TsQueryNode::or(left, right),
+            })
+            .unwrap_or(node)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pg_tsquery::PgTsQuery;
+    use std::str::FromStr;
+
+    fn query(s: &str) -> PgTsQuery {
+        PgTsQuery::from_str(s).unwrap()
+    }
+
+    // (query, numnode(query), querytree(query)) captured from PostgreSQL 15
+    const QUERYTREE: &[(&str, usize, &str)] = &[
+        ("(fat & rat) | cat", 5, "'fat' & 'rat' | 'cat'"),
+        ("!a | b", 4, "T"),
+        ("a", 1, "'a'"),
+        ("", 0, ""),
+        ("!a", 2, "T"),
+        ("a & !b", 4, "'a'"),
+        ("!a & !b", 5, "T"),
+        ("a | !b", 4, "T"),
+        ("a <-> !b", 4, "'a'"),
+        ("!a <-> b", 4, "'b'"),
+        ("!(a & b) & c", 6, "'c'"),
+        ("a & (b | !c)", 6, "'a'"),
+        ("!!a & b", 5, "'b'"),
+        ("a:* & b:AB", 3, "'a':* & 'b':AB"),
+        ("(a | b) <2> !c & d", 8, "( 'a' | 'b' ) & 'd'"),
+    ];
+
+    #[test]
+    fn numnode_and_querytree_like_the_server() {
+        for (input, numnode, querytree) in QUERYTREE {
+            let query = query(input);
+            assert_eq!(query.numnode(), *numnode, "{input}");
+            assert_eq!(query.querytree(), *querytree, "{input}");
+        }
+    }
+
+    // (query, to_tsquery('english', query)) captured from PostgreSQL 15, where
+    // 'the', 'a' and 'an' are stop words
+    const STOPWORDS: &[(&str, &str)] = &[
+        ("the & cat", "'cat'"),
+        ("cat <-> the <-> dog", "'cat' <2> 'dog'"),
+        ("the <-> cat", "'cat'"),
+        ("cat <-> the", "'cat'"),
+        ("the | a", ""),
+        ("!the", ""),
+        ("the & !cat", "!'cat'"),
+        ("cat <2> the <3> dog", "'cat' <5> 'dog'"),
+        ("(the | an) <-> cat", "'cat'"),
+        ("cat & (the | dog)", "'cat' & 'dog'"),
+        ("!(the & a) & cat", "'cat'"),
+        ("cat <-> (the & dog)", "'cat' <-> 'dog'"),
+    ];
+
+    #[test]
+    fn removes_stopwords_like_the_server() {
+        let is_stopword = |lexeme: &str| ["the", "a", "an"].contains(&lexeme);
+
+        for (input, expected) in STOPWORDS {
+            let query = query(input);
+            assert_eq!(
+                query.remove_stopwords(is_stopword).to_string(),
+                *expected,
+                "{input}"
+            );
+            assert_eq!(
+                query.is_stopword_only(is_stopword),
+                expected.is_empty(),
+                "{input}"
+            );
+        }
+
+        assert!(query("").is_stopword_only(is_stopword));
+    }
+
+    // The server prints chains the same way whatever their shape, so the results are
+    // compared with what the parser builds for the expected text
+    #[test]
+    fn normalizes() {
+        let cases = [
+            ("a & (b & c)", "a & b & c", "a & (b & c)", "a & b & c"),
+            (
+                "(a | b) | (c | d)",
+                "a | b | c | d",
+                "(a | b) | (c | d)",
+                "a | b | c | d",
+            ),
+            ("!!a & b", "!!a & b", "a & b", "a & b"),
+            ("!!!a", "!!!a", "!a", "!a"),
+            ("!(!a | !!b)", "!(!a | !!b)", "!(!a | b)", "!(!a | b)"),
+            (
+                "a & (b | (c | d)) & (e <-> f)",
+                "a & (b | c | d) & e <-> f",
+                "a & (b | (c | d)) & (e <-> f)",
+                "a & (b | c | d) & e <-> f",
+            ),
+            (
+                "!!(a & (b & c))",
+                "!!(a & b & c)",
+                "a & (b & c)",
+                "a & b & c",
+            ),
+            ("a & (!!b & c)", "a & !!b & c", "a & (b & c)", "a & b & c"),
+        ];
+
+        for (input, flat, single, normal) in cases {
+            let input_query = query(input);
+            assert_eq!(
+                input_query.flatten().entries,
+                query(flat).entries,
+                "{input}"
+            );
+            assert_eq!(
+                input_query.remove_double_negation().entries,
+                query(single).entries,
+                "{input}"
+            );
+            assert_eq!(
+                input_query.normalize().entries,
+                query(normal).entries,
+                "{input}"
+            );
+        }
+
+        // empty queries stay empty
+        assert!(query("").normalize().entries.is_empty());
+    }
+}
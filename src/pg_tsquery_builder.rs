@@ -0,0 +1,282 @@
+use crate::pg_tsquery::{Entry, Operator, Operators, PgTsQuery, Value};
+use crate::pg_tsquery_tree::TsQueryNode;
+use crate::pg_tsvector::Weight;
+use bitflags::bitflags;
+use std::error::Error;
+use std::fmt;
+use std::ops::{BitAnd, BitOr, Not};
+use std::str::FromStr;
+
+// Largest distance the phrase operator accepts (MAXENTRYPOS in ts_type.h)
+const MAX_PHRASE_DISTANCE: i16 = 1 << 14;
+
+bitflags! {
+    /// Weights an operand is restricted to, `'rat':AB` is `Weights::A | Weights::B`.
+    /// No flags at all means any weight matches.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+    pub struct Weights: u8 {
+        const A = 1 << 3;
+        const B = 1 << 2;
+        const C = 1 << 1;
+        const D = 1;
+    }
+}
+
+impl FromStr for Weights {
+    type Err = Box<dyn Error>;
+
+    /// Parses weight letters like `"AB"`, in any order and case
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.chars().try_fold(Weights::empty(), |weights, c| {
+            let weight = match c.to_ascii_uppercase() {
+                'A' => Weights::A,
+                'B' => Weights::B,
+                'C' => Weights::C,
+                'D' => Weights::D,
+                _ => return Err(format!("Invalid weight: {c}").into()),
+            };
+            Ok(weights | weight)
+        })
+    }
+}
+
+impl fmt::Display for Weights {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (weight, letter) in [
+            (Weights::A, 'A'),
+            (Weights::B, 'B'),
+            (Weights::C, 'C'),
+            (Weights::D, 'D'),
+        ] {
+            if self.contains(weight) {
+                write!(f, "{letter}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<Weight> for Weights {
+    fn from(weight: Weight) -> Self {
+        match weight {
+            Weight::A => Weights::A,
+            Weight::B => Weights::B,
+            Weight::C => Weights::C,
+            Weight::D => Weights::D,
+        }
+    }
+}
+
+impl Value {
+    /// Weight restriction of the operand as flags
+    pub fn weights(&self) -> Weights {
+        Weights::from_bits_truncate(self.weight)
+    }
+}
+
+/// A single lexeme, `term("fat") & term("rat")` is `'fat' & 'rat'`
+pub fn term(text: impl Into<String>) -> TsQueryNode {
+    TsQueryNode::Term(Value::new(text, 0, false))
+}
+
+/// A lexeme matching everything it is a prefix of, `prefix("cat")` is `'cat':*`
+pub fn prefix(text: impl Into<String>) -> TsQueryNode {
+    TsQueryNode::Term(Value::new(text, 0, true))
+}
+
+/// Parses weight letters for [`TsQueryNode::weight`], so that
+/// `term("rat").weight(weights("AB")?)` is `'rat':AB`. Fails on anything but A to D.
+pub fn weights(letters: &str) -> Result<Weights, Box<dyn Error>> {
+    letters.parse()
+}
+
+impl TsQueryNode {
+    /// Restricts every operand to the given weights,
+    /// `term("rat").weight(Weights::A | Weights::B)` is `'rat':AB`. Weight letters are
+    /// passed through [`weights`], e.g. `term("rat").weight(weights("AB")?)`.
+    pub fn weight(self, weights: impl Into<Weights>) -> Self {
+        let weights = weights.into();
+        self.transform(&mut |node| match node {
+            TsQueryNode::Term(mut value) => {
+                value.weight = weights.bits();
+                TsQueryNode::Term(value)
+            }
+            node => node,
+        })
+    }
+
+    /// `self <N> other`: `other` has to follow `distance` positions after `self`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `distance` is outside of 0 to 16384, which the server rejects.
+    pub fn followed_by(self, other: TsQueryNode, distance: i16) -> Self {
+        check_distance(distance);
+        TsQueryNode::phrase(self, other, distance)
+    }
+}
+
+impl BitAnd for TsQueryNode {
+    type Output = TsQueryNode;
+
+    fn bitand(self, rhs: TsQueryNode) -> TsQueryNode {
+        TsQueryNode::and(self, rhs)
+    }
+}
+
+impl BitOr for TsQueryNode {
+    type Output = TsQueryNode;
+
+    fn bitor(self, rhs: TsQueryNode) -> TsQueryNode {
+        TsQueryNode::or(self, rhs)
+    }
+}
+
+impl Not for TsQueryNode {
+    type Output = TsQueryNode;
+
+    fn not(self) -> TsQueryNode {
+        TsQueryNode::negate(self)
+    }
+}
+
+impl PgTsQuery {
+    /// Same as `tsquery <-> tsquery` and `tsquery_phrase(a, b, distance)`.
+    /// An empty query on either side returns the other one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `distance` is outside of 0 to 16384, which the server rejects.
+    pub fn followed_by(self, other: PgTsQuery, distance: i16) -> PgTsQuery {
+        check_distance(distance);
+        join(
+            self,
+            other,
+            Operator {
+                operator: Operators::Phrase,
+                distance: Some(distance),
+            },
+        )
+    }
+}
+
+/// Same as `tsquery && tsquery`. An empty query on either side returns the other one.
+impl BitAnd for PgTsQuery {
+    type Output = PgTsQuery;
+
+    fn bitand(self, rhs: PgTsQuery) -> PgTsQuery {
+        join(
+            self,
+            rhs,
+            Operator {
+                operator: Operators::And,
+                distance: None,
+            },
+        )
+    }
+}
+
+/// Same as `tsquery || tsquery`. An empty query on either side returns the other one.
+impl BitOr for PgTsQuery {
+    type Output = PgTsQuery;
+
+    fn bitor(self, rhs: PgTsQuery) -> PgTsQuery {
+        join(
+            self,
+            rhs,
+            Operator {
+                operator: Operators::Or,
+                distance: None,
+            },
+        )
+    }
+}
+
+/// Same as `!! tsquery`. An empty query stays empty.
+impl Not for PgTsQuery {
+    type Output = PgTsQuery;
+
+    fn not(self) -> PgTsQuery {
+        if self.entries.is_empty() {
+            return self;
+        }
+
+        let mut entries = Vec::with_capacity(self.entries.len() + 1);
+        entries.push(Entry::Operator(Operator {
+            operator: Operators::Not,
+            distance: None,
+        }));
+        entries.extend(self.entries);
+
+        PgTsQuery { entries }
+    }
+}
+
+fn check_distance(distance: i16) {
+    assert!(
+        (0..=MAX_PHRASE_DISTANCE).contains(&distance),
+        "distance in phrase operator must be an integer value between zero and {MAX_PHRASE_DISTANCE} inclusive"
+    );
+}
+
+// Entries are stored as operator, right operand, left operand (join_tsqueries in tsquery_op.c)
+fn join(left: PgTsQuery, right: PgTsQuery, operator: Operator) -> PgTsQuery {
+    if left.entries.is_empty() {
+        return right;
+    }
+    if right.entries.is_empty() {
+        return left;
+    }
+
+    let mut entries = Vec::with_capacity(left.entries.len() + right.entries.len() + 1);
+    entries.push(Entry::Operator(operator));
+    entries.extend(right.entries);
+    entries.extend(left.entries);
+
+    PgTsQuery { entries }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Weights, prefix, term, weights};
+    use crate::pg_tsquery::PgTsQuery;
+    use std::error::Error;
+    use std::str::FromStr;
+
+    fn query(s: &str) -> PgTsQuery {
+        PgTsQuery::from_str(s).unwrap()
+    }
+
+    // Expected values are the text output of PostgreSQL 15
+    #[test]
+    fn builds_like_the_server() -> Result<(), Box<dyn Error>> {
+        let built = term("fat") & term("rat").weight(weights("AB")?) | !prefix("cat");
+        assert_eq!(
+            PgTsQuery::from(built).to_string(),
+            "'fat' & 'rat':AB | !'cat':*"
+        );
+
+        let built = term("a").followed_by(term("b"), 3);
+        assert_eq!(PgTsQuery::from(built).to_string(), "'a' <3> 'b'");
+
+        // (a && b) || !!c and tsquery_phrase('a & b', 'c', 3)
+        assert_eq!(
+            (query("a") & query("b") | !query("c")).to_string(),
+            "'a' & 'b' | !'c'"
+        );
+        assert_eq!(
+            query("a & b").followed_by(query("c"), 3).to_string(),
+            "( 'a' & 'b' ) <3> 'c'"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn weight_letters() {
+        assert_eq!(weights("ab").unwrap(), Weights::A | Weights::B);
+        assert_eq!(weights("").unwrap(), Weights::empty());
+        assert!(weights("AE").is_err());
+        assert_eq!((Weights::D | Weights::B).to_string(), "BD");
+    }
+}
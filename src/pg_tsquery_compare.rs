@@ -0,0 +1,262 @@
+use crate::pg_tsquery::{Entry, Operators, PgTsQuery};
+use crate::pg_tsquery_rewrite::valcrc;
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+
+impl PgTsQuery {
+    /// Same as `query @> other`: every operand of `other` also appears in this query.
+    ///
+    /// Only the operand texts are compared, the operators, weights and prefix flags
+    /// are ignored like on the server. An empty `other` is contained in every query.
+    pub fn contains(&self, other: &PgTsQuery) -> bool {
+        other.operands().is_subset(&self.operands())
+    }
+
+    /// Same as `query <@ other`, the reverse of [`PgTsQuery::contains`]
+    pub fn contained_by(&self, other: &PgTsQuery) -> bool {
+        other.contains(self)
+    }
+
+    // Distinct operand texts (collectTSQueryValues in tsquery_op.c)
+    fn operands(&self) -> BTreeSet<&str> {
+        self.entries
+            .iter()
+            .filter_map(|entry| match entry {
+                Entry::Value(value) => Some(value.text.as_str()),
+                Entry::Operator(_) => None,
+            })
+            .collect()
+    }
+
+    // Total size of the operand texts including their terminators, which is what
+    // remains of the size of the datum once the number of entries is equal
+    fn operands_len(&self) -> usize {
+        self.entries
+            .iter()
+            .map(|entry| match entry {
+                Entry::Value(value) => value.text.len() + 1,
+                Entry::Operator(_) => 0,
+            })
+            .sum()
+    }
+}
+
+/// Queries are ordered like the server's btree operator class does it (CompareTSQ in
+/// tsquery_op.c): by number of entries, then by the length of the operand texts and
+/// finally by comparing the trees. Operands are compared by their checksum before their
+/// text, so the order is stable but not alphabetical.
+///
+/// The server considers queries equal that only differ in weights or prefix flags,
+/// those are ordered by the differing flags afterwards to stay consistent with `==`.
+impl Ord for PgTsQuery {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.entries
+            .len()
+            .cmp(&other.entries.len())
+            .then_with(|| self.operands_len().cmp(&other.operands_len()))
+            .then_with(|| compare_nodes(&self.entries, &other.entries))
+            .then_with(|| compare_flags(&self.entries, &other.entries))
+    }
+}
+
+impl PartialOrd for PgTsQuery {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Compares the trees, same as QTNodeCompare in tsquery_util.c on trees that have not
+/// been sorted.
+///
+/// Entries are stored as operator, right operand, left operand, so walking them in order
+/// visits the operands in the same order as the server does. The distances of phrase
+/// operators are compared once both operands are equal, which the stack of open
+/// operators keeps track of.
+fn compare_nodes(a: &[Entry], b: &[Entry]) -> Ordering {
+    // (operands still to compare, ordering of the distances) for every open operator
+    let mut open: Vec<(usize, Ordering)> = Vec::new();
+
+    for pos in 0.. {
+        // only possible for malformed queries
+        let (Some(a_entry), Some(b_entry)) = (a.get(pos), b.get(pos)) else {
+            return a.get(pos).is_some().cmp(&b.get(pos).is_some());
+        };
+
+        match (a_entry, b_entry) {
+            // operators come first
+            (Entry::Operator(_), Entry::Value(_)) => return Ordering::Less,
+            (Entry::Value(_), Entry::Operator(_)) => return Ordering::Greater,
+            (Entry::Value(a_value), Entry::Value(b_value)) => {
+                let ordering = valcrc(&b_value.text)
+                    .cmp(&valcrc(&a_value.text))
+                    .then_with(|| a_value.text.as_bytes().cmp(b_value.text.as_bytes()));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Entry::Operator(a_op), Entry::Operator(b_op)) => {
+                let ordering = (b_op.operator as i8).cmp(&(a_op.operator as i8));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+
+                let distance = if a_op.operator == Operators::Phrase {
+                    b_op.distance.unwrap_or(1).cmp(&a_op.distance.unwrap_or(1))
+                } else {
+                    Ordering::Equal
+                };
+                open.push((a_entry.arity(), distance));
+                continue;
+            }
+        }
+
+        // an operand is complete, and with it every operator whose last operand it was
+        loop {
+            let Some((operands, distance)) = open.last_mut() else {
+                return Ordering::Equal;
+            };
+            *operands -= 1;
+            if *operands > 0 {
+                break;
+            }
+            if *distance != Ordering::Equal {
+                return *distance;
+            }
+            open.pop();
+        }
+    }
+
+    Ordering::Equal
+}
+
+/// Tie breaker for queries the server considers equal
+fn compare_flags(a: &[Entry], b: &[Entry]) -> Ordering {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| match (a, b) {
+            (Entry::Value(a), Entry::Value(b)) => (a.weight, a.prefix, a.distance)
+                .cmp(&(b.weight, b.prefix, b.distance))
+                .then_with(|| a.text.cmp(&b.text)),
+            (Entry::Operator(a), Entry::Operator(b)) => a
+                .distance
+                .cmp(&b.distance)
+                .then_with(|| (a.operator as i8).cmp(&(b.operator as i8))),
+            (Entry::Operator(_), Entry::Value(_)) => Ordering::Less,
+            (Entry::Value(_), Entry::Operator(_)) => Ordering::Greater,
+        })
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pg_tsquery::PgTsQuery;
+    use crate::pg_tsquery_compare::compare_nodes;
+    use std::cmp::Ordering;
+    use std::str::FromStr;
+
+    fn query(s: &str) -> PgTsQuery {
+        PgTsQuery::from_str(s).unwrap()
+    }
+
+    // Result of `ORDER BY q` captured from PostgreSQL 15, no two of them are equal
+    const SORTED: &[&str] = &[
+        "",
+        "a",
+        "c",
+        "b",
+        "zz",
+        "ab",
+        "dog",
+        "abc",
+        "foo",
+        "cat",
+        "bar",
+        "!a",
+        "!b",
+        "b <-> a",
+        "x <-> y",
+        "a <3> b",
+        "a <2> b",
+        "a <-> b",
+        "a | b",
+        "b & a",
+        "a & b",
+        "aa & b",
+        "fat & rat",
+        "a & !b",
+        "!a & b",
+        "a | b & c",
+        "a & (b & c)",
+        "a & b & c",
+    ];
+
+    #[test]
+    fn sorts_like_the_server() {
+        let mut queries: Vec<(PgTsQuery, &str)> = SORTED
+            .iter()
+            .rev()
+            .map(|input| (query(input), *input))
+            .collect();
+        queries.sort();
+
+        let sorted: Vec<&str> = queries.iter().map(|(_, input)| *input).collect();
+        assert_eq!(sorted, SORTED);
+
+        for pair in queries.windows(2) {
+            assert_eq!(pair[0].0.cmp(&pair[1].0), Ordering::Less, "{}", pair[0].1);
+            assert_eq!(
+                pair[1].0.cmp(&pair[0].0),
+                Ordering::Greater,
+                "{}",
+                pair[1].1
+            );
+        }
+    }
+
+    // Queries the server considers equal, they still get a consistent order here
+    const SAME_TREE: &[(&str, &str)] = &[
+        ("a:A", "a"),
+        ("a:*", "a"),
+        ("a:AB & b", "a & b:C"),
+        ("a:*A", "a:B"),
+    ];
+
+    #[test]
+    fn orders_weights_and_prefixes_after_the_tree() {
+        for (a, b) in SAME_TREE {
+            let (a, b) = (query(a), query(b));
+            assert_eq!(
+                compare_nodes(&a.entries, &b.entries),
+                Ordering::Equal,
+                "{a}"
+            );
+            assert_ne!(a, b);
+            assert_ne!(a.cmp(&b), Ordering::Equal, "{a}");
+            assert_eq!(a.cmp(&b), b.cmp(&a).reverse(), "{a}");
+        }
+    }
+
+    // (a, b, a @> b, a <@ b) captured from PostgreSQL 15
+    const CONTAINS: &[(&str, &str, bool, bool)] = &[
+        ("a & b", "a", true, false),
+        ("a", "a & b", false, true),
+        ("a | b", "b & a", true, true),
+        ("!a <-> b", "a", true, false),
+        ("a:A", "a:*B", true, true),
+        ("a", "a & a", true, true),
+        ("a & b", "", true, false),
+        ("", "a", false, true),
+        ("", "", true, true),
+        ("a <-> b & c", "c | d", false, false),
+    ];
+
+    #[test]
+    fn contains_like_the_server() {
+        for (a, b, contains, contained_by) in CONTAINS {
+            let (a, b) = (query(a), query(b));
+            assert_eq!(a.contains(&b), *contains, "{a} @> {b}");
+            assert_eq!(a.contained_by(&b), *contained_by, "{a} <@ {b}");
+        }
+    }
+}
@@ -0,0 +1,428 @@
+use crate::pg_tsquery::{Entry, Operator, Operators, PgTsQuery, Value};
+use crate::pg_tsquery_parser::{Item, ParseMode, TsQueryParseError, parse_query};
+
+/// Lexemes the tokenizer produced for one word position of the input
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct QueryWord {
+    /// Alternative normalizations of the word. Each variant is a group of lexemes that
+    /// must all be present, e.g. a dictionary splitting a compound word.
+    /// Empty when the word is a stop word.
+    pub variants: Vec<Vec<QueryLexeme>>,
+}
+
+/// One lexeme of a [`QueryWord`]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct QueryLexeme {
+    pub lexeme: String,
+    /// Matches every lexeme starting with it even without `:*`, like a synonym
+    /// ending in `*` (TSL_PREFIX)
+    pub prefix: bool,
+}
+
+impl QueryLexeme {
+    pub fn new(lexeme: impl Into<String>) -> Self {
+        QueryLexeme {
+            lexeme: lexeme.into(),
+            prefix: false,
+        }
+    }
+}
+
+impl QueryWord {
+    pub fn lexeme(lexeme: impl Into<String>) -> Self {
+        QueryWord {
+            variants: vec![vec![QueryLexeme::new(lexeme)]],
+        }
+    }
+
+    pub fn stopword() -> Self {
+        QueryWord::default()
+    }
+
+    pub fn is_stopword(&self) -> bool {
+        self.variants.iter().all(|variant| variant.is_empty())
+    }
+}
+
+/// Turns raw text into normalized lexemes, the client side counterpart of the
+/// text search configuration the server would use.
+///
+/// Every word of the input must produce one [`QueryWord`], including stop words,
+/// so that phrase distances come out right.
+pub trait QueryTokenizer {
+    fn tokenize(&self, text: &str) -> Vec<QueryWord>;
+}
+
+impl<F> QueryTokenizer for F
+where
+    F: Fn(&str) -> Vec<QueryWord>,
+{
+    fn tokenize(&self, text: &str) -> Vec<QueryWord> {
+        self(text)
+    }
+}
+
+impl PgTsQuery {
+    /// Same as `to_tsquery`: regular tsquery syntax where every operand is normalized by
+    /// the tokenizer. Operands that produce several words are joined with `<->`.
+    pub fn to_tsquery<T>(tokenizer: &T, text: &str) -> Result<PgTsQuery, TsQueryParseError>
+    where
+        T: QueryTokenizer + ?Sized,
+    {
+        let items = parse_query(text, ParseMode::Standard, &mut |value, output| {
+            push_morph(tokenizer, value, Operators::Phrase, output)
+        })?;
+
+        Ok(finish(items))
+    }
+
+    /// Same as `plainto_tsquery`: all words of the text are ANDed together
+    pub fn plainto_tsquery<T>(tokenizer: &T, text: &str) -> PgTsQuery
+    where
+        T: QueryTokenizer + ?Sized,
+    {
+        Self::from_words(tokenizer, text, Operators::And)
+    }
+
+    /// Same as `phraseto_tsquery`: all words of the text are joined with `<->`,
+    /// with the distance increased for every stop word skipped
+    pub fn phraseto_tsquery<T>(tokenizer: &T, text: &str) -> PgTsQuery
+    where
+        T: QueryTokenizer + ?Sized,
+    {
+        Self::from_words(tokenizer, text, Operators::Phrase)
+    }
+
+    /// Same as `websearch_to_tsquery`: unquoted words are ANDed, `"quoted text"` becomes a
+    /// phrase, `or` becomes `|` and a leading `-` negates the next operand.
+    ///
+    /// Like the server this never rejects input, only an excessive number of operators
+    /// in a row makes it fail.
+    pub fn websearch_to_tsquery<T>(
+        tokenizer: &T,
+        text: &str,
+    ) -> Result<PgTsQuery, TsQueryParseError>
+    where
+        T: QueryTokenizer + ?Sized,
+    {
+        let items = parse_query(text, ParseMode::Web, &mut |value, output| {
+            push_morph(tokenizer, value, Operators::Phrase, output)
+        })?;
+
+        Ok(finish(items))
+    }
+
+    fn from_words<T>(tokenizer: &T, text: &str, operator: Operators) -> PgTsQuery
+    where
+        T: QueryTokenizer + ?Sized,
+    {
+        let mut items = Vec::new();
+
+        // gettoken_query_plain: the whole text is a single operand
+        if !text.is_empty() {
+            push_morph(tokenizer, Value::new(text, 0, false), operator, &mut items);
+        }
+
+        finish(items)
+    }
+}
+
+/// Pushes the lexemes of one operand (pushval_morph in to_tsany.c).
+///
+/// Variants of a word are ORed, lexemes within a variant are ANDed and consecutive
+/// words are joined with `operator`. Skipped stop words between words leave a placeholder
+/// behind so a phrase operator can account for them.
+fn push_morph<T>(tokenizer: &T, operand: Value, operator: Operators, output: &mut Vec<Item>)
+where
+    T: QueryTokenizer + ?Sized,
+{
+    let words = tokenizer.tokenize(&operand.text);
+    let prefix = operand.prefix != 0;
+
+    let join = |output: &mut Vec<Item>| {
+        let distance = (operator == Operators::Phrase).then_some(1);
+        output.push(Item::Operator(Operator { operator, distance }));
+    };
+    let push_op = |output: &mut Vec<Item>, operator: Operators| {
+        output.push(Item::Operator(Operator {
+            operator,
+            distance: None,
+        }));
+    };
+
+    let mut pos = 0;
+    let mut count_pos = 0;
+
+    for (index, word) in words.iter().enumerate() {
+        if word.is_stopword() {
+            continue;
+        }
+        let word_pos = index + 1;
+
+        // put placeholders for each stop word skipped since the previous word
+        if pos > 0 {
+            while pos + 1 < word_pos {
+                output.push(Item::Stop);
+                if count_pos > 0 {
+                    join(output);
+                }
+                count_pos += 1;
+                pos += 1;
+            }
+        }
+        pos = word_pos;
+
+        let variants = word.variants.iter().filter(|variant| !variant.is_empty());
+        for (count_variants, variant) in variants.enumerate() {
+            for (i, lexeme) in variant.iter().enumerate() {
+                output.push(Item::Value(Value::new(
+                    lexeme.lexeme.as_str(),
+                    operand.weight,
+                    prefix || lexeme.prefix,
+                )));
+                if i > 0 {
+                    push_op(output, Operators::And);
+                }
+            }
+
+            if count_variants > 0 {
+                push_op(output, Operators::Or);
+            }
+        }
+
+        if count_pos > 0 {
+            join(output);
+        }
+        count_pos += 1;
+    }
+
+    if count_pos == 0 {
+        output.push(Item::Stop);
+    }
+}
+
+/// Removes every operand for which `is_stopword` returns true, adjusting phrase
+/// distances the same way the server does for stop words
+pub(crate) fn remove_stopwords<F>(entries: &[Entry], is_stopword: &mut F) -> PgTsQuery
+where
+    F: FnMut(&Value) -> bool,
+{
+    let items = entries
+        .iter()
+        .rev()
+        .map(|entry| match entry {
+            Entry::Value(value) if is_stopword(value) => Item::Stop,
+            Entry::Value(value) => Item::Value(value.clone()),
+            Entry::Operator(op) => Item::Operator(*op),
+        })
+        .collect();
+
+    finish(items)
+}
+
+/// Builds the final query from reverse polish items, dropping stop word placeholders
+fn finish(items: Vec<Item>) -> PgTsQuery {
+    let mut entries = clean_stopwords(items).unwrap_or_default();
+    entries.reverse();
+
+    PgTsQuery { entries }
+}
+
+/// Removes stop word placeholders (clean_stopword_intree in tsquery_cleanup.c).
+///
+/// Every item leaves the reverse polish entries of its cleaned sub tree on the stack,
+/// together with the distances that have to be added to a phrase operator on its left
+/// and on its right, so that `a <-> stop <-> b` becomes `a <2> b`. Working on the items
+/// instead of a tree keeps long queries from overflowing the stack.
+fn clean_stopwords(items: Vec<Item>) -> Option<Vec<Entry>> {
+    let mut stack: Vec<(Option<Vec<Entry>>, i32, i32)> = Vec::new();
+    let pop = |stack: &mut Vec<_>| stack.pop().unwrap_or((None, 0, 0));
+
+    for item in items {
+        let cleaned = match item {
+            Item::Stop => (None, 0, 0),
+            Item::Value(value) => (Some(vec![Entry::Value(value)]), 0, 0),
+            Item::Operator(op) if op.operator == Operators::Not => {
+                // NOT doesn't change pattern width, so just report child distances
+                let (entries, ladd, radd) = pop(&mut stack);
+                let entries = entries.map(|mut entries| {
+                    entries.push(Entry::Operator(op));
+                    entries
+                });
+                (entries, ladd, radd)
+            }
+            Item::Operator(op) => {
+                let (right, rladd, rradd) = pop(&mut stack);
+                let (left, lladd, lradd) = pop(&mut stack);
+
+                let is_phrase = op.operator == Operators::Phrase;
+                let distance = if is_phrase {
+                    i32::from(op.distance.unwrap_or(1))
+                } else {
+                    0
+                };
+
+                match (left, right) {
+                    (None, None) if is_phrase => {
+                        let add = lladd + distance + rladd;
+                        (None, add, add)
+                    }
+                    (None, None) => {
+                        let add = lladd.max(rladd);
+                        (None, add, add)
+                    }
+                    (None, Some(right)) if is_phrase => {
+                        (Some(right), lladd + distance + rladd, rradd)
+                    }
+                    (None, Some(right)) => (Some(right), rladd, rradd),
+                    (Some(left), None) if is_phrase => {
+                        (Some(left), lladd, lradd + distance + rradd)
+                    }
+                    (Some(left), None) => (Some(left), lladd, lradd),
+                    (Some(mut left), Some(right)) => {
+                        left.extend(right);
+                        if is_phrase {
+                            let distance = (distance + lradd + rladd) as i16;
+                            left.push(Entry::Operator(Operator {
+                                operator: Operators::Phrase,
+                                distance: Some(distance),
+                            }));
+                            (Some(left), lladd, rradd)
+                        } else {
+                            left.push(Entry::Operator(Operator {
+                                operator: op.operator,
+                                distance: None,
+                            }));
+                            (Some(left), 0, 0)
+                        }
+                    }
+                }
+            }
+        };
+        stack.push(cleaned);
+    }
+
+    stack.pop().and_then(|(entries, ..)| entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pg_tsquery::PgTsQuery;
+    use crate::pg_tsvector_config::{SimpleDictionary, Stopwords, TextSearchConfig};
+    use crate::pg_tsvector_dictionaries::SynonymDictionary;
+    use crate::pg_tsvector_tokenizer::{DefaultParser, TokenType};
+    use std::sync::Arc;
+
+    // Configuration rv on the server maps every token type to a simple dictionary
+    // whose stop word file lists "the" and "of"
+    fn config() -> TextSearchConfig {
+        TextSearchConfig::with_dictionary(SimpleDictionary::with_stopwords(Stopwords::new([
+            "the", "of",
+        ])))
+    }
+
+    // (input, SELECT to_tsquery('rv', input)::text) captured from PostgreSQL 15
+    const TO_TSQUERY: &[(&str, &str)] = &[
+        ("fat & rat", "'fat' & 'rat'"),
+        ("the & cat", "'cat'"),
+        ("fat <-> the <-> rat", "'fat' <2> 'rat'"),
+        ("'fat rat' & cat", "'fat' <-> 'rat' & 'cat'"),
+        ("Fat:AB & Rats:*", "'fat':AB & 'rats':*"),
+        ("!the | cat", "'cat'"),
+        (
+            "supernovae-stars",
+            "'supernovae-stars' <-> 'supernovae' <-> 'stars'",
+        ),
+        ("cat & (the | of)", "'cat'"),
+        ("the <2> cat", "'cat'"),
+    ];
+
+    const PLAINTO_TSQUERY: &[(&str, &str)] = &[
+        ("The Fat Rats", "'fat' & 'rats'"),
+        ("fat & rat", "'fat' & 'rat'"),
+        ("the of", ""),
+        ("it's cat", "'it' & 's' & 'cat'"),
+        ("a:1 b", "'a' & '1' & 'b'"),
+    ];
+
+    const PHRASETO_TSQUERY: &[(&str, &str)] = &[
+        ("The Fat Rats", "'fat' <-> 'rats'"),
+        ("fat the of rat", "'fat' <3> 'rat'"),
+        ("the cat", "'cat'"),
+        ("cat of the", "'cat'"),
+        ("fat & rat", "'fat' <-> 'rat'"),
+    ];
+
+    const WEBSEARCH_TO_TSQUERY: &[(&str, &str)] = &[
+        ("fat rat", "'fat' & 'rat'"),
+        ("\"fat rat\" cat", "'fat' <-> 'rat' & 'cat'"),
+        ("cat or dog", "'cat' | 'dog'"),
+        ("cat -dog", "'cat' & !'dog'"),
+        ("\"the cat", "'cat'"),
+        ("\"cat dog", "'cat' <-> 'dog'"),
+        ("cat ! or dog", "'cat' | 'dog'"),
+        ("cat ) or dog", "'cat' | 'dog'"),
+        ("cat (or dog", "'cat' | 'dog'"),
+        ("\"fat > rat\"", "'fat' <-> 'rat'"),
+        ("\"a the b\"", "'a' <2> 'b'"),
+        ("cat \" or dog", "'cat' & 'or' <-> 'dog'"),
+        ("-\"fat rat\"", "!( 'fat' <-> 'rat' )"),
+        ("or cat", "'or' & 'cat'"),
+        ("cat or", "'cat' & 'or'"),
+        ("", ""),
+    ];
+
+    #[test]
+    fn to_tsquery_like_server() {
+        for (input, expected) in TO_TSQUERY {
+            let query = PgTsQuery::to_tsquery(&config(), input).unwrap();
+            assert_eq!(query.to_string(), *expected, "input: {input}");
+        }
+    }
+
+    #[test]
+    fn plainto_tsquery_like_server() {
+        for (input, expected) in PLAINTO_TSQUERY {
+            let query = PgTsQuery::plainto_tsquery(&config(), input);
+            assert_eq!(query.to_string(), *expected, "input: {input}");
+        }
+    }
+
+    #[test]
+    fn phraseto_tsquery_like_server() {
+        for (input, expected) in PHRASETO_TSQUERY {
+            let query = PgTsQuery::phraseto_tsquery(&config(), input);
+            assert_eq!(query.to_string(), *expected, "input: {input}");
+        }
+    }
+
+    #[test]
+    fn websearch_to_tsquery_like_server() {
+        for (input, expected) in WEBSEARCH_TO_TSQUERY {
+            let query = PgTsQuery::websearch_to_tsquery(&config(), input).unwrap();
+            assert_eq!(query.to_string(), *expected, "input: {input}");
+        }
+    }
+
+    // Configuration c_synonym on the server maps asciiword and word to a synonym
+    // dictionary with synonym_sample.syn, then to simple
+    #[test]
+    fn synonym_prefix() {
+        let synonyms = SynonymDictionary::parse("postgres\tpgsql\nindices\tindex*\n", false);
+        let config = TextSearchConfig::new(DefaultParser).add_mapping(
+            &[TokenType::AsciiWord, TokenType::Word],
+            vec![Arc::new(synonyms), Arc::new(SimpleDictionary::default())],
+        );
+
+        let query = PgTsQuery::to_tsquery(&config, "indices & Postgres").unwrap();
+        assert_eq!(query.to_string(), "'index':* & 'pgsql'");
+        let query = PgTsQuery::to_tsquery(&config, "indices:*B | other").unwrap();
+        assert_eq!(query.to_string(), "'index':*B | 'other'");
+        let query = PgTsQuery::plainto_tsquery(&config, "the indices");
+        assert_eq!(query.to_string(), "'the' & 'index':*");
+
+        // to_tsvector has no use for it
+        let vector = config.to_tsvector("indices postgres");
+        assert_eq!(vector.to_string(), "'index':1 'pgsql':2");
+    }
+}
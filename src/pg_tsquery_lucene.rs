@@ -0,0 +1,724 @@
+use crate::pg_tsquery::{Operators, PgTsQuery, Value};
+use crate::pg_tsquery_tree::TsQueryNode;
+use std::error::Error;
+use std::fmt;
+
+/// How clauses without an explicit `AND`/`OR` are combined, `OR` in Lucene's classic
+/// query parser and in Tantivy unless conjunction by default is enabled
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum DefaultOperator {
+    #[default]
+    Or,
+    And,
+}
+
+/// Error returned when converting between tsquery and Lucene query syntax
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LuceneError {
+    /// The Lucene query string is malformed, `position` is a byte offset
+    Syntax { position: usize, message: String },
+    /// Parts of the query that have no equivalent in the other syntax
+    Unsupported(Vec<String>),
+}
+
+impl fmt::Display for LuceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LuceneError::Syntax { position, message } => write!(
+                f,
+                "syntax error in lucene query at position {position}: {message}"
+            ),
+            LuceneError::Unsupported(constructs) => {
+                write!(f, "query can't be converted: {}", constructs.join(", "))
+            }
+        }
+    }
+}
+
+impl Error for LuceneError {}
+
+impl PgTsQuery {
+    /// Writes the query in Lucene query syntax, which Tantivy understands as well.
+    ///
+    /// AND becomes `+a +b`, a negated operand of an AND `-a`, OR becomes `a OR b`,
+    /// `'a':*` becomes `a*` and a chain of `<->` becomes `"a b"`. The result doesn't
+    /// depend on the default operator of the engine.
+    ///
+    /// Weight restrictions, phrase distances other than 1, phrases over anything but
+    /// plain lexemes and negations that aren't part of an AND with at least one
+    /// positive operand can't be expressed and are reported.
+    pub fn to_lucene(&self) -> Result<String, LuceneError> {
+        let root = match self.to_tree() {
+            Ok(Some(root)) => root,
+            Ok(None) => return Ok(String::new()),
+            Err(e) => return Err(LuceneError::Unsupported(vec![e.to_string()])),
+        };
+
+        let mut unsupported = Vec::new();
+        let query = write_node(&root, &mut unsupported);
+
+        if unsupported.is_empty() {
+            Ok(query)
+        } else {
+            Err(LuceneError::Unsupported(unsupported))
+        }
+    }
+
+    /// Reads a Lucene style query: terms, `term*`, `"phrases"`, `+required`,
+    /// `-prohibited`, `AND`/`OR`/`NOT`, `&&`/`||`/`!` and parenthesized groups.
+    ///
+    /// Clauses are combined the way Lucene's classic query parser does it. Terms are used
+    /// as lexemes as they are, so they should already be normalized. Boosts and optional
+    /// clauses next to required ones only affect scoring and are dropped.
+    ///
+    /// Fields, fuzzy and wildcard terms, sloppy phrases (`"a b"~2` also matches the words
+    /// apart or swapped), ranges, regular expressions and groups made of negated clauses
+    /// only, which match nothing in Lucene, are reported as unsupported.
+    pub fn from_lucene(
+        query: &str,
+        default_operator: DefaultOperator,
+    ) -> Result<PgTsQuery, LuceneError> {
+        let mut parser = LuceneParser {
+            input: query,
+            pos: 0,
+            default_operator,
+            unsupported: Vec::new(),
+        };
+
+        let root = parser.parse_query(0)?;
+
+        if parser.unsupported.is_empty() {
+            Ok(root.into())
+        } else {
+            Err(LuceneError::Unsupported(parser.unsupported))
+        }
+    }
+}
+
+fn describe(node: &TsQueryNode) -> String {
+    PgTsQuery::from(node.clone()).to_string()
+}
+
+fn write_node(node: &TsQueryNode, unsupported: &mut Vec<String>) -> String {
+    match node {
+        TsQueryNode::Term(value) => write_term(value, unsupported),
+        TsQueryNode::Not(_) => {
+            unsupported.push(format!("negation outside of an AND: {}", describe(node)));
+            String::new()
+        }
+        TsQueryNode::And(..) => {
+            let operands = node.flatten_chain(Operators::And);
+
+            if operands
+                .iter()
+                .all(|operand| matches!(operand, TsQueryNode::Not(_)))
+            {
+                unsupported.push(format!("AND of negations only: {}", describe(node)));
+            }
+
+            operands
+                .iter()
+                .map(|operand| match operand {
+                    TsQueryNode::Not(negated) => format!("-{}", write_clause(negated, unsupported)),
+                    operand => format!("+{}", write_clause(operand, unsupported)),
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+        }
+        TsQueryNode::Or(..) => node
+            .flatten_chain(Operators::Or)
+            .iter()
+            .map(|operand| write_clause(operand, unsupported))
+            .collect::<Vec<_>>()
+            .join(" OR "),
+        TsQueryNode::Phrase { .. } => {
+            let Some(words) = phrase_words(node) else {
+                unsupported.push(format!(
+                    "phrase other than a sequence of adjacent lexemes: {}",
+                    describe(node)
+                ));
+                return String::new();
+            };
+
+            let mut phrase = String::from('"');
+            for (i, word) in words.iter().enumerate() {
+                if word.weight != 0 || word.prefix != 0 {
+                    unsupported.push(format!(
+                        "weight or prefix inside a phrase: {}",
+                        describe(node)
+                    ));
+                }
+                if i > 0 {
+                    phrase.push(' ');
+                }
+                for c in word.text.chars() {
+                    if c == '"' || c == '\\' {
+                        phrase.push('\\');
+                    }
+                    phrase.push(c);
+                }
+            }
+            phrase.push('"');
+
+            phrase
+        }
+    }
+}
+
+// An operand of AND or OR, compound ones need parentheses
+fn write_clause(node: &TsQueryNode, unsupported: &mut Vec<String>) -> String {
+    match node {
+        TsQueryNode::And(..) | TsQueryNode::Or(..) => {
+            format!("({})", write_node(node, unsupported))
+        }
+        node => write_node(node, unsupported),
+    }
+}
+
+fn write_term(value: &Value, unsupported: &mut Vec<String>) -> String {
+    if value.weight != 0 {
+        unsupported.push(format!(
+            "weight restriction: {}",
+            describe(&TsQueryNode::Term(value.clone()))
+        ));
+    }
+
+    let mut term = String::new();
+    // a lexeme that reads like an operator
+    if matches!(value.text.as_str(), "AND" | "OR" | "NOT") {
+        term.push('\\');
+    }
+    for c in value.text.chars() {
+        if c.is_whitespace() || is_special(c) {
+            term.push('\\');
+        }
+        term.push(c);
+    }
+
+    if value.prefix != 0 {
+        term.push('*');
+    }
+
+    term
+}
+
+// The words of a chain of `<->` over plain lexemes, in order
+fn phrase_words(node: &TsQueryNode) -> Option<Vec<&Value>> {
+    match node {
+        TsQueryNode::Term(value) => Some(vec![value]),
+        TsQueryNode::Phrase {
+            left,
+            right,
+            distance: 1,
+        } => {
+            let mut words = phrase_words(left)?;
+            words.extend(phrase_words(right)?);
+            Some(words)
+        }
+        _ => None,
+    }
+}
+
+// Characters with a meaning in Lucene's query syntax
+fn is_special(c: char) -> bool {
+    matches!(
+        c,
+        '+' | '-'
+            | '!'
+            | '&'
+            | '|'
+            | '('
+            | ')'
+            | '{'
+            | '}'
+            | '['
+            | ']'
+            | '^'
+            | '"'
+            | '~'
+            | '*'
+            | '?'
+            | ':'
+            | '\\'
+            | '/'
+    )
+}
+
+// Characters that end a term, `+`, `-`, `&` and `|` are allowed within one
+fn ends_term(c: char) -> bool {
+    c.is_whitespace()
+        || matches!(
+            c,
+            '(' | ')' | '{' | '}' | '[' | ']' | '^' | '"' | '~' | ':' | '!' | '/'
+        )
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum Conjunction {
+    None,
+    And,
+    Or,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum Modifier {
+    None,
+    Required,
+    Prohibited,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum Occur {
+    Must,
+    Should,
+    MustNot,
+}
+
+struct LuceneParser<'a> {
+    input: &'a str,
+    pos: usize,
+    default_operator: DefaultOperator,
+    unsupported: Vec<String>,
+}
+
+fn error<T>(position: usize, message: impl Into<String>) -> Result<T, LuceneError> {
+    Err(LuceneError::Syntax {
+        position,
+        message: message.into(),
+    })
+}
+
+impl LuceneParser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek().filter(|c| c.is_whitespace()) {
+            self.pos += c.len_utf8();
+        }
+    }
+
+    // `AND`, `OR` and `NOT` only count as operators when written as a separate word
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let rest = &self.input[self.pos..];
+        let is_word = rest.starts_with(keyword)
+            && rest[keyword.len()..]
+                .chars()
+                .next()
+                .is_none_or(|c| ends_term(c) && c != ':');
+
+        if is_word {
+            self.pos += keyword.len();
+        }
+        is_word
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        let found = self.input[self.pos..].starts_with(token);
+        if found {
+            self.pos += token.len();
+        }
+        found
+    }
+
+    /// A list of clauses up to the end of the input or the closing parenthesis of the
+    /// current group (Query in QueryParser.jj)
+    fn parse_query(&mut self, depth: usize) -> Result<Option<TsQueryNode>, LuceneError> {
+        let mut clauses: Vec<(Occur, Option<TsQueryNode>)> = Vec::new();
+
+        loop {
+            self.skip_whitespace();
+            let start = self.pos;
+
+            match self.peek() {
+                None if depth > 0 => return error(start, "missing ')'"),
+                None => break,
+                Some(')') if depth == 0 => return error(start, "unmatched ')'"),
+                Some(')') => {
+                    self.pos += 1;
+                    break;
+                }
+                Some(_) => {}
+            }
+
+            let conjunction = if self.eat_keyword("AND") || self.eat("&&") {
+                Conjunction::And
+            } else if self.eat_keyword("OR") || self.eat("||") {
+                Conjunction::Or
+            } else {
+                Conjunction::None
+            };
+            if conjunction != Conjunction::None && clauses.is_empty() {
+                return error(start, "expected a term before the operator");
+            }
+            self.skip_whitespace();
+
+            let modifier = if self.eat("+") {
+                Modifier::Required
+            } else if self.eat("-") || self.eat("!") || self.eat_keyword("NOT") {
+                Modifier::Prohibited
+            } else {
+                Modifier::None
+            };
+            self.skip_whitespace();
+
+            if matches!(self.peek(), None | Some(')')) {
+                return error(self.pos, "expected a term after the operator");
+            }
+
+            let query = self.parse_clause(depth)?;
+            self.add_clause(&mut clauses, conjunction, modifier, query);
+        }
+
+        Ok(self.combine(clauses))
+    }
+
+    /// Decides whether a clause is required, optional or prohibited
+    /// (addClause in QueryParserBase.java)
+    fn add_clause(
+        &self,
+        clauses: &mut Vec<(Occur, Option<TsQueryNode>)>,
+        conjunction: Conjunction,
+        modifier: Modifier,
+        query: Option<TsQueryNode>,
+    ) {
+        // an AND makes the preceding clause required, unless it is prohibited
+        if let Some((occur, _)) = clauses.last_mut() {
+            if conjunction == Conjunction::And && *occur != Occur::MustNot {
+                *occur = Occur::Must;
+            }
+            // and with AND as default, an OR makes it optional again
+            if self.default_operator == DefaultOperator::And
+                && conjunction == Conjunction::Or
+                && *occur != Occur::MustNot
+            {
+                *occur = Occur::Should;
+            }
+        }
+
+        let prohibited = modifier == Modifier::Prohibited;
+        let required = match self.default_operator {
+            DefaultOperator::Or => {
+                modifier == Modifier::Required || (conjunction == Conjunction::And && !prohibited)
+            }
+            DefaultOperator::And => !prohibited && conjunction != Conjunction::Or,
+        };
+
+        let occur = match (required, prohibited) {
+            (_, true) => Occur::MustNot,
+            (true, false) => Occur::Must,
+            (false, false) => Occur::Should,
+        };
+
+        clauses.push((occur, query));
+    }
+
+    // Required clauses decide the match on their own, optional ones only
+    // when there is no required one
+    fn combine(&mut self, clauses: Vec<(Occur, Option<TsQueryNode>)>) -> Option<TsQueryNode> {
+        let of = |occur: Occur| -> Vec<TsQueryNode> {
+            clauses
+                .iter()
+                .filter(|(o, _)| *o == occur)
+                .filter_map(|(_, query)| query.clone())
+                .collect()
+        };
+        let (must, should, must_not) = (of(Occur::Must), of(Occur::Should), of(Occur::MustNot));
+
+        let positive = if !must.is_empty() {
+            must.into_iter().reduce(TsQueryNode::and)
+        } else {
+            should.into_iter().reduce(TsQueryNode::or)
+        };
+
+        let Some(positive) = positive else {
+            if let Some(negated) = must_not.into_iter().reduce(TsQueryNode::or) {
+                self.unsupported.push(format!(
+                    "group of negated clauses only, which matches nothing: {}",
+                    describe(&TsQueryNode::negate(negated))
+                ));
+            }
+            return None;
+        };
+
+        Some(must_not.into_iter().fold(positive, |query, negated| {
+            TsQueryNode::and(query, TsQueryNode::negate(negated))
+        }))
+    }
+
+    /// A term, phrase or group with an optional boost. `None` for parts that are
+    /// unsupported or produce no query at all, like an empty phrase.
+    fn parse_clause(&mut self, depth: usize) -> Result<Option<TsQueryNode>, LuceneError> {
+        let start = self.pos;
+
+        let query = match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                self.parse_query(depth + 1)?
+            }
+            Some('"') => self.parse_phrase()?,
+            Some(open @ ('[' | '{')) => {
+                let close = if open == '[' { ']' } else { '}' };
+                match self.input[self.pos..].find(close) {
+                    Some(end) => self.pos += end + 1,
+                    None => return error(start, format!("missing '{close}'")),
+                }
+                self.unsupported
+                    .push(format!("range query: {}", &self.input[start..self.pos]));
+                None
+            }
+            Some('/') => {
+                match self.input[self.pos + 1..].find('/') {
+                    Some(end) => self.pos += end + 2,
+                    None => return error(start, "unterminated regular expression"),
+                }
+                self.unsupported.push(format!(
+                    "regular expression: {}",
+                    &self.input[start..self.pos]
+                ));
+                None
+            }
+            _ => {
+                let (text, wildcards) = self.read_term()?;
+
+                if self.peek() == Some(':') {
+                    self.pos += 1;
+                    self.unsupported.push(format!("field: {text}:"));
+                    self.skip_whitespace();
+                    if matches!(self.peek(), None | Some(')')) {
+                        return error(self.pos, "expected a term after the field");
+                    }
+                    // keep going to report problems in the value as well
+                    return self.parse_clause(depth);
+                }
+
+                if self.peek() == Some('~') {
+                    self.pos += 1;
+                    self.read_number();
+                    self.unsupported
+                        .push(format!("fuzzy term: {}", &self.input[start..self.pos]));
+                }
+
+                self.term_query(text, &wildcards)
+            }
+        };
+
+        // boosts only affect scoring
+        if self.peek() == Some('^') {
+            self.pos += 1;
+            self.read_number();
+        }
+
+        Ok(query)
+    }
+
+    fn term_query(&mut self, text: String, wildcards: &[usize]) -> Option<TsQueryNode> {
+        match wildcards {
+            [] => Some(TsQueryNode::Term(Value::new(text, 0, false))),
+            // a single trailing star is a prefix search
+            [last] if *last == text.len() - 1 && text.ends_with('*') && text.len() > 1 => {
+                let mut text = text;
+                text.pop();
+                Some(TsQueryNode::Term(Value::new(text, 0, true)))
+            }
+            _ if text == "*" => {
+                self.unsupported.push("match all: *".to_string());
+                None
+            }
+            _ => {
+                self.unsupported.push(format!("wildcard term: {text}"));
+                None
+            }
+        }
+    }
+
+    /// Reads a term with its escapes removed, along with the byte offsets of the
+    /// unescaped wildcard characters in it
+    fn read_term(&mut self) -> Result<(String, Vec<usize>), LuceneError> {
+        let start = self.pos;
+        let mut text = String::new();
+        let mut wildcards = Vec::new();
+
+        while let Some(c) = self.peek() {
+            if ends_term(c) {
+                break;
+            }
+            self.pos += c.len_utf8();
+
+            match c {
+                '\\' => match self.peek() {
+                    Some(escaped) => {
+                        self.pos += escaped.len_utf8();
+                        text.push(escaped);
+                    }
+                    None => return error(self.pos, "there is no escaped character"),
+                },
+                '*' | '?' => {
+                    wildcards.push(text.len());
+                    text.push(c);
+                }
+                c => text.push(c),
+            }
+        }
+
+        if text.is_empty() {
+            let c = self.peek().unwrap_or_default();
+            return error(start, format!("unexpected '{c}', expected a term"));
+        }
+
+        Ok((text, wildcards))
+    }
+
+    /// `"words"` with an optional `~slop`, the words joined with `<->`
+    fn parse_phrase(&mut self) -> Result<Option<TsQueryNode>, LuceneError> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut text = String::new();
+
+        loop {
+            match self.peek() {
+                None => return error(start, "unterminated phrase"),
+                Some('"') => {
+                    self.pos += 1;
+                    break;
+                }
+                Some('\\') => {
+                    self.pos += 1;
+                    match self.peek() {
+                        Some(escaped) => {
+                            self.pos += escaped.len_utf8();
+                            text.push(escaped);
+                        }
+                        None => return error(self.pos, "there is no escaped character"),
+                    }
+                }
+                Some(c) => {
+                    self.pos += c.len_utf8();
+                    text.push(c);
+                }
+            }
+        }
+
+        let words: Vec<&str> = text.split_whitespace().collect();
+
+        if self.peek() == Some('~') {
+            self.pos += 1;
+            let slop = self.read_number();
+            if words.len() > 1 && slop.is_some_and(|slop| slop > 0.0) {
+                self.unsupported
+                    .push(format!("sloppy phrase: {}", &self.input[start..self.pos]));
+                return Ok(None);
+            }
+        }
+
+        Ok(words
+            .into_iter()
+            .map(|word| TsQueryNode::Term(Value::new(word, 0, false)))
+            .reduce(|left, right| TsQueryNode::phrase(left, right, 1)))
+    }
+
+    // Number after `~` or `^`, a missing number means the default
+    fn read_number(&mut self) -> Option<f64> {
+        let rest = &self.input[self.pos..];
+        let len = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(rest.len());
+        self.pos += len;
+
+        rest[..len].parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DefaultOperator, LuceneError};
+    use crate::pg_tsquery::PgTsQuery;
+    use std::str::FromStr;
+
+    fn query(s: &str) -> PgTsQuery {
+        PgTsQuery::from_str(s).unwrap()
+    }
+
+    fn from_lucene(lucene: &str, default_operator: DefaultOperator) -> String {
+        PgTsQuery::from_lucene(lucene, default_operator)
+            .unwrap()
+            .to_string()
+    }
+
+    fn unsupported(result: Result<impl std::fmt::Debug, LuceneError>) -> Vec<String> {
+        match result {
+            Err(LuceneError::Unsupported(constructs)) => constructs,
+            other => panic!("expected unsupported constructs, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        let cases = [
+            ("+a -b", "'a' & !'b'"),
+            ("\"a b c\"", "'a' <-> 'b' <-> 'c'"),
+            ("a OR b", "'a' | 'b'"),
+            ("+a +(b OR c)", "'a' & ( 'b' | 'c' )"),
+            ("term*", "'term':*"),
+            ("a\\:b", "'a:b'"),
+            ("a\\ b", "'a b'"),
+            ("\\AND", "'AND'"),
+            ("\"say \\\"hi\\\"\"", "'say' <-> '\"hi\"'"),
+        ];
+
+        for (lucene, tsquery) in cases {
+            for default_operator in [DefaultOperator::Or, DefaultOperator::And] {
+                assert_eq!(from_lucene(lucene, default_operator), tsquery, "{lucene}");
+            }
+            assert_eq!(query(tsquery).to_lucene().unwrap(), lucene, "{tsquery}");
+        }
+    }
+
+    #[test]
+    fn default_operator() {
+        assert_eq!(from_lucene("a b", DefaultOperator::Or), "'a' | 'b'");
+        assert_eq!(from_lucene("a b", DefaultOperator::And), "'a' & 'b'");
+
+        // like Lucene's classic parser, the OR makes `b` optional again with AND as default
+        assert_eq!(
+            from_lucene("a AND b OR c", DefaultOperator::Or),
+            "'a' & 'b'"
+        );
+        assert_eq!(from_lucene("a AND b OR c", DefaultOperator::And), "'a'");
+    }
+
+    #[test]
+    fn phrases_and_wildcards() {
+        let or = DefaultOperator::Or;
+        assert_eq!(from_lucene("\"a b\"~0", or), "'a' <-> 'b'");
+        assert_eq!(
+            unsupported(PgTsQuery::from_lucene("\"a b\"~2", or)),
+            ["sloppy phrase: \"a b\"~2"]
+        );
+
+        assert_eq!(from_lucene("term*", or), "'term':*");
+        assert_eq!(
+            unsupported(PgTsQuery::from_lucene("te*m", or)),
+            ["wildcard term: te*m"]
+        );
+    }
+
+    #[test]
+    fn reports_unsupported_parts() {
+        let constructs = unsupported(PgTsQuery::from_lucene(
+            "title:a AND [a TO c]",
+            DefaultOperator::Or,
+        ));
+        assert_eq!(constructs, ["field: title:", "range query: [a TO c]"]);
+
+        let constructs = unsupported(query("a:A & b <2> c").to_lucene());
+        assert_eq!(
+            constructs,
+            [
+                "weight restriction: 'a':A",
+                "phrase other than a sequence of adjacent lexemes: 'b' <2> 'c'"
+            ]
+        );
+    }
+}
@@ -0,0 +1,417 @@
+use crate::pg_tsquery::{PgTsQuery, Value};
+use crate::pg_tsquery_tree::TsQueryNode;
+use crate::pg_tsvector::{Lexeme, PgTsVector};
+
+// Result of evaluating part of a query. Maybe is returned when a phrase needs
+// positions but the vector was stripped of them.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub(crate) enum Ternary {
+    No,
+    Yes,
+    Maybe,
+}
+
+/// Where a sub query matched, as used for phrase evaluation (ExecPhraseData in ts_utils.h).
+///
+/// With `negate` set the match is everywhere except `positions`. When `width` > 0 the
+/// positions are the ends of the matches, not the starts.
+#[derive(Clone, Debug, Default)]
+struct PhraseData {
+    positions: Vec<i32>,
+    negate: bool,
+    width: i32,
+}
+
+// Which positions to emit when merging two position lists
+const EMIT_LEFT_ONLY: u8 = 0x01;
+const EMIT_RIGHT_ONLY: u8 = 0x02;
+const EMIT_BOTH: u8 = 0x04;
+
+impl PgTsQuery {
+    /// Evaluates `vector @@ query` locally, following `TS_execute` in tsvector_op.c.
+    ///
+    /// Like the server an empty vector or an empty query never matches, and a malformed
+    /// query doesn't match anything either.
+    pub fn matches(&self, vector: &PgTsVector) -> bool {
+        if vector.words.is_empty() {
+            return false;
+        }
+
+        match self.to_tree() {
+            Ok(Some(root)) => {
+                let check =
+                    |value: &Value, want_positions| check_operand(vector, value, want_positions);
+                execute(&root, &check) != Ternary::No
+            }
+            _ => false,
+        }
+    }
+}
+
+impl PgTsVector {
+    /// Same as [`PgTsQuery::matches`], mirroring the commutator `query @@ vector`
+    pub fn matches(&self, query: &PgTsQuery) -> bool {
+        query.matches(self)
+    }
+}
+
+/// Evaluates a query tree (TS_execute in tsvector_op.c). `check` looks up an operand
+/// and returns where it occurs when asked for positions, which phrases need.
+pub(crate) fn execute<F>(node: &TsQueryNode, check: &F) -> Ternary
+where
+    F: Fn(&Value, bool) -> (Ternary, Vec<i32>),
+{
+    match node {
+        TsQueryNode::Term(value) => check(value, false).0,
+        TsQueryNode::Not(operand) => match execute(operand, check) {
+            Ternary::Yes => Ternary::No,
+            Ternary::No => Ternary::Yes,
+            Ternary::Maybe => Ternary::Maybe,
+        },
+        TsQueryNode::And(left, right) => {
+            let left = execute(left, check);
+            if left == Ternary::No {
+                return Ternary::No;
+            }
+            match execute(right, check) {
+                Ternary::Yes => left,
+                other => other,
+            }
+        }
+        TsQueryNode::Or(left, right) => {
+            let left = execute(left, check);
+            if left == Ternary::Yes {
+                return Ternary::Yes;
+            }
+            match execute(right, check) {
+                Ternary::No => left,
+                other => other,
+            }
+        }
+        // Without positions a phrase can't be confirmed, which counts as no match
+        TsQueryNode::Phrase { .. } => match phrase_execute(node, check).0 {
+            Ternary::Yes => Ternary::Yes,
+            _ => Ternary::No,
+        },
+    }
+}
+
+/// Evaluates a node below a phrase operator, where match positions matter
+/// (TS_phrase_execute in tsvector_op.c)
+fn phrase_execute<F>(node: &TsQueryNode, check: &F) -> (Ternary, PhraseData)
+where
+    F: Fn(&Value, bool) -> (Ternary, Vec<i32>),
+{
+    match node {
+        TsQueryNode::Term(value) => {
+            let (result, positions) = check(value, true);
+            let data = PhraseData {
+                positions,
+                ..Default::default()
+            };
+            (result, data)
+        }
+        TsQueryNode::Not(operand) => {
+            // NOT doesn't change the match width
+            let (result, mut data) = phrase_execute(operand, check);
+            match result {
+                Ternary::No => {
+                    // "match nowhere" becomes "match everywhere"
+                    data.positions.clear();
+                    data.negate = true;
+                    (Ternary::Yes, data)
+                }
+                Ternary::Yes if !data.positions.is_empty() => {
+                    data.negate = !data.negate;
+                    (Ternary::Yes, data)
+                }
+                Ternary::Yes => {
+                    // "match everywhere" becomes "match nowhere"
+                    data.negate = false;
+                    (Ternary::No, data)
+                }
+                Ternary::Maybe => (Ternary::Maybe, PhraseData::default()),
+            }
+        }
+        TsQueryNode::And(left, right) | TsQueryNode::Phrase { left, right, .. } => {
+            let (left_match, left) = phrase_execute(left, check);
+            if left_match == Ternary::No {
+                return (Ternary::No, PhraseData::default());
+            }
+            let (right_match, right) = phrase_execute(right, check);
+            if right_match == Ternary::No {
+                return (Ternary::No, PhraseData::default());
+            }
+
+            // Without position information we can only say maybe
+            if left_match == Ternary::Maybe || right_match == Ternary::Maybe {
+                return (Ternary::Maybe, PhraseData::default());
+            }
+
+            let (left_offset, right_offset, width) = match node {
+                TsQueryNode::Phrase { distance, .. } => {
+                    let distance = i32::from(*distance);
+                    (
+                        distance + right.width,
+                        0,
+                        distance + left.width + right.width,
+                    )
+                }
+                // AND aligns the match positions like OR does
+                _ => {
+                    let width = left.width.max(right.width);
+                    (width - left.width, width - right.width, width)
+                }
+            };
+
+            let output = |emit| phrase_output(&left, &right, emit, left_offset, right_offset);
+            let data = |positions, negate| PhraseData {
+                positions,
+                negate,
+                width,
+            };
+
+            match (left.negate, right.negate) {
+                // !L <-> !R => !(L | R)
+                (true, true) => (
+                    Ternary::Yes,
+                    data(output(EMIT_BOTH | EMIT_LEFT_ONLY | EMIT_RIGHT_ONLY), true),
+                ),
+                // !L <-> R => R & !L
+                (true, false) => found(data(output(EMIT_RIGHT_ONLY), false)),
+                // L <-> !R => L & !R
+                (false, true) => found(data(output(EMIT_LEFT_ONLY), false)),
+                (false, false) => found(data(output(EMIT_BOTH), false)),
+            }
+        }
+        TsQueryNode::Or(left, right) => {
+            let (left_match, mut left) = phrase_execute(left, check);
+            let (right_match, mut right) = phrase_execute(right, check);
+
+            if left_match == Ternary::No && right_match == Ternary::No {
+                return (Ternary::No, PhraseData::default());
+            }
+            if left_match == Ternary::Maybe || right_match == Ternary::Maybe {
+                return (Ternary::Maybe, PhraseData::default());
+            }
+
+            // The width of a failed branch is undefined
+            if left_match == Ternary::No {
+                left.width = 0;
+            }
+            if right_match == Ternary::No {
+                right.width = 0;
+            }
+
+            // OR has to align the match positions of both sides itself
+            let width = left.width.max(right.width);
+            let left_offset = width - left.width;
+            let right_offset = width - right.width;
+
+            let output = |emit| phrase_output(&left, &right, emit, left_offset, right_offset);
+            let data = |positions, negate| PhraseData {
+                positions,
+                negate,
+                width,
+            };
+
+            match (left.negate, right.negate) {
+                // !L | !R => !(L & R)
+                (true, true) => (Ternary::Yes, data(output(EMIT_BOTH), true)),
+                // !L | R => !(L & !R)
+                (true, false) => (Ternary::Yes, data(output(EMIT_LEFT_ONLY), true)),
+                // L | !R => !(!L & R)
+                (false, true) => (Ternary::Yes, data(output(EMIT_RIGHT_ONLY), true)),
+                (false, false) => found(data(
+                    output(EMIT_BOTH | EMIT_LEFT_ONLY | EMIT_RIGHT_ONLY),
+                    false,
+                )),
+            }
+        }
+    }
+}
+
+// A failed match keeps its width, NOT turns it into a match everywhere of that width
+fn found(data: PhraseData) -> (Ternary, PhraseData) {
+    if data.positions.is_empty() {
+        (
+            Ternary::No,
+            PhraseData {
+                positions: vec![],
+                width: data.width,
+                ..Default::default()
+            },
+        )
+    } else {
+        (Ternary::Yes, data)
+    }
+}
+
+/// Merge-joins two sorted position lists, keeping the positions selected by `emit`
+/// (TS_phrase_output in tsvector_op.c)
+fn phrase_output(
+    left: &PhraseData,
+    right: &PhraseData,
+    emit: u8,
+    left_offset: i32,
+    right_offset: i32,
+) -> Vec<i32> {
+    let mut output = Vec::new();
+    let (mut l, mut r) = (0, 0);
+
+    while l < left.positions.len() || r < right.positions.len() {
+        let left_pos = match left.positions.get(l) {
+            Some(pos) => pos + left_offset,
+            None if emit & EMIT_RIGHT_ONLY == 0 => break,
+            None => i32::MAX,
+        };
+        let right_pos = match right.positions.get(r) {
+            Some(pos) => pos + right_offset,
+            None if emit & EMIT_LEFT_ONLY == 0 => break,
+            None => i32::MAX,
+        };
+
+        let mut output_pos = 0;
+        if left_pos < right_pos {
+            if emit & EMIT_LEFT_ONLY != 0 {
+                output_pos = left_pos;
+            }
+            l += 1;
+        } else if left_pos == right_pos {
+            if emit & EMIT_BOTH != 0 {
+                output_pos = right_pos;
+            }
+            l += 1;
+            r += 1;
+        } else {
+            if emit & EMIT_RIGHT_ONLY != 0 {
+                output_pos = right_pos;
+            }
+            r += 1;
+        }
+
+        if output_pos > 0 {
+            output.push(output_pos);
+        }
+    }
+
+    output
+}
+
+/// Looks up an operand in the vector (checkcondition_str in tsvector_op.c).
+/// Positions are only collected when `want_positions` is set.
+fn check_operand(vector: &PgTsVector, value: &Value, want_positions: bool) -> (Ternary, Vec<i32>) {
+    let mut result = (Ternary::No, vec![]);
+
+    if let Some(lexeme) = vector.words.iter().find(|lexeme| lexeme.word == value.text) {
+        result = check_class(lexeme, value, want_positions);
+    }
+
+    // A prefix also matches every lexeme that starts with it. This can be skipped
+    // when the exact term already matched and positions don't matter.
+    if value.prefix != 0 && (result.0 != Ternary::Yes || want_positions) {
+        let mut matched = Ternary::No;
+        let mut all_positions = Vec::new();
+
+        let candidates = vector
+            .words
+            .iter()
+            .filter(|lexeme| lexeme.word.starts_with(&value.text));
+
+        for lexeme in candidates {
+            let (sub_result, positions) = check_class(lexeme, value, want_positions);
+
+            match sub_result {
+                Ternary::No => {}
+                // No position info for this match, so the whole operand is a maybe
+                Ternary::Maybe if want_positions => {
+                    matched = Ternary::Maybe;
+                    all_positions.clear();
+                    break;
+                }
+                _ if want_positions => all_positions.extend(positions),
+                _ => {
+                    if sub_result == Ternary::Yes || matched == Ternary::No {
+                        matched = sub_result;
+                    }
+                    if matched == Ternary::Yes {
+                        break;
+                    }
+                }
+            }
+        }
+
+        if !all_positions.is_empty() {
+            all_positions.sort_unstable();
+            all_positions.dedup();
+            matched = Ternary::Yes;
+        }
+
+        result = (matched, all_positions);
+    }
+
+    result
+}
+
+/// Checks the weight restriction of an operand against one lexeme
+/// (checkclass_str in tsvector_op.c)
+fn check_class(lexeme: &Lexeme, value: &Value, want_positions: bool) -> (Ternary, Vec<i32>) {
+    if lexeme.positions.is_empty() {
+        // Stripped vectors match regardless of the weight restriction, but a phrase
+        // can only be a maybe without positions
+        let result = if want_positions {
+            Ternary::Maybe
+        } else {
+            Ternary::Yes
+        };
+        return (result, vec![]);
+    }
+
+    let mut positions: Vec<i32> = lexeme
+        .positions
+        .iter()
+        .filter(|position| value.weight == 0 || value.weights().contains(position.weight.into()))
+        .map(|position| i32::from(position.pos))
+        .collect();
+    positions.sort_unstable();
+    positions.dedup();
+
+    if positions.is_empty() {
+        return (Ternary::No, vec![]);
+    }
+
+    if want_positions {
+        (Ternary::Yes, positions)
+    } else {
+        (Ternary::Yes, vec![])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pg_tsquery::PgTsQuery;
+    use crate::pg_tsvector::PgTsVector;
+    use std::str::FromStr;
+
+    // (vector, query, vector @@ query) captured from PostgreSQL 15
+    const CASES: &[(&str, &str, bool)] = &[
+        ("'a':1 'd':3 'x':2", "a <-> !(b <-> c) <-> d", true),
+        ("'a':1 'b':3 'c':2 'd':4", "a <-> !(b <-> c) <-> d", true),
+        ("'a':1 'b':2 'c':4 'd':3", "a <-> !(b <-> c) <-> d", false),
+        ("'a':1,2 'b':4 'c':3 'd':5", "a <-> !(b <-> c) <-> d", true),
+        ("'a':1,3 'b':2 'c':4 'd':5", "a <-> !(b <-> c) <-> d", false),
+        ("'a':1 'b':3 'c':2 'd':4", "a <-> !(b <-> c) <2> d", false),
+        ("'a':1,3 'b':2 'c':4 'd':5", "a <-> !(b <-> c) <2> d", true),
+        ("'a':1 'b':2 'c':4 'd':3", "a <-> !(b <2> c)", false),
+        ("'a':1 'b':3 'c':2 'd':4", "a <-> !(b <2> c)", true),
+    ];
+
+    #[test]
+    fn negated_phrase_keeps_its_width() {
+        for (vector, query, expected) in CASES {
+            let vector = PgTsVector::from_str(vector).unwrap();
+            let query = PgTsQuery::from_str(query).unwrap();
+            assert_eq!(vector.matches(&query), *expected, "{vector} @@ {query}");
+        }
+    }
+}
@@ -0,0 +1,590 @@
+use crate::pg_tsquery::{
+    Entry, MAX_QUERY_DEPTH, Operator, Operators, PgTsQuery, Value, tree_depth,
+};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+// Limits taken from ts_type.h / tsquery.c so that we reject the same inputs as the server
+const MAX_OPERAND_LEN: usize = (1 << 11) - 1;
+const MAX_PHRASE_DISTANCE: u32 = 1 << 14;
+const STACK_DEPTH: usize = 32;
+
+/// Error returned when a tsquery string cannot be parsed.
+/// `position` is the byte offset in the input where the problem was found.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TsQueryParseError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for TsQueryParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "syntax error in tsquery at position {}: {}",
+            self.position, self.message
+        )
+    }
+}
+
+impl Error for TsQueryParseError {}
+
+impl FromStr for PgTsQuery {
+    type Err = TsQueryParseError;
+
+    /// Parse the text form of a tsquery the same way `tsquery_in` does.
+    ///
+    /// The resulting `entries` are laid out exactly like the server sends them over the wire,
+    /// i.e. operator first, then its right operand and finally its left operand.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let items = parse_query(s, ParseMode::Standard, &mut |value, output| {
+            output.push(Item::Value(value))
+        })?;
+
+        // makepol produces the usual reverse polish order (left, right, operator)
+        let entries = items
+            .into_iter()
+            .rev()
+            .filter_map(|item| match item {
+                Item::Value(value) => Some(Entry::Value(value)),
+                Item::Operator(op) => Some(Entry::Operator(op)),
+                Item::Stop => None,
+            })
+            .collect();
+
+        Ok(PgTsQuery { entries })
+    }
+}
+
+/// Input grammars understood by the parser (P_TSQ_WEB in tsquery.c)
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub(crate) enum ParseMode {
+    /// Regular tsquery syntax, as used by `tsquery_in` and `to_tsquery`
+    Standard,
+    /// Search engine style syntax, as used by `websearch_to_tsquery`
+    Web,
+}
+
+/// Reverse polish output of the parser
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Item {
+    Value(Value),
+    Operator(Operator),
+    /// Placeholder for a stop word, removed again once the query is complete
+    Stop,
+}
+
+/// Parses `input` into reverse polish order, handing every operand to `pushval`
+/// which decides what to emit for it (parse_tsquery in tsquery.c)
+pub(crate) fn parse_query<F>(
+    input: &str,
+    mode: ParseMode,
+    pushval: &mut F,
+) -> Result<Vec<Item>, TsQueryParseError>
+where
+    F: FnMut(Value, &mut Vec<Item>),
+{
+    let mut parser = Parser {
+        input,
+        pos: 0,
+        depth: 0,
+        state: State::FirstOperand,
+        mode,
+    };
+
+    let mut output = Vec::new();
+    parser.parse_expression(&mut output, pushval)?;
+
+    let arities = output.iter().map(|item| match item {
+        Item::Value(_) | Item::Stop => 0,
+        Item::Operator(op) if op.operator == Operators::Not => 1,
+        Item::Operator(_) => 2,
+    });
+    if tree_depth(arities) > MAX_QUERY_DEPTH {
+        return error(input.len(), "stack depth limit exceeded");
+    }
+
+    Ok(output)
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum State {
+    FirstOperand,
+    Operand,
+    Operator,
+}
+
+enum Token {
+    Value(Value),
+    Operator(Operator),
+    Stop,
+    Open,
+    Close,
+    End,
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+    depth: i32,
+    state: State,
+    mode: ParseMode,
+}
+
+// Characters that terminate an unquoted operand (ISOPERATOR in tsvector_parser.c)
+fn is_operator(c: char) -> bool {
+    matches!(c, '!' | '&' | '|' | '(' | ')' | '<')
+}
+
+fn error<T>(position: usize, message: impl Into<String>) -> Result<T, TsQueryParseError> {
+    Err(TsQueryParseError {
+        position,
+        message: message.into(),
+    })
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn next_char(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    /// Parses operands and operators up to the end of the input. Parentheses start a new
+    /// operator stack instead of recursing, so deep nesting can't overflow the stack.
+    fn parse_expression<F>(
+        &mut self,
+        output: &mut Vec<Item>,
+        pushval: &mut F,
+    ) -> Result<(), TsQueryParseError>
+    where
+        F: FnMut(Value, &mut Vec<Item>),
+    {
+        let mut stack: Vec<Operator> = Vec::new();
+        let mut outer: Vec<Vec<Operator>> = Vec::new();
+
+        loop {
+            let start = self.pos;
+
+            let token = match self.mode {
+                ParseMode::Standard => self.next_token()?,
+                ParseMode::Web => self.next_token_web(),
+            };
+
+            match token {
+                Token::Value(value) => pushval(value, output),
+                Token::Stop => output.push(Item::Stop),
+                Token::Operator(op) => {
+                    clean_stack(&mut stack, output, op.operator);
+                    if stack.len() == STACK_DEPTH {
+                        return error(start, "tsquery stack too small");
+                    }
+                    stack.push(op);
+                }
+                Token::Open => outer.push(std::mem::take(&mut stack)),
+                Token::Close => {
+                    clean_stack(&mut stack, output, Operators::Or);
+                    match outer.pop() {
+                        Some(parent) => stack = parent,
+                        None => return Ok(()),
+                    }
+                }
+                Token::End => {
+                    clean_stack(&mut stack, output, Operators::Or);
+                    while let Some(mut parent) = outer.pop() {
+                        clean_stack(&mut parent, output, Operators::Or);
+                    }
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    fn next_token(&mut self) -> Result<Token, TsQueryParseError> {
+        loop {
+            let start = self.pos;
+
+            let Some(c) = self.peek() else {
+                return match self.state {
+                    State::FirstOperand => Ok(Token::End),
+                    State::Operand => error(start, "unexpected end of input, expected operand"),
+                    State::Operator if self.depth > 0 => {
+                        error(start, "unexpected end of input, expected ')'")
+                    }
+                    State::Operator => Ok(Token::End),
+                };
+            };
+
+            if c.is_whitespace() {
+                self.pos += c.len_utf8();
+                continue;
+            }
+
+            match self.state {
+                State::FirstOperand | State::Operand => match c {
+                    '!' => {
+                        self.pos += 1;
+                        self.state = State::Operand;
+                        return Ok(Token::Operator(Operator {
+                            operator: Operators::Not,
+                            distance: None,
+                        }));
+                    }
+                    '(' => {
+                        self.pos += 1;
+                        self.state = State::Operand;
+                        self.depth += 1;
+                        return Ok(Token::Open);
+                    }
+                    ':' => return error(start, "unexpected ':', expected operand"),
+                    _ => {
+                        let value = self.read_operand()?;
+                        self.state = State::Operator;
+                        return Ok(Token::Value(value));
+                    }
+                },
+                State::Operator => {
+                    let (operator, distance) = match c {
+                        '&' => {
+                            self.pos += 1;
+                            (Operators::And, None)
+                        }
+                        '|' => {
+                            self.pos += 1;
+                            (Operators::Or, None)
+                        }
+                        '<' => (Operators::Phrase, Some(self.read_phrase_distance()?)),
+                        ')' => {
+                            self.pos += 1;
+                            self.depth -= 1;
+                            if self.depth < 0 {
+                                return error(start, "unmatched ')'");
+                            }
+                            return Ok(Token::Close);
+                        }
+                        _ => return error(start, format!("unexpected '{c}', expected operator")),
+                    };
+
+                    self.state = State::Operand;
+                    return Ok(Token::Operator(Operator { operator, distance }));
+                }
+            }
+        }
+    }
+
+    /// Tokenizer for websearch_to_tsquery (gettoken_query_websearch in tsquery.c).
+    ///
+    /// Never fails: anything that doesn't look like an operator becomes part of an operand,
+    /// and a dangling operator gets a stop word placeholder as its operand.
+    /// Quoted text, up to the closing quote or the end of the input, is a single operand
+    /// whose words the caller joins with `<->`.
+    fn next_token_web(&mut self) -> Token {
+        loop {
+            let c = self.peek();
+
+            match self.state {
+                State::FirstOperand | State::Operand => match c {
+                    Some('-') => {
+                        self.pos += 1;
+                        self.state = State::Operand;
+                        return Token::Operator(Operator {
+                            operator: Operators::Not,
+                            distance: None,
+                        });
+                    }
+                    Some('"') => {
+                        self.pos += 1;
+                        let rest = &self.input[self.pos..];
+                        let len = rest.find('"').unwrap_or(rest.len());
+                        let text = &rest[..len];
+
+                        // skip the closing quote, if there is one
+                        self.pos = (self.pos + len + 1).min(self.input.len());
+                        self.state = State::Operator;
+                        return Token::Value(Value::new(text, 0, false));
+                    }
+                    Some(c) if is_operator(c) => {
+                        self.pos += 1;
+                        self.state = State::Operand;
+                    }
+                    Some(c) if c.is_whitespace() => self.pos += c.len_utf8(),
+                    Some(c) => {
+                        // Unlike the standard syntax quotes and backslashes aren't special here
+                        let mut text = String::from(c);
+                        self.pos += c.len_utf8();
+
+                        while let Some(c) = self.peek() {
+                            if c.is_whitespace() || is_operator(c) || c == '"' || c == ':' {
+                                break;
+                            }
+                            text.push(c);
+                            self.pos += c.len_utf8();
+                        }
+
+                        self.state = State::Operator;
+                        return Token::Value(Value::new(text, 0, false));
+                    }
+                    None if self.state == State::FirstOperand => return Token::End,
+                    None => {
+                        // an operator still needs an operand
+                        self.state = State::Operator;
+                        return Token::Stop;
+                    }
+                },
+                State::Operator => {
+                    let operator = match c {
+                        // handle the quote when waiting for an operand, with an implicit AND
+                        Some('"') => Operators::And,
+                        _ if self.at_or_operator() => {
+                            self.pos += 2;
+                            Operators::Or
+                        }
+                        None => return Token::End,
+                        // stray operator characters are skipped like blanks, so `or` may follow
+                        Some(c) if c.is_whitespace() || is_operator(c) => {
+                            self.pos += c.len_utf8();
+                            continue;
+                        }
+                        Some(_) => Operators::And,
+                    };
+
+                    self.state = State::Operand;
+                    return Token::Operator(Operator {
+                        operator,
+                        distance: None,
+                    });
+                }
+            }
+        }
+    }
+
+    /// `or` written as a separate word and followed by something else (parse_or_operator in tsquery.c)
+    fn at_or_operator(&self) -> bool {
+        let rest = &self.input[self.pos..];
+        if !rest
+            .get(..2)
+            .is_some_and(|or| or.eq_ignore_ascii_case("or"))
+        {
+            return false;
+        }
+
+        let mut chars = rest[2..].chars();
+        match chars.next() {
+            None => false,
+            Some(c) if c == '-' || c == '_' || c.is_alphanumeric() => false,
+            Some(_) => chars.any(|c| !c.is_whitespace()),
+        }
+    }
+
+    /// Reads `<->` or `<N>` like parse_phrase_operator in tsquery.c
+    fn read_phrase_distance(&mut self) -> Result<i16, TsQueryParseError> {
+        let start = self.pos;
+        let rest = &self.input[self.pos + 1..];
+
+        let (distance, len) = if rest.starts_with('-') {
+            (1, 1)
+        } else {
+            let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+            if digits == 0 {
+                return error(start, "invalid phrase operator, expected <-> or <N>");
+            }
+            match rest[..digits].parse::<u32>() {
+                Ok(distance) if distance <= MAX_PHRASE_DISTANCE => (distance as i16, digits),
+                _ => {
+                    return error(
+                        start,
+                        format!(
+                            "distance in phrase operator must be an integer value between zero and {MAX_PHRASE_DISTANCE} inclusive"
+                        ),
+                    );
+                }
+            }
+        };
+
+        if !rest[len..].starts_with('>') {
+            return error(start, "invalid phrase operator, expected <-> or <N>");
+        }
+        self.pos += len + 2;
+
+        Ok(distance)
+    }
+
+    /// Reads a quoted or unquoted operand followed by optional `:` modifiers
+    fn read_operand(&mut self) -> Result<Value, TsQueryParseError> {
+        let start = self.pos;
+        let mut text = String::new();
+
+        if self.peek() == Some('\'') {
+            self.pos += 1;
+            loop {
+                match self.next_char() {
+                    None => return error(start, "unterminated quoted operand"),
+                    Some('\'') if self.peek() == Some('\'') => {
+                        self.pos += 1;
+                        text.push('\'');
+                    }
+                    Some('\'') => break,
+                    Some('\\') => text.push(self.read_escaped()?),
+                    Some(c) => text.push(c),
+                }
+            }
+
+            if text.is_empty() {
+                return error(start, "empty quoted operand");
+            }
+        } else {
+            while let Some(c) = self.peek() {
+                if c.is_whitespace() || is_operator(c) || c == ':' {
+                    break;
+                }
+                self.pos += c.len_utf8();
+                if c == '\\' {
+                    text.push(self.read_escaped()?);
+                } else {
+                    text.push(c);
+                }
+            }
+
+            if text.is_empty() {
+                let c = self.peek().unwrap_or_default();
+                return error(start, format!("unexpected '{c}', expected operand"));
+            }
+        }
+
+        if text.len() >= MAX_OPERAND_LEN {
+            return error(
+                start,
+                format!("operand is too long in tsquery: length={}", text.len()),
+            );
+        }
+
+        let (weight, prefix) = self.read_modifiers();
+
+        Ok(Value::new(text, weight, prefix))
+    }
+
+    fn read_escaped(&mut self) -> Result<char, TsQueryParseError> {
+        match self.next_char() {
+            Some(c) => Ok(c),
+            None => error(self.pos, "there is no escaped character"),
+        }
+    }
+
+    /// Weight letters and `*` after a colon, in any order (get_modifiers in tsquery.c)
+    fn read_modifiers(&mut self) -> (u8, bool) {
+        let mut weight = 0;
+        let mut prefix = false;
+
+        if self.peek() != Some(':') {
+            return (weight, prefix);
+        }
+        self.pos += 1;
+
+        while let Some(c) = self.peek() {
+            match c {
+                'a' | 'A' => weight |= 1 << 3,
+                'b' | 'B' => weight |= 1 << 2,
+                'c' | 'C' => weight |= 1 << 1,
+                'd' | 'D' => weight |= 1,
+                '*' => prefix = true,
+                _ => break,
+            }
+            self.pos += 1;
+        }
+
+        (weight, prefix)
+    }
+}
+
+// Pop every operator that binds at least as tightly as `op` (cleanOpStack in tsquery.c).
+// NOT is right associative, so it never pops anything.
+fn clean_stack(stack: &mut Vec<Operator>, output: &mut Vec<Item>, op: Operators) {
+    let op_priority = op.priority();
+
+    while let Some(top) = stack.last() {
+        let top_priority = top.operator.priority();
+        if (op != Operators::Not && op_priority > top_priority)
+            || (op == Operators::Not && op_priority >= top_priority)
+        {
+            break;
+        }
+
+        if let Some(top) = stack.pop() {
+            output.push(Item::Operator(top));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pg_tsquery::PgTsQuery;
+    use std::str::FromStr;
+
+    // Inputs for which SELECT input::tsquery fails on PostgreSQL 15
+    const REJECTED: &[&str] = &[
+        "a &",
+        "&a",
+        "a | !",
+        "!",
+        "()",
+        "(a",
+        "a)",
+        "a & ) b",
+        "a & (b",
+        "a b",
+        ":a",
+        "a\\",
+        "'a",
+        "''",
+        "'' & a",
+        "a:*Z",
+        "a <-x> b",
+        "a <1",
+        "a < 2 > b",
+        "a <16385> b",
+    ];
+
+    #[test]
+    fn rejects_like_tsquery_in() {
+        for input in REJECTED {
+            assert!(PgTsQuery::from_str(input).is_err(), "input: {input}");
+        }
+    }
+
+    #[test]
+    fn accepts_what_tsquery_in_accepts() {
+        for (input, expected) in [("a:", "'a'"), ("a <0> b", "'a' <0> 'b'")] {
+            let query = PgTsQuery::from_str(input).unwrap();
+            assert_eq!(query.to_string(), expected, "input: {input}");
+        }
+    }
+
+    #[test]
+    fn deep_nesting() {
+        let depth = 100_000;
+        let input = format!("{}a{}", "(".repeat(depth), ")".repeat(depth));
+
+        // run on a small stack so a recursive parser would overflow it
+        let query = std::thread::Builder::new()
+            .stack_size(256 * 1024)
+            .spawn(move || PgTsQuery::from_str(&input).map(|query| query.to_string()))
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(query.unwrap(), "'a'");
+
+        let unbalanced = format!("{}a{}", "(".repeat(depth), ")".repeat(depth - 1));
+        assert!(PgTsQuery::from_str(&unbalanced).is_err());
+    }
+
+    #[test]
+    fn operand_length_limit() {
+        assert!(PgTsQuery::from_str(&"a".repeat(2046)).is_ok());
+        assert!(PgTsQuery::from_str(&"a".repeat(2047)).is_err());
+    }
+}
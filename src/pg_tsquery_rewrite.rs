@@ -0,0 +1,449 @@
+use crate::pg_tsquery::{Operator, Operators, PgTsQuery, Value};
+use crate::pg_tsquery_tree::TsQueryNode;
+use std::cmp::Ordering;
+
+impl PgTsQuery {
+    /// Same as `ts_rewrite(query, target, substitute)`: every occurrence of `target` is
+    /// replaced with `substitute`, an empty substitute removes the occurrence.
+    ///
+    /// Sub queries are compared the way the server does it: AND and OR are matched
+    /// regardless of the order or grouping of their operands, so `b & c` is also found in
+    /// `a & c & b`, and weights and prefix flags of operands are ignored. Like the server
+    /// the operands of AND and OR come back in the server's sort order, unless the target
+    /// is empty, which returns the query unchanged.
+    pub fn rewrite(&self, target: &PgTsQuery, substitute: &PgTsQuery) -> PgTsQuery {
+        self.rewrite_with([(target, substitute)], false)
+    }
+
+    /// Same as `ts_rewrite(query, select)`: applies every `(target, substitute)` pair in
+    /// order, each rule seeing the result of the previous one.
+    ///
+    /// A rule never rewrites the text it put in place itself, but later rules do, so
+    /// `a → b` followed by `b → c` turns `a` into `c`. As on the server, an operator whose
+    /// operands were rewritten is still matched against its original operands, which keeps
+    /// `a & b → x` followed by `x & c → y` from changing `a & b & c` into `y`.
+    /// Rules with an empty target are skipped and malformed queries are returned unchanged.
+    pub fn rewrite_all<'a, I>(&self, rules: I) -> PgTsQuery
+    where
+        I: IntoIterator<Item = (&'a PgTsQuery, &'a PgTsQuery)>,
+    {
+        self.rewrite_with(rules, true)
+    }
+
+    // Unlike the single rule form, the batch form clears the no-change marks and brings
+    // the tree back into sorted shape after every rule (tsquery_rewrite_query in tsrewrite.c)
+    fn rewrite_with<'a, I>(&self, rules: I, resort: bool) -> PgTsQuery
+    where
+        I: IntoIterator<Item = (&'a PgTsQuery, &'a PgTsQuery)>,
+    {
+        let rules: Vec<_> = rules
+            .into_iter()
+            .filter_map(|(target, substitute)| {
+                let Ok(Some(target)) = target.to_tree() else {
+                    return None;
+                };
+                let substitute = substitute.to_tree().ok()?;
+                Some((target, substitute))
+            })
+            .collect();
+
+        // The single rule form returns the query as it is, unsorted, if it has no rule
+        // to apply (tsquery_rewrite in tsrewrite.c)
+        if rules.is_empty() && !resort {
+            return self.clone();
+        }
+
+        let Ok(Some(root)) = self.to_tree() else {
+            return self.clone();
+        };
+
+        let mut tree = QtNode::from_tree(&root);
+        tree.ternary();
+        tree.sort();
+
+        for (target, substitute) in rules {
+            let mut target = QtNode::from_tree(&target);
+            target.ternary();
+            target.sort();
+            let substitute = substitute.as_ref().map(QtNode::from_tree);
+
+            match tree.find_subquery(&target, substitute.as_ref()) {
+                Some(rewritten) => tree = rewritten,
+                None => return PgTsQuery { entries: vec![] },
+            }
+
+            if resort {
+                tree.clear_no_change();
+                tree.ternary();
+                tree.sort();
+            }
+        }
+
+        tree.into_tree().into()
+    }
+}
+
+/// Query node with any number of operands (QTNode in ts_utils.h).
+///
+/// Like the server the operands of a binary operator are stored right first, which
+/// matters for the sort order and for how chains are split up again.
+#[derive(Clone, Debug)]
+pub(crate) struct QtNode {
+    pub(crate) item: QtItem,
+    pub(crate) children: Vec<QtNode>,
+    // One bit per operand checksum below the node, computed once when the node is built
+    // and, like on the server, not updated when a rule rewrites the operands (sign)
+    sign: u32,
+    // Set on substituted nodes so they aren't searched again (QTN_NOCHANGE)
+    no_change: bool,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum QtItem {
+    /// Operand together with the checksum the server sorts operands by
+    Value(Value, i32),
+    Operator(Operator),
+}
+
+impl QtNode {
+    /// Converts a binary tree, same as QT2QTN in tsquery_util.c
+    pub(crate) fn from_tree(node: &TsQueryNode) -> QtNode {
+        let (item, children) = match node {
+            TsQueryNode::Term(value) => (QtItem::Value(value.clone(), valcrc(&value.text)), vec![]),
+            TsQueryNode::Not(operand) => (
+                QtItem::Operator(Operator {
+                    operator: Operators::Not,
+                    distance: None,
+                }),
+                vec![QtNode::from_tree(operand)],
+            ),
+            TsQueryNode::And(left, right)
+            | TsQueryNode::Or(left, right)
+            | TsQueryNode::Phrase { left, right, .. } => {
+                let operator = node.operator().expect("binary node has an operator");
+                (
+                    QtItem::Operator(operator),
+                    vec![QtNode::from_tree(right), QtNode::from_tree(left)],
+                )
+            }
+        };
+
+        let sign = match &item {
+            QtItem::Value(_, crc) => 1 << (*crc as u32 % 32),
+            QtItem::Operator(_) => children.iter().fold(0, |sign, child| sign | child.sign),
+        };
+
+        QtNode {
+            item,
+            children,
+            sign,
+            no_change: false,
+        }
+    }
+
+    /// Splits n-ary operators up again (QTNBinary in tsquery_util.c)
+    pub(crate) fn into_tree(self) -> TsQueryNode {
+        let operator = match self.item {
+            QtItem::Value(value, _) => return TsQueryNode::Term(value),
+            QtItem::Operator(operator) => operator,
+        };
+
+        let mut children = self.children;
+        while children.len() > 2 {
+            let last = children.pop().expect("more than two operands");
+            let right = children.remove(0);
+            let left = children.remove(0);
+            let pair = QtNode {
+                item: QtItem::Operator(operator),
+                sign: right.sign | left.sign,
+                children: vec![right, left],
+                no_change: false,
+            };
+            children.insert(0, pair);
+            children.insert(1, last);
+        }
+
+        let mut children = children.into_iter().map(QtNode::into_tree);
+        let right = children.next().expect("operator has an operand");
+
+        match (operator.operator, children.next()) {
+            (Operators::And, Some(left)) => TsQueryNode::and(left, right),
+            (Operators::Or, Some(left)) => TsQueryNode::or(left, right),
+            (Operators::Phrase, Some(left)) => {
+                TsQueryNode::phrase(left, right, operator.distance.unwrap_or(1))
+            }
+            _ => TsQueryNode::negate(right),
+        }
+    }
+
+    fn operator(&self) -> Option<Operators> {
+        match &self.item {
+            QtItem::Operator(operator) => Some(operator.operator),
+            QtItem::Value(..) => None,
+        }
+    }
+
+    /// Makes substituted nodes searchable again (QTNClearFlags in tsquery_util.c)
+    fn clear_no_change(&mut self) {
+        self.no_change = false;
+        for child in &mut self.children {
+            child.clear_no_change();
+        }
+    }
+
+    /// Merges nested AND and OR operators into their parent (QTNTernary in tsquery_util.c)
+    pub(crate) fn ternary(&mut self) {
+        for child in &mut self.children {
+            child.ternary();
+        }
+
+        let operator = match self.operator() {
+            Some(operator @ (Operators::And | Operators::Or)) => operator,
+            _ => return,
+        };
+
+        let children = std::mem::take(&mut self.children);
+        for child in children {
+            if child.operator() == Some(operator) {
+                self.children.extend(child.children);
+            } else {
+                self.children.push(child);
+            }
+        }
+    }
+
+    /// Sorts the operands of everything but phrases (QTNSort in tsquery_util.c)
+    pub(crate) fn sort(&mut self) {
+        for child in &mut self.children {
+            child.sort();
+        }
+
+        if self.operator() != Some(Operators::Phrase) {
+            self.children.sort_by(QtNode::compare);
+        }
+    }
+
+    /// Orders nodes like QTNodeCompare in tsquery_util.c: operators before operands,
+    /// operands by checksum and then by text. Weights and prefix flags are not compared.
+    pub(crate) fn compare(&self, other: &QtNode) -> Ordering {
+        match (&self.item, &other.item) {
+            (QtItem::Operator(_), QtItem::Value(..)) => Ordering::Less,
+            (QtItem::Value(..), QtItem::Operator(_)) => Ordering::Greater,
+            (QtItem::Value(a, a_crc), QtItem::Value(b, b_crc)) => b_crc
+                .cmp(a_crc)
+                .then_with(|| a.text.as_bytes().cmp(b.text.as_bytes())),
+            (QtItem::Operator(a), QtItem::Operator(b)) => {
+                let ordering = (b.operator as i8)
+                    .cmp(&(a.operator as i8))
+                    .then_with(|| other.children.len().cmp(&self.children.len()));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+
+                for (a_child, b_child) in self.children.iter().zip(&other.children) {
+                    let ordering = a_child.compare(b_child);
+                    if ordering != Ordering::Equal {
+                        return ordering;
+                    }
+                }
+
+                if a.operator == Operators::Phrase {
+                    let distance = |op: &Operator| op.distance.unwrap_or(1);
+                    distance(b).cmp(&distance(a))
+                } else {
+                    Ordering::Equal
+                }
+            }
+        }
+    }
+
+    fn substitute(substitute: Option<&QtNode>) -> Option<QtNode> {
+        substitute.map(|substitute| QtNode {
+            no_change: true,
+            ..substitute.clone()
+        })
+    }
+
+    /// Replaces the node itself if it equals `target` (findeq in tsrewrite.c).
+    ///
+    /// An AND or OR with more operands than the target also matches when all operands of
+    /// the target are among its own, in which case only those are replaced.
+    fn find_eq(mut self, target: &QtNode, substitute: Option<&QtNode>) -> Option<QtNode> {
+        if (self.sign & target.sign) != target.sign
+            || self.no_change
+            || self.operator() != target.operator()
+        {
+            return Some(self);
+        }
+
+        if self.operator().is_none() || self.children.len() == target.children.len() {
+            // QTNEq also wants the signs to be the same, which a stale sign prevents
+            if self.sign == target.sign && self.compare(target) == Ordering::Equal {
+                return QtNode::substitute(substitute);
+            }
+            return Some(self);
+        }
+
+        if self.children.len() < target.children.len() || target.children.is_empty() {
+            return Some(self);
+        }
+
+        // Both operand lists are sorted, so a single pass finds the matches
+        let mut matched = vec![false; self.children.len()];
+        let mut count = 0;
+        let (mut i, mut j) = (0, 0);
+        while i < self.children.len() && j < target.children.len() {
+            match self.children[i].compare(&target.children[j]) {
+                Ordering::Equal => {
+                    matched[i] = true;
+                    count += 1;
+                    i += 1;
+                    j += 1;
+                }
+                Ordering::Less => i += 1,
+                Ordering::Greater => break,
+            }
+        }
+
+        if count == target.children.len() {
+            let children = std::mem::take(&mut self.children);
+            self.children = children
+                .into_iter()
+                .zip(matched)
+                .filter(|(_, matched)| !matched)
+                .map(|(child, _)| child)
+                .collect();
+            self.children.extend(QtNode::substitute(substitute));
+            // keeps the result in the same order as the server, not needed for searching
+            self.sort();
+        }
+
+        Some(self)
+    }
+
+    /// Replaces `target` throughout the tree, dropping operators that are left without
+    /// operands (findsubquery in tsrewrite.c). `None` if nothing is left at all.
+    pub(crate) fn find_subquery(
+        self,
+        target: &QtNode,
+        substitute: Option<&QtNode>,
+    ) -> Option<QtNode> {
+        let mut node = self.find_eq(target, substitute)?;
+
+        if node.no_change || node.operator().is_none() {
+            return Some(node);
+        }
+
+        // a plain loop keeps the stack frames of deep queries small
+        for child in std::mem::take(&mut node.children) {
+            node.children
+                .extend(child.find_subquery(target, substitute));
+        }
+
+        match node.children.len() {
+            0 => None,
+            1 if node.operator() != Some(Operators::Not) => node.children.pop(),
+            _ => Some(node),
+        }
+    }
+}
+
+/// Checksum the server keeps for every operand and uses to sort them.
+///
+/// This is the historical variant of CRC-32 the server still uses for tsquery
+/// (COMP_LEGACY_CRC32 in pg_crc.h), which shifts the wrong way for its lookup table.
+pub(crate) fn valcrc(text: &str) -> i32 {
+    let mut crc = u32::MAX;
+    for &byte in text.as_bytes() {
+        let index = ((crc >> 24) ^ u32::from(byte)) & 0xff;
+        crc = CRC32_TABLE[index as usize] ^ (crc << 8);
+    }
+    (crc ^ u32::MAX) as i32
+}
+
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pg_tsquery::PgTsQuery;
+    use std::str::FromStr;
+
+    fn query(s: &str) -> PgTsQuery {
+        PgTsQuery::from_str(s).unwrap()
+    }
+
+    // Expected values are ts_rewrite output captured from PostgreSQL 15
+    #[test]
+    fn rewrite_like_the_server() {
+        let cases = [
+            ("a & b", "a", "c | d", "'b' & ( 'c' | 'd' )"),
+            ("a & b", "x", "c", "'b' & 'a'"),
+            ("a & b", "", "c", "'a' & 'b'"),
+            ("a & (b & c)", "x", "c", "'b' & 'c' & 'a'"),
+            ("a & (b & c)", "", "c", "'a' & 'b' & 'c'"),
+            ("a & (b & c)", "c & a", "", "'b'"),
+        ];
+
+        for (input, target, substitute, expected) in cases {
+            let rewritten = query(input).rewrite(&query(target), &query(substitute));
+            assert_eq!(
+                rewritten.to_string(),
+                expected,
+                "{input}, {target}, {substitute}"
+            );
+        }
+    }
+
+    // ts_rewrite(query, 'SELECT target, substitute ...') with the rules in this order,
+    // captured from PostgreSQL 15
+    #[test]
+    fn rewrite_all_like_the_server() {
+        let cases = [
+            ("a & d", [("a", "b"), ("b", "c")], "'c' & 'd'"),
+            ("a & d", [("b", "c"), ("a", "b")], "'b' & 'd'"),
+            ("a & d", [("a", "a"), ("a", "b")], "'b' & 'd'"),
+            ("a | b", [("a", "c & d"), ("c", "e")], "'b' | 'e' & 'd'"),
+            // the server doesn't update the operand signature of a rewritten node,
+            // so these second rules can't match any more
+            ("a & b & c", [("a & b", "x"), ("x & c", "y")], "'c' & 'x'"),
+            ("a <-> b", [("a", "b"), ("b <-> b", "c")], "'b' <-> 'b'"),
+            ("a & b", [("a", ""), ("b", "")], ""),
+        ];
+
+        for (input, rules, expected) in cases {
+            let rules: Vec<_> = rules
+                .iter()
+                .map(|(target, substitute)| (query(target), query(substitute)))
+                .collect();
+            let rewritten = query(input).rewrite_all(rules.iter().map(|(t, s)| (t, s)));
+            assert_eq!(rewritten.to_string(), expected, "{input}, {rules:?}");
+        }
+    }
+
+    #[test]
+    fn rewrite_all_sorts_without_rules() {
+        let (target, substitute) = (query(""), query("c"));
+        let rewritten = query("a & b").rewrite_all([(&target, &substitute)]);
+        assert_eq!(rewritten.to_string(), "'b' & 'a'");
+    }
+}
@@ -0,0 +1,401 @@
+use crate::pg_tsquery::{
+    Entry, MAX_QUERY_DEPTH, Operator, Operators, PgTsQuery, Value, tree_depth,
+};
+use std::error::Error;
+
+/// Expression tree for a tsquery.
+///
+/// `PgTsQuery.entries` stores the query as a flat list in the server's layout
+/// (operator, right operand, left operand). This is the same query as a real tree.
+/// The methods walking the tree recurse, [`PgTsQuery::to_tree`] never builds trees deeper
+/// than [`crate::pg_tsquery::MAX_QUERY_DEPTH`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum TsQueryNode {
+    Term(Value),
+    Not(Box<TsQueryNode>),
+    And(Box<TsQueryNode>, Box<TsQueryNode>),
+    Or(Box<TsQueryNode>, Box<TsQueryNode>),
+    Phrase {
+        left: Box<TsQueryNode>,
+        right: Box<TsQueryNode>,
+        distance: i16,
+    },
+}
+
+impl TsQueryNode {
+    pub fn negate(node: TsQueryNode) -> Self {
+        TsQueryNode::Not(Box::new(node))
+    }
+
+    pub fn and(left: TsQueryNode, right: TsQueryNode) -> Self {
+        TsQueryNode::And(Box::new(left), Box::new(right))
+    }
+
+    pub fn or(left: TsQueryNode, right: TsQueryNode) -> Self {
+        TsQueryNode::Or(Box::new(left), Box::new(right))
+    }
+
+    pub fn phrase(left: TsQueryNode, right: TsQueryNode, distance: i16) -> Self {
+        TsQueryNode::Phrase {
+            left: Box::new(left),
+            right: Box::new(right),
+            distance,
+        }
+    }
+
+    /// Operator at the top of this node, `None` for a term
+    pub fn operator(&self) -> Option<Operator> {
+        let (operator, distance) = match self {
+            TsQueryNode::Term(_) => return None,
+            TsQueryNode::Not(_) => (Operators::Not, None),
+            TsQueryNode::And(..) => (Operators::And, None),
+            TsQueryNode::Or(..) => (Operators::Or, None),
+            TsQueryNode::Phrase { distance, .. } => (Operators::Phrase, Some(*distance)),
+        };
+
+        Some(Operator { operator, distance })
+    }
+
+    /// Direct children, left operand first
+    pub fn children(&self) -> Vec<&TsQueryNode> {
+        match self {
+            TsQueryNode::Term(_) => vec![],
+            TsQueryNode::Not(operand) => vec![operand],
+            TsQueryNode::And(left, right)
+            | TsQueryNode::Or(left, right)
+            | TsQueryNode::Phrase { left, right, .. } => vec![left, right],
+        }
+    }
+
+    /// Calls `visitor` for every node, parents before their children
+    pub fn visit<F: FnMut(&TsQueryNode)>(&self, visitor: &mut F) {
+        visitor(self);
+        for child in self.children() {
+            child.visit(visitor);
+        }
+    }
+
+    /// Bottom-up fold: `f` receives each node together with the already folded
+    /// results of its children (left operand first)
+    pub fn fold<T, F: FnMut(&TsQueryNode, Vec<T>) -> T>(&self, f: &mut F) -> T {
+        let children = self
+            .children()
+            .into_iter()
+            .map(|child| child.fold(f))
+            .collect();
+
+        f(self, children)
+    }
+
+    /// Rebuilds the tree bottom-up, replacing every node with the result of `f`.
+    /// Children are transformed before `f` sees their parent.
+    pub fn transform<F: FnMut(TsQueryNode) -> TsQueryNode>(self, f: &mut F) -> TsQueryNode {
+        let node = match self {
+            TsQueryNode::Term(_) => self,
+            TsQueryNode::Not(operand) => TsQueryNode::negate(operand.transform(f)),
+            TsQueryNode::And(left, right) => {
+                TsQueryNode::and(left.transform(f), right.transform(f))
+            }
+            TsQueryNode::Or(left, right) => TsQueryNode::or(left.transform(f), right.transform(f)),
+            TsQueryNode::Phrase {
+                left,
+                right,
+                distance,
+            } => TsQueryNode::phrase(left.transform(f), right.transform(f), distance),
+        };
+
+        f(node)
+    }
+
+    /// All operands in left to right order
+    pub fn terms(&self) -> Vec<&Value> {
+        let mut terms = Vec::new();
+        self.collect_terms(&mut terms);
+        terms
+    }
+
+    fn collect_terms<'a>(&'a self, terms: &mut Vec<&'a Value>) {
+        match self {
+            TsQueryNode::Term(value) => terms.push(value),
+            _ => {
+                for child in self.children() {
+                    child.collect_terms(terms);
+                }
+            }
+        }
+    }
+
+    /// Flattens the tree into the server's entry layout
+    pub fn to_entries(&self) -> Vec<Entry> {
+        let mut entries = Vec::new();
+        let mut pending = vec![self];
+
+        // Pre-order with the right operand emitted before the left one
+        while let Some(node) = pending.pop() {
+            match node {
+                TsQueryNode::Term(value) => entries.push(Entry::Value(value.clone())),
+                _ => {
+                    if let Some(operator) = node.operator() {
+                        entries.push(Entry::Operator(operator));
+                    }
+                    pending.extend(node.children());
+                }
+            }
+        }
+
+        entries
+    }
+}
+
+impl From<TsQueryNode> for PgTsQuery {
+    fn from(node: TsQueryNode) -> Self {
+        PgTsQuery {
+            entries: node.to_entries(),
+        }
+    }
+}
+
+impl From<Option<TsQueryNode>> for PgTsQuery {
+    fn from(node: Option<TsQueryNode>) -> Self {
+        match node {
+            Some(node) => node.into(),
+            None => PgTsQuery { entries: vec![] },
+        }
+    }
+}
+
+impl TryFrom<&PgTsQuery> for TsQueryNode {
+    type Error = Box<dyn Error>;
+
+    fn try_from(query: &PgTsQuery) -> Result<Self, Self::Error> {
+        query
+            .to_tree()?
+            .ok_or_else(|| "Invalid tsquery: query is empty".into())
+    }
+}
+
+impl PgTsQuery {
+    /// Builds the expression tree, `None` for an empty query.
+    ///
+    /// Fails when the entry list is not a well formed query, i.e. an operator is
+    /// missing operands, the entries don't form exactly one root, a phrase operator has
+    /// no distance or another operator has one, and when the tree is deeper
+    /// than [`MAX_QUERY_DEPTH`]. So converting back with [`TsQueryNode::to_entries`]
+    /// gives the same entries.
+    pub fn to_tree(&self) -> Result<Option<TsQueryNode>, Box<dyn Error>> {
+        if tree_depth(self.entries.iter().rev().map(Entry::arity)) > MAX_QUERY_DEPTH {
+            return Err("Invalid tsquery: stack depth limit exceeded".into());
+        }
+
+        let mut stack: Vec<TsQueryNode> = Vec::new();
+
+        // Walking the entries backwards gives the usual reverse polish order
+        for (index, entry) in self.entries.iter().enumerate().rev() {
+            let node = match entry {
+                Entry::Value(value) => TsQueryNode::Term(value.clone()),
+                Entry::Operator(op) => {
+                    let missing = || -> Box<dyn Error> {
+                        format!(
+                            "Invalid tsquery: operator {:?} at entry {index} is missing operands",
+                            op.operator
+                        )
+                        .into()
+                    };
+
+                    let mut operand = || stack.pop().ok_or_else(missing);
+
+                    // Only phrase operators have a distance, anything else would be
+                    // lost in the tree
+                    match (op.operator, op.distance) {
+                        (Operators::Not, None) => TsQueryNode::negate(operand()?),
+                        (Operators::And, None) => {
+                            let right = operand()?;
+                            TsQueryNode::and(operand()?, right)
+                        }
+                        (Operators::Or, None) => {
+                            let right = operand()?;
+                            TsQueryNode::or(operand()?, right)
+                        }
+                        (Operators::Phrase, Some(distance)) => {
+                            let right = operand()?;
+                            TsQueryNode::phrase(operand()?, right, distance)
+                        }
+                        (operator, distance) => {
+                            return Err(format!(
+                                "Invalid tsquery: operator {operator:?} at entry {index} has distance {distance:?}"
+                            )
+                            .into());
+                        }
+                    }
+                }
+            };
+
+            stack.push(node);
+        }
+
+        if stack.len() > 1 {
+            return Err(format!(
+                "Invalid tsquery: expected a single root, found {}",
+                stack.len()
+            )
+            .into());
+        }
+
+        Ok(stack.pop())
+    }
+
+    /// Checks that the entries form a well formed query
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        self.to_tree().map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pg_tsquery::{Entry, Operator, Operators, PgTsQuery, Value};
+    use crate::pg_tsquery_tree::TsQueryNode;
+    use std::str::FromStr;
+
+    fn term(text: &str) -> TsQueryNode {
+        TsQueryNode::Term(Value::new(text, 0, false))
+    }
+
+    fn operator(operator: Operators, distance: Option<i16>) -> Entry {
+        Entry::Operator(Operator { operator, distance })
+    }
+
+    fn value(text: &str) -> Entry {
+        Entry::Value(Value::new(text, 0, false))
+    }
+
+    #[test]
+    fn converts_between_tree_and_entries() {
+        let tree = TsQueryNode::or(
+            TsQueryNode::and(term("a"), TsQueryNode::negate(term("b"))),
+            TsQueryNode::phrase(term("c"), term("d"), 3),
+        );
+        let query = PgTsQuery::from_str("a & !b | c <3> d").unwrap();
+        assert_eq!(query.to_tree().unwrap(), Some(tree.clone()));
+        assert_eq!(PgTsQuery::from(tree.clone()), query);
+        assert_eq!(TsQueryNode::try_from(&query).unwrap(), tree);
+
+        let terms: Vec<_> = tree.terms().iter().map(|v| v.text.as_str()).collect();
+        assert_eq!(terms, ["a", "b", "c", "d"]);
+
+        let empty = PgTsQuery::from_str("").unwrap();
+        assert_eq!(empty.to_tree().unwrap(), None);
+        assert_eq!(PgTsQuery::from(None), empty);
+        assert!(TsQueryNode::try_from(&empty).is_err());
+
+        for input in [
+            "a <-> b",
+            "a <0> b",
+            "!(a <2> (b | c))",
+            "a:*B & b:AC <-> !c",
+        ] {
+            let query = PgTsQuery::from_str(input).unwrap();
+            let tree = query.to_tree().unwrap().unwrap();
+            assert_eq!(tree.to_entries(), query.entries, "{input}");
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_entries() {
+        let cases = [
+            (
+                vec![operator(Operators::And, None), value("a")],
+                "Invalid tsquery: operator And at entry 0 is missing operands",
+            ),
+            (
+                vec![operator(Operators::Not, None)],
+                "Invalid tsquery: operator Not at entry 0 is missing operands",
+            ),
+            (
+                vec![value("a"), value("b")],
+                "Invalid tsquery: expected a single root, found 2",
+            ),
+            (
+                vec![operator(Operators::Phrase, None), value("a"), value("b")],
+                "Invalid tsquery: operator Phrase at entry 0 has distance None",
+            ),
+            (
+                vec![operator(Operators::And, Some(2)), value("a"), value("b")],
+                "Invalid tsquery: operator And at entry 0 has distance Some(2)",
+            ),
+            (
+                vec![
+                    operator(Operators::Or, None),
+                    operator(Operators::Not, Some(1)),
+                    value("a"),
+                    value("b"),
+                ],
+                "Invalid tsquery: operator Not at entry 1 has distance Some(1)",
+            ),
+        ];
+
+        for (entries, expected) in cases {
+            let query = PgTsQuery { entries };
+            assert_eq!(query.validate().unwrap_err().to_string(), expected);
+            assert_eq!(query.to_tree().unwrap_err().to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn folds_and_transforms() {
+        let tree = PgTsQuery::from_str("a & !b | c <-> d")
+            .unwrap()
+            .to_tree()
+            .unwrap()
+            .unwrap();
+
+        let count = tree.fold(&mut |_, children: Vec<usize>| 1 + children.iter().sum::<usize>());
+        assert_eq!(count, 8);
+
+        let printed = tree.fold(&mut |node, children: Vec<String>| match node {
+            TsQueryNode::Term(value) => value.text.clone(),
+            _ => format!(
+                "{:?}({})",
+                node.operator().unwrap().operator,
+                children.join(", ")
+            ),
+        });
+        assert_eq!(printed, "Or(And(a, Not(b)), Phrase(c, d))");
+
+        let mut visited = Vec::new();
+        tree.visit(&mut |node| visited.push(node.operator().map(|op| op.operator)));
+        assert_eq!(
+            visited,
+            [
+                Some(Operators::Or),
+                Some(Operators::And),
+                None,
+                Some(Operators::Not),
+                None,
+                Some(Operators::Phrase),
+                None,
+                None
+            ]
+        );
+
+        // children are transformed first, so the new terms are seen by their parents
+        let mut seen = Vec::new();
+        let transformed = tree.transform(&mut |node| match node {
+            TsQueryNode::Term(value) => term(&value.text.to_uppercase()),
+            TsQueryNode::Not(operand) => *operand,
+            _ => {
+                seen.push(
+                    node.terms()
+                        .iter()
+                        .map(|v| v.text.clone())
+                        .collect::<String>(),
+                );
+                node
+            }
+        });
+        assert_eq!(
+            PgTsQuery::from(transformed).to_string(),
+            "'A' & 'B' | 'C' <-> 'D'"
+        );
+        assert_eq!(seen, ["AB", "CD", "ABCD"]);
+    }
+}
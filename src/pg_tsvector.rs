@@ -6,10 +6,115 @@ use std::fmt::{Display, Write};
 use std::io::{BufRead, Cursor};
 use std::{error::Error, fmt::Formatter};
 
+// Largest position a tsvector can store (MAXENTRYPOS - 1 in ts_type.h)
+pub const MAX_POSITION: u16 = (1 << 14) - 1;
+// Most positions the server keeps for one lexeme (MAXNUMPOS in ts_type.h)
+pub const MAX_POSITIONS: usize = 256;
+// Longest lexeme and longest total of all lexemes in bytes (MAXSTRLEN and MAXSTRPOS)
+pub const MAX_LEXEME_LEN: usize = (1 << 11) - 1;
+pub const MAX_TOTAL_LEN: usize = (1 << 20) - 1;
+
+/// How [`PgTsVector::encode`] deals with vectors the server wouldn't store as they are
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EncodeMode {
+    /// Sort and merge lexemes and positions and cut off positions like `tsvector_in` does
+    #[default]
+    Canonicalize,
+    /// Return an error for anything that isn't already in canonical form
+    Strict,
+}
+
+/// Weight of a lexeme occurrence, D being the default
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Weight {
+    #[default]
+    D = 0,
+    C = 1,
+    B = 2,
+    A = 3,
+}
+
+impl Weight {
+    pub fn letter(self) -> char {
+        match self {
+            Weight::A => 'A',
+            Weight::B => 'B',
+            Weight::C => 'C',
+            Weight::D => 'D',
+        }
+    }
+}
+
+impl TryFrom<char> for Weight {
+    type Error = Box<dyn Error>;
+
+    fn try_from(value: char) -> Result<Self, Self::Error> {
+        match value.to_ascii_uppercase() {
+            'A' => Ok(Weight::A),
+            'B' => Ok(Weight::B),
+            'C' => Ok(Weight::C),
+            'D' => Ok(Weight::D),
+            _ => Err(format!("Invalid weight: {value}").into()),
+        }
+    }
+}
+
+/// Where a lexeme occurs in the document.
+///
+/// On the wire both are packed into 16 bits, the weight in the top two bits and
+/// the position in the remaining 14 (WordEntryPos in ts_type.h).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    /// 1 to 16383
+    pub pos: u16,
+    pub weight: Weight,
+}
+
+impl Position {
+    pub fn new(pos: u16, weight: Weight) -> Result<Self, Box<dyn Error>> {
+        if !(1..=MAX_POSITION).contains(&pos) {
+            return Err(format!("Invalid tsvector: position {pos} out of range").into());
+        }
+
+        Ok(Position { pos, weight })
+    }
+
+    /// Decodes the packed wire format
+    pub fn from_raw(raw: u16) -> Self {
+        let weight = match raw >> 14 {
+            3 => Weight::A,
+            2 => Weight::B,
+            1 => Weight::C,
+            _ => Weight::D,
+        };
+
+        Position {
+            pos: raw & MAX_POSITION,
+            weight,
+        }
+    }
+
+    /// Encodes into the packed wire format, positions above 16383 are cut off
+    pub fn to_raw(self) -> u16 {
+        ((self.weight as u16) << 14) | self.pos.min(MAX_POSITION)
+    }
+}
+
+/// Prints the position like `tsvectorout`, e.g. `3A`. Weight D is not printed.
+impl Display for Position {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.pos)?;
+        if self.weight != Weight::D {
+            f.write_char(self.weight.letter())?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Lexeme {
     pub word: String,
-    pub positions: Vec<i32>,
+    pub positions: Vec<Position>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -17,6 +122,59 @@ pub struct PgTsVector {
     pub words: Vec<Lexeme>,
 }
 
+impl PgTsVector {
+    /// Brings the vector into the server's canonical form: lexemes sorted bytewise without
+    /// duplicates, and positions sorted and unique (uniqueentry in tsvector.c).
+    ///
+    /// Positions of duplicate lexemes are merged, for a position given more than once
+    /// the highest weight wins. Like the server positions above 16383 become 16383 and
+    /// only the first 256 positions are kept.
+    pub fn canonicalize(&mut self) {
+        self.words
+            .sort_by(|a, b| a.word.as_bytes().cmp(b.word.as_bytes()));
+
+        let mut words: Vec<Lexeme> = Vec::with_capacity(self.words.len());
+        for lexeme in self.words.drain(..) {
+            match words.last_mut() {
+                Some(last) if last.word == lexeme.word => last.positions.extend(lexeme.positions),
+                _ => words.push(lexeme),
+            }
+        }
+
+        for lexeme in &mut words {
+            lexeme.positions = unique_positions(std::mem::take(&mut lexeme.positions));
+        }
+
+        self.words = words;
+    }
+}
+
+// Sorts and deduplicates positions (uniquePos in tsvector.c)
+fn unique_positions(mut positions: Vec<Position>) -> Vec<Position> {
+    for position in &mut positions {
+        position.pos = position.pos.min(MAX_POSITION);
+    }
+    positions.sort_by_key(|position| position.pos);
+
+    let mut unique: Vec<Position> = Vec::with_capacity(positions.len());
+    for position in positions {
+        match unique.last_mut() {
+            Some(last) if last.pos == position.pos => {
+                last.weight = last.weight.max(position.weight)
+            }
+            Some(_) => {
+                unique.push(position);
+                if unique.len() == MAX_POSITIONS || position.pos == MAX_POSITION {
+                    break;
+                }
+            }
+            None => unique.push(position),
+        }
+    }
+
+    unique
+}
+
 impl<'a> FromSql<'a> for PgTsVector {
     fn from_sql(_: &Type, raw: &'a [u8]) -> Result<Self, Box<dyn Error + Sync + Send>> {
         let mut reader = Cursor::new(raw);
@@ -30,12 +188,12 @@ impl<'a> FromSql<'a> for PgTsVector {
             reader.read_until(b'\0', &mut lexeme)?;
 
             let num_positions = reader.read_u16::<BigEndian>()?;
-            let mut positions = Vec::<i32>::with_capacity(num_positions as usize);
+            let mut positions = Vec::<Position>::with_capacity(num_positions as usize);
 
             if num_positions > 0 {
                 for _ in 0..num_positions {
                     let position = reader.read_u16::<BigEndian>()?;
-                    positions.push(position as i32);
+                    positions.push(Position::from_raw(position));
                 }
             }
 
@@ -56,16 +214,23 @@ impl Display for PgTsVector {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let mut words = self.words.iter().peekable();
 
+        // Same as tsvectorout: quotes and backslashes are doubled and
+        // lexemes without positions are printed on their own
         while let Some(word) = words.next() {
-            f.write_str(&format!(
-                "'{}':{}",
-                word.word,
-                word.positions
-                    .iter()
-                    .map(|pos| pos.to_string())
-                    .collect::<Vec<_>>()
-                    .join(",")
-            ))?;
+            f.write_char('\'')?;
+            for c in word.word.chars() {
+                if c == '\'' || c == '\\' {
+                    f.write_char(c)?;
+                }
+                f.write_char(c)?;
+            }
+            f.write_char('\'')?;
+
+            for (i, position) in word.positions.iter().enumerate() {
+                f.write_char(if i == 0 { ':' } else { ',' })?;
+                write!(f, "{position}")?;
+            }
+
             if words.peek().is_some() {
                 f.write_char(' ')?;
             }
@@ -75,8 +240,142 @@ impl Display for PgTsVector {
     }
 }
 
-impl ToSql for PgTsVector {
-    fn to_sql(&self, _: &Type, out: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+impl PgTsVector {
+    /// Checks the vector against the limits of the server and that it is in canonical
+    /// form: lexemes sorted bytewise and unique, positions sorted and unique.
+    pub fn validate(&self) -> Result<(), Box<dyn Error + Sync + Send>> {
+        self.check_limits()?;
+
+        for (i, pair) in self.words.windows(2).enumerate() {
+            if pair[0].word.as_bytes() >= pair[1].word.as_bytes() {
+                return Err(format!(
+                    "Invalid tsvector: lexemes are not sorted or not unique at index {}: '{}' after '{}'",
+                    i + 1,
+                    pair[1].word,
+                    pair[0].word
+                )
+                .into());
+            }
+        }
+
+        for lexeme in &self.words {
+            if lexeme.positions.len() > MAX_POSITIONS {
+                return Err(format!(
+                    "Invalid tsvector: lexeme '{}' has {} positions, max {MAX_POSITIONS}",
+                    lexeme.word,
+                    lexeme.positions.len()
+                )
+                .into());
+            }
+
+            if let Some(position) = lexeme.positions.iter().find(|p| p.pos > MAX_POSITION) {
+                return Err(format!(
+                    "Invalid tsvector: position {} of lexeme '{}' is larger than {MAX_POSITION}",
+                    position.pos, lexeme.word
+                )
+                .into());
+            }
+
+            if lexeme
+                .positions
+                .windows(2)
+                .any(|pair| pair[0].pos >= pair[1].pos)
+            {
+                return Err(format!(
+                    "Invalid tsvector: positions of lexeme '{}' are not sorted or not unique",
+                    lexeme.word
+                )
+                .into());
+            }
+        }
+
+        self.check_data_len()
+    }
+
+    // Limits that can't be fixed by canonicalizing
+    fn check_limits(&self) -> Result<(), Box<dyn Error + Sync + Send>> {
+        for lexeme in &self.words {
+            if lexeme.word.len() > MAX_LEXEME_LEN {
+                return Err(format!(
+                    "Invalid tsvector: lexeme is too long ({} bytes, max {MAX_LEXEME_LEN} bytes)",
+                    lexeme.word.len()
+                )
+                .into());
+            }
+            if lexeme.word.contains('\0') {
+                return Err("Invalid tsvector: lexeme contains a null character".into());
+            }
+            if lexeme.positions.iter().any(|position| position.pos == 0) {
+                return Err(format!(
+                    "Invalid tsvector: lexeme '{}' has position 0, positions start at 1",
+                    lexeme.word
+                )
+                .into());
+            }
+        }
+
+        Ok(())
+    }
+
+    // Expects a vector in canonical form
+    fn check_data_len(&self) -> Result<(), Box<dyn Error + Sync + Send>> {
+        let len = self.data_len();
+        if len > MAX_TOTAL_LEN {
+            return Err(format!(
+                "Invalid tsvector: string is too long for tsvector ({len} bytes, max {MAX_TOTAL_LEN} bytes)"
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+
+    /// Bytes the lexemes and positions take up on the server, which may not exceed
+    /// [`MAX_TOTAL_LEN`]: each lexeme, and for lexemes with positions a count and the
+    /// positions, aligned to two bytes (uniqueentry in tsvector.c)
+    pub(crate) fn data_len(&self) -> usize {
+        self.words.iter().fold(0, |len, lexeme| {
+            let len = len + lexeme.word.len();
+            if lexeme.positions.is_empty() {
+                len
+            } else {
+                len.next_multiple_of(2) + (lexeme.positions.len() + 1) * 2
+            }
+        })
+    }
+
+    /// Writes the binary format, either bringing the vector into canonical form first
+    /// or failing if it isn't. Lexemes that are too long, position 0 and vectors over
+    /// the total size limit are an error in both modes.
+    pub fn encode(
+        &self,
+        mode: EncodeMode,
+        out: &mut BytesMut,
+    ) -> Result<(), Box<dyn Error + Sync + Send>> {
+        match mode {
+            EncodeMode::Strict => {
+                self.validate()?;
+                self.write(out);
+            }
+            EncodeMode::Canonicalize => {
+                self.check_limits()?;
+                let mut vector = self.clone();
+                vector.canonicalize();
+                vector.check_data_len()?;
+                vector.write(out);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The vector as a query parameter that is encoded with the given mode
+    pub fn encoded(&self, mode: EncodeMode) -> EncodedTsVector<'_> {
+        EncodedTsVector { vector: self, mode }
+    }
+
+    // Expects a valid vector
+    fn write(&self, out: &mut BytesMut) {
         // Write number of lexemes
         out.put_u32(self.words.len() as u32);
 
@@ -89,10 +388,39 @@ impl ToSql for PgTsVector {
             out.put_u16(lexeme.positions.len() as u16);
 
             // Write positions
-            for &position in &lexeme.positions {
-                out.put_u16(position as u16);
+            for position in &lexeme.positions {
+                out.put_u16(position.to_raw());
             }
         }
+    }
+}
+
+/// Encodes the vector with [`EncodeMode::Canonicalize`], use [`PgTsVector::encoded`]
+/// to pick the mode for a single call
+impl ToSql for PgTsVector {
+    fn to_sql(&self, _: &Type, out: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        self.encode(EncodeMode::default(), out)?;
+
+        Ok(IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        ty.name() == "tsvector"
+    }
+
+    to_sql_checked!();
+}
+
+/// A [`PgTsVector`] query parameter with its [`EncodeMode`]
+#[derive(Debug, Clone, Copy)]
+pub struct EncodedTsVector<'a> {
+    pub vector: &'a PgTsVector,
+    pub mode: EncodeMode,
+}
+
+impl ToSql for EncodedTsVector<'_> {
+    fn to_sql(&self, _: &Type, out: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        self.vector.encode(self.mode, out)?;
 
         Ok(IsNull::No)
     }
@@ -103,3 +431,46 @@ impl ToSql for PgTsVector {
 
     to_sql_checked!();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{EncodeMode, Lexeme, PgTsVector, Position, Weight};
+    use bytes::BytesMut;
+
+    // Distinct four letter lexemes, each at position 1 if `with_positions`
+    fn vector(count: usize, with_positions: bool) -> PgTsVector {
+        let words = (0..count)
+            .map(|i| Lexeme {
+                word: [17576, 676, 26, 1]
+                    .iter()
+                    .map(|div| char::from(b'a' + (i / div % 26) as u8))
+                    .collect(),
+                positions: if with_positions {
+                    vec![Position {
+                        pos: 1,
+                        weight: Weight::D,
+                    }]
+                } else {
+                    vec![]
+                },
+            })
+            .collect();
+
+        PgTsVector { words }
+    }
+
+    // The server accepts 131071 such lexemes with positions and rejects 131072:
+    // "string is too long for tsvector (1048576 bytes, max 1048575 bytes)"
+    #[test]
+    fn positions_count_towards_the_size_limit() {
+        for mode in [EncodeMode::Strict, EncodeMode::Canonicalize] {
+            let mut out = BytesMut::new();
+            assert!(vector(131_071, true).encode(mode, &mut out).is_ok());
+            assert!(vector(131_072, true).encode(mode, &mut out).is_err());
+            assert!(vector(150_000, false).encode(mode, &mut out).is_ok());
+        }
+
+        assert_eq!(vector(131_072, true).data_len(), 1_048_576);
+        assert_eq!(vector(150_000, true).data_len(), 1_200_000);
+    }
+}
@@ -0,0 +1,413 @@
+use crate::pg_tsquery_construct::{QueryLexeme, QueryTokenizer, QueryWord};
+use crate::pg_tsvector::{
+    Lexeme, MAX_LEXEME_LEN, MAX_POSITION, MAX_POSITIONS, PgTsVector, Position, Weight,
+};
+use crate::pg_tsvector_tokenizer::{DefaultParser, TextParser, Token, TokenType};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+
+/// One lexeme returned by a dictionary (TSLexeme in ts_public.h)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DictLexeme {
+    pub lexeme: String,
+    /// Lexemes with the same variant number are alternatives that belong together,
+    /// e.g. the parts of a split compound word
+    pub variant: u16,
+    /// Takes the next position instead of the one of the token, used by dictionaries
+    /// that replace a phrase by several words (TSL_ADDPOS)
+    pub add_pos: bool,
+    /// Makes `to_tsquery` match every lexeme starting with this one, set by a synonym
+    /// ending in `*` (TSL_PREFIX)
+    pub prefix: bool,
+}
+
+impl DictLexeme {
+    pub fn new(lexeme: impl Into<String>) -> Self {
+        DictLexeme {
+            lexeme: lexeme.into(),
+            variant: 0,
+            add_pos: false,
+            prefix: false,
+        }
+    }
+}
+
+/// Normalizes a token, the counterpart of a text search dictionary's lexize function.
+///
+/// `None` means the dictionary doesn't know the token and the next dictionary of the
+/// mapping gets to try. An empty result marks a stop word: it produces no lexeme but
+/// still takes up a position.
+pub trait Dictionary: Send + Sync {
+    fn lexize(&self, token: &str) -> Option<Vec<DictLexeme>>;
+
+    /// Like `lexize` but may consume several of the upcoming tokens at once, like the
+    /// thesaurus does for phrases. Returns the number of tokens consumed, at least one.
+    fn lexize_phrase(&self, tokens: &[&str]) -> Option<(usize, Vec<DictLexeme>)> {
+        self.lexize(tokens.first()?).map(|lexemes| (1, lexemes))
+    }
+}
+
+/// Lowercases one character at a time like the server does, which leaves a final sigma
+/// as `σ` where [`str::to_lowercase`] would make it `ς`
+pub(crate) fn lowercase(text: &str) -> String {
+    text.chars().flat_map(char::to_lowercase).collect()
+}
+
+/// A list of stop words, compared after lowercasing
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stopwords(HashSet<String>);
+
+impl Stopwords {
+    pub fn new<I, S>(words: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Stopwords(
+            words
+                .into_iter()
+                .map(|word| lowercase(word.as_ref()))
+                .collect(),
+        )
+    }
+
+    pub fn contains(&self, word: &str) -> bool {
+        self.0.contains(word)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// The `simple` dictionary template (dict_simple.c): lowercases the token and
+/// drops it if it is a stop word.
+///
+/// With `accept` turned off words that aren't stop words are passed on to the next
+/// dictionary instead, which is what the `Accept` option does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimpleDictionary {
+    pub stopwords: Stopwords,
+    pub accept: bool,
+}
+
+impl Default for SimpleDictionary {
+    fn default() -> Self {
+        SimpleDictionary {
+            stopwords: Stopwords::default(),
+            accept: true,
+        }
+    }
+}
+
+impl SimpleDictionary {
+    pub fn with_stopwords(stopwords: Stopwords) -> Self {
+        SimpleDictionary {
+            stopwords,
+            accept: true,
+        }
+    }
+}
+
+impl Dictionary for SimpleDictionary {
+    fn lexize(&self, token: &str) -> Option<Vec<DictLexeme>> {
+        let word = lowercase(token);
+
+        if word.is_empty() || self.stopwords.contains(&word) {
+            Some(vec![])
+        } else if self.accept {
+            Some(vec![DictLexeme::new(word)])
+        } else {
+            None
+        }
+    }
+}
+
+/// Only recognizes stop words and passes everything else on, to put in front of
+/// dictionaries that have no stop word list of their own
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StopwordFilter(pub Stopwords);
+
+impl Dictionary for StopwordFilter {
+    fn lexize(&self, token: &str) -> Option<Vec<DictLexeme>> {
+        self.0.contains(&lowercase(token)).then(Vec::new)
+    }
+}
+
+/// A lexeme of the parsed document with its position (ParsedWord in ts_utils.h)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedWord {
+    pub lexeme: String,
+    pub variant: u16,
+    /// 1 to 16383
+    pub pos: u16,
+    /// Only used by `to_tsquery`, see [`DictLexeme::prefix`]
+    pub prefix: bool,
+}
+
+// Tokens a dictionary turned into lexemes together, more than one for a phrase
+pub(crate) struct TokenGroup {
+    // indexes into the tokens of the text
+    pub(crate) tokens: Vec<usize>,
+    pub(crate) words: Vec<ParsedWord>,
+    // the last position taken up, stop words take one without any words
+    pub(crate) end: u16,
+}
+
+/// A text search configuration: a parser and, for every token type, the dictionaries
+/// that are tried in order.
+///
+/// Token types without a mapping are skipped, as are tokens no dictionary recognizes.
+///
+/// ```
+/// use postgres_types_extra::pg_tsvector_config::TextSearchConfig;
+///
+/// let config = TextSearchConfig::simple();
+/// let vector = config.to_tsvector("The Fat Rats");
+/// assert_eq!(vector.to_string(), "'fat':2 'rats':3 'the':1");
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone)]
+pub struct TextSearchConfig {
+    parser: Arc<dyn TextParser>,
+    mappings: HashMap<TokenType, Vec<Arc<dyn Dictionary>>>,
+}
+
+impl TextSearchConfig {
+    /// A configuration without any mappings, which produces empty vectors
+    pub fn new(parser: impl TextParser + 'static) -> Self {
+        TextSearchConfig {
+            parser: Arc::new(parser),
+            mappings: HashMap::new(),
+        }
+    }
+
+    /// The built-in `simple` configuration: the default parser with every token type
+    /// except `protocol`, `tag`, `entity` and `blank` mapped to the simple dictionary
+    pub fn simple() -> Self {
+        Self::with_dictionary(SimpleDictionary::default())
+    }
+
+    /// Like [`TextSearchConfig::simple`] but with a different dictionary for all
+    /// mapped token types
+    pub fn with_dictionary(dictionary: impl Dictionary + 'static) -> Self {
+        let types: Vec<TokenType> = TokenType::ALL
+            .into_iter()
+            .filter(|kind| {
+                !matches!(
+                    kind,
+                    TokenType::Protocol | TokenType::Tag | TokenType::Entity | TokenType::Blank
+                )
+            })
+            .collect();
+
+        Self::new(DefaultParser).add_mapping(&types, vec![Arc::new(dictionary)])
+    }
+
+    /// Same as `ALTER TEXT SEARCH CONFIGURATION ... ADD MAPPING FOR types WITH dictionaries`,
+    /// replacing any existing mapping of the types
+    pub fn add_mapping(
+        mut self,
+        types: &[TokenType],
+        dictionaries: Vec<Arc<dyn Dictionary>>,
+    ) -> Self {
+        for kind in types {
+            self.mappings.insert(*kind, dictionaries.clone());
+        }
+        self
+    }
+
+    /// Same as `ALTER TEXT SEARCH CONFIGURATION ... DROP MAPPING FOR types`
+    pub fn drop_mapping(mut self, types: &[TokenType]) -> Self {
+        for kind in types {
+            self.mappings.remove(kind);
+        }
+        self
+    }
+
+    /// Runs a token through the dictionaries mapped to its type, `None` if there is no
+    /// mapping or no dictionary recognizes it
+    pub fn lexize(&self, kind: TokenType, token: &str) -> Option<Vec<DictLexeme>> {
+        self.mappings
+            .get(&kind)?
+            .iter()
+            .find_map(|dictionary| dictionary.lexize(token))
+    }
+
+    /// Parses the text into lexemes with their positions (parsetext in ts_parse.c).
+    ///
+    /// Every token or phrase that some dictionary recognizes takes up a position, stop
+    /// words included. Tokens of 2047 bytes or more are ignored like on the server.
+    pub fn parse(&self, text: &str) -> Vec<ParsedWord> {
+        let (_, groups) = self.analyze(text);
+        groups.into_iter().flat_map(|group| group.words).collect()
+    }
+
+    /// All tokens of the text, along with the lexemes of every token or phrase some
+    /// dictionary recognized
+    pub(crate) fn analyze(&self, text: &str) -> (Vec<Token>, Vec<TokenGroup>) {
+        let tokens = self.parser.parse(text);
+
+        // only tokens with a mapping take part, phrases span the others
+        let mapped: Vec<(usize, &Vec<Arc<dyn Dictionary>>)> = tokens
+            .iter()
+            .enumerate()
+            .filter(|(_, token)| token.text.len() < MAX_LEXEME_LEN)
+            .filter_map(|(index, token)| Some((index, self.mappings.get(&token.kind)?)))
+            .collect();
+        let texts: Vec<&str> = mapped
+            .iter()
+            .map(|(index, _)| tokens[*index].text.as_str())
+            .collect();
+
+        let mut groups = Vec::new();
+        let mut pos: u16 = 0;
+        let mut i = 0;
+
+        while let Some((_, dictionaries)) = mapped.get(i) {
+            let Some((consumed, lexemes)) = dictionaries
+                .iter()
+                .find_map(|dictionary| dictionary.lexize_phrase(&texts[i..]))
+            else {
+                i += 1;
+                continue;
+            };
+            let consumed = consumed.clamp(1, mapped.len() - i);
+
+            pos = pos.saturating_add(1);
+            let mut words = Vec::with_capacity(lexemes.len());
+            for lexeme in lexemes {
+                if lexeme.add_pos {
+                    pos = pos.saturating_add(1);
+                }
+                words.push(ParsedWord {
+                    lexeme: lexeme.lexeme,
+                    variant: lexeme.variant,
+                    pos: pos.min(MAX_POSITION),
+                    prefix: lexeme.prefix,
+                });
+            }
+
+            groups.push(TokenGroup {
+                tokens: mapped[i..i + consumed]
+                    .iter()
+                    .map(|(index, _)| *index)
+                    .collect(),
+                words,
+                end: pos,
+            });
+            i += consumed;
+        }
+
+        (tokens, groups)
+    }
+
+    /// Same as `to_tsvector(config, text)`, every position has weight D
+    pub fn to_tsvector(&self, text: &str) -> PgTsVector {
+        make_tsvector(self.parse(text), Weight::D)
+    }
+
+    /// Vector of several parts of a document with their own weight, e.g. a title with
+    /// weight A and the body with weight B. Same as
+    /// `setweight(to_tsvector(title), 'A') || setweight(to_tsvector(body), 'B')`.
+    pub fn to_weighted_tsvector(&self, parts: &[(&str, Weight)]) -> PgTsVector {
+        parts
+            .iter()
+            .map(|(text, weight)| make_tsvector(self.parse(text), *weight))
+            .fold(PgTsVector { words: vec![] }, |vector, part| {
+                vector.concat(&part)
+            })
+    }
+}
+
+/// Every token a dictionary recognizes is one word of the query, stop words included
+impl QueryTokenizer for TextSearchConfig {
+    fn tokenize(&self, text: &str) -> Vec<QueryWord> {
+        let mut words: Vec<QueryWord> = Vec::new();
+        let mut last_variant = None;
+
+        for word in self.parse(text) {
+            // positions without lexemes in between are stop words
+            while words.len() < usize::from(word.pos) {
+                words.push(QueryWord::stopword());
+                last_variant = None;
+            }
+
+            let Some(current) = words.last_mut() else {
+                continue;
+            };
+            let lexeme = QueryLexeme {
+                lexeme: word.lexeme,
+                prefix: word.prefix,
+            };
+            match current.variants.last_mut() {
+                Some(variant) if last_variant == Some(word.variant) => variant.push(lexeme),
+                _ => current.variants.push(vec![lexeme]),
+            }
+            last_variant = Some(word.variant);
+        }
+
+        words
+    }
+}
+
+/// Lexemes sorted and merged like make_tsvector in to_tsany.c. Unlike `tsvector_in`
+/// this keeps at most 255 positions per lexeme.
+pub(crate) fn make_tsvector(words: Vec<ParsedWord>, weight: Weight) -> PgTsVector {
+    // String sorts bytewise like the server does
+    let mut grouped: BTreeMap<String, Vec<u16>> = BTreeMap::new();
+    for word in words {
+        grouped.entry(word.lexeme).or_default().push(word.pos);
+    }
+
+    let words = grouped
+        .into_iter()
+        .map(|(word, mut positions)| {
+            positions.sort_unstable();
+
+            let mut kept: Vec<u16> = Vec::new();
+            for pos in positions {
+                let full = kept.len() >= MAX_POSITIONS - 1;
+                let at_end = kept.last() == Some(&MAX_POSITION);
+                if kept.is_empty() || !full && !at_end && kept.last() != Some(&pos) {
+                    kept.push(pos);
+                }
+            }
+
+            Lexeme {
+                word,
+                positions: kept
+                    .into_iter()
+                    .map(|pos| Position { pos, weight })
+                    .collect(),
+            }
+        })
+        .collect();
+
+    PgTsVector { words }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TextSearchConfig;
+
+    // to_tsvector('simple', ...) captured from PostgreSQL 15 in a UTF8 database
+    #[test]
+    fn to_tsvector_like_simple_config() {
+        let config = TextSearchConfig::simple();
+
+        assert_eq!(
+            config
+                .to_tsvector("ÄΣ ΟΔΟΣ The Fat-Rats +1 b.a_b")
+                .to_string(),
+            "'+1':7 'b.a_b':8 'fat':5 'fat-rats':4 'rats':6 'the':3 'äσ':1 'οδοσ':2"
+        );
+
+        let text = format!("a {} b ", "x".repeat(2047));
+        assert_eq!(config.to_tsvector(&text).to_string(), "'a':1 'b':2");
+    }
+}
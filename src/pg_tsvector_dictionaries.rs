@@ -0,0 +1,491 @@
+use crate::pg_tsvector_config::{DictLexeme, Dictionary, Stopwords, lowercase};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+impl Stopwords {
+    /// Reads a stop word file like `english.stop` (readstoplist in dict_common.c).
+    ///
+    /// Every line holds one word, anything after the first whitespace is ignored and so
+    /// are lines starting with whitespace.
+    pub fn parse(contents: &str) -> Stopwords {
+        Stopwords::new(contents.lines().filter_map(|line| {
+            let word = line.split(char::is_whitespace).next()?;
+            (!word.is_empty()).then_some(word)
+        }))
+    }
+
+    /// [`Stopwords::parse`] on the contents of a file
+    pub fn load(path: impl AsRef<Path>) -> Result<Stopwords, Box<dyn Error>> {
+        Ok(Stopwords::parse(&fs::read_to_string(path)?))
+    }
+}
+
+/// The `synonym` dictionary template (dict_synonym.c), replacing a word by another.
+///
+/// Unknown words are passed on to the next dictionary. A trailing `*` on the synonym
+/// marks it as a prefix, which only makes a difference for `to_tsquery`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SynonymDictionary {
+    synonyms: HashMap<String, DictLexeme>,
+    case_sensitive: bool,
+}
+
+impl SynonymDictionary {
+    /// Reads a synonym file like `synonym_sample.syn`: every line is a word followed by
+    /// its synonym, lines with less than two words are skipped. Unless `case_sensitive`
+    /// is set, which is the `CaseSensitive` option, both are lowercased.
+    pub fn parse(contents: &str, case_sensitive: bool) -> SynonymDictionary {
+        let mut synonyms = HashMap::new();
+
+        for line in contents.lines() {
+            let mut words = line.split_whitespace();
+            let (Some(word), Some(synonym)) = (words.next(), words.next()) else {
+                continue;
+            };
+            let (synonym, prefix) = match synonym.strip_suffix('*') {
+                Some(synonym) => (synonym, true),
+                None => (synonym, false),
+            };
+
+            let (word, synonym) = if case_sensitive {
+                (word.to_string(), synonym.to_string())
+            } else {
+                (lowercase(word), lowercase(synonym))
+            };
+            // the first line for a word wins
+            synonyms.entry(word).or_insert(DictLexeme {
+                prefix,
+                ..DictLexeme::new(synonym)
+            });
+        }
+
+        SynonymDictionary {
+            synonyms,
+            case_sensitive,
+        }
+    }
+
+    /// [`SynonymDictionary::parse`] on the contents of a file
+    pub fn load(
+        path: impl AsRef<Path>,
+        case_sensitive: bool,
+    ) -> Result<SynonymDictionary, Box<dyn Error>> {
+        Ok(SynonymDictionary::parse(
+            &fs::read_to_string(path)?,
+            case_sensitive,
+        ))
+    }
+}
+
+impl Dictionary for SynonymDictionary {
+    fn lexize(&self, token: &str) -> Option<Vec<DictLexeme>> {
+        let synonym = if self.case_sensitive {
+            self.synonyms.get(token)
+        } else {
+            self.synonyms.get(&lowercase(token))
+        }?;
+
+        Some(vec![synonym.clone()])
+    }
+}
+
+// One rule of a thesaurus, the sample words are the lexemes the subdictionary produces
+// for them, `None` standing for any stop word
+struct ThesaurusRule {
+    sample: Vec<Option<Vec<String>>>,
+    substitute: Vec<DictLexeme>,
+}
+
+/// The `thesaurus` dictionary template (dict_thesaurus.c), replacing whole phrases.
+///
+/// Both the phrases and the input are normalized by the subdictionary before they are
+/// compared. The longest matching phrase wins, or the last one defined if several are
+/// equally long. Words that aren't part of any phrase are passed on to the next dictionary.
+pub struct ThesaurusDictionary {
+    rules: Vec<ThesaurusRule>,
+    subdictionary: Arc<dyn Dictionary>,
+    longest: usize,
+}
+
+impl ThesaurusDictionary {
+    /// Reads a thesaurus file like `thesaurus_sample.ths`.
+    ///
+    /// Every line is `sample words : substitute words`, lines starting with `#` are
+    /// comments. In the sample `?` stands for any stop word, in the substitute a word
+    /// starting with `*` is taken as it is instead of being normalized. Any other
+    /// character, `#` and `:` included, is part of a word.
+    pub fn parse(
+        contents: &str,
+        subdictionary: Arc<dyn Dictionary>,
+    ) -> Result<ThesaurusDictionary, Box<dyn Error>> {
+        let mut rules = Vec::new();
+
+        for (index, line) in contents.lines().enumerate() {
+            let line_number = index + 1;
+            // rules are numbered without the comments and empty lines
+            let rule_number = rules.len() + 1;
+            let trimmed = line.trim_start();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+
+            let Some((sample, substitute)) = line.split_once(':') else {
+                return Err(format!(
+                    "Invalid thesaurus: unexpected end of line on line {line_number}"
+                )
+                .into());
+            };
+
+            let sample = sample
+                .split_whitespace()
+                .map(|word| sample_word(subdictionary.as_ref(), word, rule_number))
+                .collect::<Result<Vec<_>, _>>()?;
+            if sample.is_empty() {
+                return Err(format!(
+                    "Invalid thesaurus: unexpected delimiter on line {line_number}"
+                )
+                .into());
+            }
+
+            let mut lexemes = Vec::new();
+            for word in substitute.split_whitespace() {
+                // `\` only marks the start of a word that is normalized as usual
+                let (word, as_is) = match word.strip_prefix('*') {
+                    Some(word) => (word, true),
+                    None => (word.strip_prefix('\\').unwrap_or(word), false),
+                };
+                if word.is_empty() {
+                    return Err(format!(
+                        "Invalid thesaurus: unexpected end of line or lexeme on line {line_number}"
+                    )
+                    .into());
+                }
+
+                let normalized = if as_is {
+                    vec![DictLexeme::new(word)]
+                } else {
+                    match subdictionary.lexize(word) {
+                        Some(lexemes) if lexemes.is_empty() => {
+                            return Err(format!(
+                                "Invalid thesaurus: substitute word \"{word}\" is a stop word (rule {rule_number})"
+                            )
+                            .into());
+                        }
+                        Some(lexemes) => lexemes,
+                        None => {
+                            return Err(format!(
+                                "Invalid thesaurus: substitute word \"{word}\" isn't recognized by subdictionary (rule {rule_number})"
+                            )
+                            .into());
+                        }
+                    }
+                };
+
+                // every word after the first takes the next position
+                let first_of_word = lexemes.len();
+                for (i, lexeme) in normalized.into_iter().enumerate() {
+                    lexemes.push(DictLexeme {
+                        add_pos: first_of_word > 0 && i == 0,
+                        ..lexeme
+                    });
+                }
+            }
+            if lexemes.is_empty() {
+                return Err(format!(
+                    "Invalid thesaurus: unexpected end of line on line {line_number}"
+                )
+                .into());
+            }
+
+            rules.push(ThesaurusRule {
+                sample,
+                substitute: lexemes,
+            });
+        }
+
+        let longest = rules
+            .iter()
+            .map(|rule| rule.sample.len())
+            .max()
+            .unwrap_or(0);
+
+        Ok(ThesaurusDictionary {
+            rules,
+            subdictionary,
+            longest,
+        })
+    }
+
+    /// [`ThesaurusDictionary::parse`] on the contents of a file
+    pub fn load(
+        path: impl AsRef<Path>,
+        subdictionary: Arc<dyn Dictionary>,
+    ) -> Result<ThesaurusDictionary, Box<dyn Error>> {
+        ThesaurusDictionary::parse(&fs::read_to_string(path)?, subdictionary)
+    }
+}
+
+fn sample_word(
+    subdictionary: &dyn Dictionary,
+    word: &str,
+    rule_number: usize,
+) -> Result<Option<Vec<String>>, Box<dyn Error>> {
+    if word == "?" {
+        return Ok(None);
+    }
+
+    match subdictionary.lexize(word) {
+        None => Err(format!(
+            "Invalid thesaurus: sample word \"{word}\" isn't recognized by subdictionary (rule {rule_number})"
+        )
+        .into()),
+        Some(lexemes) if lexemes.is_empty() => Err(format!(
+            "Invalid thesaurus: sample word \"{word}\" is a stop word (rule {rule_number}), use \"?\" to represent a stop word"
+        )
+        .into()),
+        Some(lexemes) => Ok(Some(
+            lexemes.into_iter().map(|lexeme| lexeme.lexeme).collect(),
+        )),
+    }
+}
+
+impl Dictionary for ThesaurusDictionary {
+    fn lexize(&self, token: &str) -> Option<Vec<DictLexeme>> {
+        self.lexize_phrase(&[token]).map(|(_, lexemes)| lexemes)
+    }
+
+    fn lexize_phrase(&self, tokens: &[&str]) -> Option<(usize, Vec<DictLexeme>)> {
+        let normalized: Vec<Option<Vec<DictLexeme>>> = tokens
+            .iter()
+            .take(self.longest)
+            .map(|token| self.subdictionary.lexize(token))
+            .collect();
+
+        let matches = |rule: &&ThesaurusRule| {
+            rule.sample.len() <= normalized.len()
+                && rule.sample.iter().zip(&normalized).all(|(sample, input)| {
+                    match (sample, input) {
+                        (None, Some(input)) => input.is_empty(),
+                        (Some(sample), Some(input)) => {
+                            input.iter().any(|lexeme| sample.contains(&lexeme.lexeme))
+                        }
+                        (_, None) => false,
+                    }
+                })
+        };
+
+        // max_by_key returns the last of equally long phrases
+        let rule = self
+            .rules
+            .iter()
+            .filter(matches)
+            .max_by_key(|rule| rule.sample.len())?;
+
+        Some((rule.sample.len(), rule.substitute.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pg_tsvector_config::{
+        DictLexeme, Dictionary, SimpleDictionary, Stopwords, TextSearchConfig,
+    };
+    use crate::pg_tsvector_dictionaries::{SynonymDictionary, ThesaurusDictionary};
+    use crate::pg_tsvector_tokenizer::TokenType;
+    use std::sync::Arc;
+
+    // Copies of the sample files shipped in the server's tsearch_data directory
+    const RV_STOP: &str = "the\nof\n";
+    const SYNONYM_SAMPLE: &str =
+        "postgres\tpgsql\npostgresql\tpgsql\npostgre\tpgsql\ngogle\tgoogl\nindices\tindex*\n";
+    const THESAURUS_SAMPLE: &str = "\
+#
+# Theasurus config file. Character ':' separates string from replacement, eg
+# sample-words : substitute-words
+#
+# Any substitute-word can be marked by preceding '*' character,
+# which means do not lexize this word
+# Docs: http://www.sai.msu.su/~megera/oddmuse/index.cgi/Thesaurus_dictionary
+
+one two three : *123
+one two : *12
+one : *1
+two : *2
+
+supernovae stars : *sn
+supernovae : *sn
+booking tickets : order invitation cards
+booking ? tickets : order invitation Cards
+";
+
+    fn lexemes(lexemes: Option<Vec<DictLexeme>>) -> Option<Vec<String>> {
+        lexemes.map(|lexemes| lexemes.into_iter().map(|lexeme| lexeme.lexeme).collect())
+    }
+
+    fn rv_simple() -> Arc<dyn Dictionary> {
+        Arc::new(SimpleDictionary::with_stopwords(Stopwords::parse(RV_STOP)))
+    }
+
+    // Configuration mapping asciiword to a thesaurus with `rules` over the rv_simple
+    // dictionary, then to rv_simple
+    fn thesaurus_config(rules: &str) -> TextSearchConfig {
+        let thesaurus = ThesaurusDictionary::parse(rules, rv_simple()).unwrap();
+        TextSearchConfig::with_dictionary(SimpleDictionary::default()).add_mapping(
+            &[TokenType::AsciiWord],
+            vec![Arc::new(thesaurus), rv_simple()],
+        )
+    }
+
+    #[test]
+    fn parses_stopwords_like_readstoplist() {
+        let stopwords = Stopwords::parse("The\n of\nand x\n\nA\t\nfoo#bar\n");
+        assert_eq!(stopwords.len(), 4);
+        for word in ["the", "and", "a", "foo#bar"] {
+            assert!(stopwords.contains(word), "{word}");
+        }
+        for word in ["of", "x", "foo"] {
+            assert!(!stopwords.contains(word), "{word}");
+        }
+    }
+
+    // (word, ts_lexize(synonym_sample), ts_lexize(synonym_sample with CaseSensitive))
+    // captured from PostgreSQL 15
+    const SYNONYMS: &[(&str, Option<&str>, Option<&str>)] = &[
+        ("postgres", Some("pgsql"), Some("pgsql")),
+        ("Postgres", Some("pgsql"), None),
+        ("POSTGRESQL", Some("pgsql"), None),
+        ("gogle", Some("googl"), Some("googl")),
+        ("indices", Some("index"), Some("index")),
+        ("google", None, None),
+        ("pgsql", None, None),
+    ];
+
+    #[test]
+    fn lexizes_synonyms_like_the_server() {
+        let synonyms = SynonymDictionary::parse(SYNONYM_SAMPLE, false);
+        let case_sensitive = SynonymDictionary::parse(SYNONYM_SAMPLE, true);
+
+        for (word, expected, expected_case_sensitive) in SYNONYMS {
+            let expected = expected.map(|lexeme| vec![lexeme.to_string()]);
+            assert_eq!(lexemes(synonyms.lexize(word)), expected, "{word}");
+            let expected = expected_case_sensitive.map(|lexeme| vec![lexeme.to_string()]);
+            assert_eq!(lexemes(case_sensitive.lexize(word)), expected, "{word}");
+        }
+
+        // only the synonym marked with `*` is a prefix
+        assert!(synonyms.lexize("indices").unwrap()[0].prefix);
+        assert!(!synonyms.lexize("postgres").unwrap()[0].prefix);
+    }
+
+    // (input, ts_lexize(thesaurus_sample) or to_tsvector with it) captured from
+    // PostgreSQL 15, with rv_simple as subdictionary
+    const THESAURUS_LEXIZE: &[(&str, Option<&str>)] = &[
+        ("one", Some("1")),
+        ("One", Some("1")),
+        ("two", Some("2")),
+        ("three", None),
+        ("supernovae", Some("sn")),
+        ("stars", None),
+        ("booking", None),
+        ("the", None),
+    ];
+
+    const THESAURUS_TSVECTOR: &[(&str, &str)] = &[
+        ("one two three", "'123':1"),
+        ("one two", "'12':1"),
+        ("one three", "'1':1 'three':2"),
+        ("two one", "'1':2 '2':1"),
+        ("one two three four", "'123':1 'four':2"),
+        ("one one two", "'1':1 '12':2"),
+        ("supernovae stars", "'sn':1"),
+        ("supernovae the stars", "'sn':1 'stars':3"),
+        ("booking tickets", "'cards':3 'invitation':2 'order':1"),
+        ("booking the tickets", "'cards':3 'invitation':2 'order':1"),
+        ("booking of tickets", "'cards':3 'invitation':2 'order':1"),
+        ("booking fat tickets", "'booking':1 'fat':2 'tickets':3"),
+        ("the booking tickets", "'cards':4 'invitation':3 'order':2"),
+    ];
+
+    #[test]
+    fn thesaurus_like_the_server() {
+        let thesaurus = ThesaurusDictionary::parse(THESAURUS_SAMPLE, rv_simple()).unwrap();
+        for (word, expected) in THESAURUS_LEXIZE {
+            let expected = expected.map(|lexeme| vec![lexeme.to_string()]);
+            assert_eq!(lexemes(thesaurus.lexize(word)), expected, "{word}");
+        }
+
+        let config = thesaurus_config(THESAURUS_SAMPLE);
+        for (input, expected) in THESAURUS_TSVECTOR {
+            assert_eq!(config.to_tsvector(input).to_string(), *expected, "{input}");
+        }
+    }
+
+    // The longest phrase wins and the last one defined of equally long phrases,
+    // to_tsvector output captured from PostgreSQL 15
+    #[test]
+    fn thesaurus_picks_like_the_server() {
+        let config = thesaurus_config(
+            "fat rat : *first\nfat rat : *second\nfat : *one\nfat ? cat : *stop\n",
+        );
+        let cases = [
+            ("fat rat", "'second':1"),
+            ("fat", "'one':1"),
+            ("fat the cat", "'stop':1"),
+            ("fat of cat", "'stop':1"),
+            ("fat dog cat", "'cat':3 'dog':2 'one':1"),
+            ("fat rat cat", "'cat':2 'second':1"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(config.to_tsvector(input).to_string(), expected, "{input}");
+        }
+
+        // only lines starting with `#` are comments, `\` starts a word that is
+        // normalized and `:` is part of the words of the substitute
+        let config = thesaurus_config("# c\n\nfat : *x # note\nrat : \\Cat a:b\ncat : rat : dog\n");
+        let cases = [
+            ("fat", "'#':2 'note':3 'x':1"),
+            ("rat", "'a:b':2 'cat':1"),
+            ("cat", "':':2 'dog':3 'rat':1"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(config.to_tsvector(input).to_string(), expected, "{input}");
+        }
+    }
+
+    // Errors of CREATE TEXT SEARCH DICTIONARY captured from PostgreSQL 15
+    const THESAURUS_ERRORS: &[(&str, &str)] = &[
+        ("fat rat\n", "unexpected end of line on line 1"),
+        ("fat : \n", "unexpected end of line on line 1"),
+        (" : *x\n", "unexpected delimiter on line 1"),
+        ("fat : *\n", "unexpected end of line or lexeme on line 1"),
+        (
+            "fat : rat *\n",
+            "unexpected end of line or lexeme on line 1",
+        ),
+        (
+            "# c\nfat : *x\nrat the : *y\n",
+            "sample word \"the\" is a stop word (rule 2), use \"?\" to represent a stop word",
+        ),
+        (
+            "# c\nfat : *x\ncat : the\n",
+            "substitute word \"the\" is a stop word (rule 2)",
+        ),
+    ];
+
+    #[test]
+    fn rejects_what_the_server_rejects() {
+        for (rules, expected) in THESAURUS_ERRORS {
+            let error = ThesaurusDictionary::parse(rules, rv_simple())
+                .err()
+                .unwrap();
+            assert_eq!(
+                error.to_string(),
+                format!("Invalid thesaurus: {expected}"),
+                "{rules}"
+            );
+        }
+    }
+}
@@ -0,0 +1,301 @@
+use crate::pg_tsvector::{Lexeme, MAX_POSITION, MAX_POSITIONS, PgTsVector, Position, Weight};
+use std::cmp::Ordering;
+use std::error::Error;
+
+/// One row of `unnest(tsvector)`. `positions` and `weights` are `None` for a lexeme
+/// without positions, same as the NULLs the server returns.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnnestRow {
+    pub lexeme: String,
+    pub positions: Option<Vec<u16>>,
+    pub weights: Option<Vec<Weight>>,
+}
+
+// The functions below expect vectors in canonical form, like the server sends them
+// and the parser produces them, see PgTsVector::canonicalize.
+impl PgTsVector {
+    /// Same as `tsvector || tsvector`: the positions of `other` are shifted by the largest
+    /// position of this vector, so its text counts as following this one.
+    /// Positions of lexemes found in both are merged.
+    pub fn concat(&self, other: &PgTsVector) -> PgTsVector {
+        let max_pos = self
+            .words
+            .iter()
+            .flat_map(|lexeme| &lexeme.positions)
+            .map(|position| position.pos)
+            .max()
+            .unwrap_or(0);
+
+        let shifted = |lexeme: &Lexeme| Lexeme {
+            word: lexeme.word.clone(),
+            positions: add_positions(vec![], &lexeme.positions, max_pos),
+        };
+
+        let mut words = Vec::with_capacity(self.words.len() + other.words.len());
+        let (mut left, mut right) = (self.words.iter().peekable(), other.words.iter().peekable());
+
+        loop {
+            let ordering = match (left.peek(), right.peek()) {
+                (Some(l), Some(r)) => l.word.as_bytes().cmp(r.word.as_bytes()),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => break,
+            };
+
+            match ordering {
+                Ordering::Less => words.extend(left.next().cloned()),
+                Ordering::Greater => words.extend(right.next().map(shifted)),
+                Ordering::Equal => {
+                    if let (Some(l), Some(r)) = (left.next(), right.next()) {
+                        words.push(Lexeme {
+                            word: l.word.clone(),
+                            positions: add_positions(l.positions.clone(), &r.positions, max_pos),
+                        });
+                    }
+                }
+            }
+        }
+
+        PgTsVector { words }
+    }
+
+    /// Same as `setweight(tsvector, weight)`: gives every position the weight
+    pub fn setweight(&self, weight: Weight) -> PgTsVector {
+        self.map_positions(|_| true, weight)
+    }
+
+    /// Same as `setweight(tsvector, weight, lexemes)`: only changes the positions of the
+    /// given lexemes, those that aren't part of the vector are ignored
+    pub fn setweight_lexemes(&self, weight: Weight, lexemes: &[&str]) -> PgTsVector {
+        self.map_positions(|word| lexemes.contains(&word), weight)
+    }
+
+    fn map_positions<F>(&self, mut selected: F, weight: Weight) -> PgTsVector
+    where
+        F: FnMut(&str) -> bool,
+    {
+        let words = self
+            .words
+            .iter()
+            .map(|lexeme| {
+                let mut lexeme = lexeme.clone();
+                if selected(&lexeme.word) {
+                    for position in &mut lexeme.positions {
+                        position.weight = weight;
+                    }
+                }
+                lexeme
+            })
+            .collect();
+
+        PgTsVector { words }
+    }
+
+    /// Same as `strip(tsvector)`: removes all positions and weights
+    pub fn strip(&self) -> PgTsVector {
+        let words = self
+            .words
+            .iter()
+            .map(|lexeme| Lexeme {
+                word: lexeme.word.clone(),
+                positions: vec![],
+            })
+            .collect();
+
+        PgTsVector { words }
+    }
+
+    /// Same as `ts_delete(tsvector, lexeme)`
+    pub fn delete(&self, lexeme: &str) -> PgTsVector {
+        self.delete_all(&[lexeme])
+    }
+
+    /// Same as `ts_delete(tsvector, lexemes)`
+    pub fn delete_all(&self, lexemes: &[&str]) -> PgTsVector {
+        let words = self
+            .words
+            .iter()
+            .filter(|lexeme| !lexemes.contains(&lexeme.word.as_str()))
+            .cloned()
+            .collect();
+
+        PgTsVector { words }
+    }
+
+    /// Same as `ts_filter(tsvector, weights)`: keeps only the positions with one of the
+    /// given weights. Lexemes left without positions are removed, which includes those
+    /// that had none to begin with.
+    pub fn filter(&self, weights: &[Weight]) -> PgTsVector {
+        let words = self
+            .words
+            .iter()
+            .filter_map(|lexeme| {
+                let positions: Vec<Position> = lexeme
+                    .positions
+                    .iter()
+                    .filter(|position| weights.contains(&position.weight))
+                    .copied()
+                    .collect();
+
+                (!positions.is_empty()).then(|| Lexeme {
+                    word: lexeme.word.clone(),
+                    positions,
+                })
+            })
+            .collect();
+
+        PgTsVector { words }
+    }
+
+    /// Same as `unnest(tsvector)`: one row per lexeme
+    pub fn unnest(&self) -> Vec<UnnestRow> {
+        self.words
+            .iter()
+            .map(|lexeme| {
+                let has_positions = !lexeme.positions.is_empty();
+                UnnestRow {
+                    lexeme: lexeme.word.clone(),
+                    positions: has_positions
+                        .then(|| lexeme.positions.iter().map(|p| p.pos).collect()),
+                    weights: has_positions
+                        .then(|| lexeme.positions.iter().map(|p| p.weight).collect()),
+                }
+            })
+            .collect()
+    }
+
+    /// Same as `array_to_tsvector(text[])`: a vector of the lexemes without positions.
+    /// Duplicates are removed, empty strings are rejected like on the server.
+    pub fn from_lexemes<S: AsRef<str>>(lexemes: &[S]) -> Result<PgTsVector, Box<dyn Error>> {
+        let mut words = Vec::with_capacity(lexemes.len());
+
+        for lexeme in lexemes {
+            let word = lexeme.as_ref();
+            if word.is_empty() {
+                return Err("Invalid tsvector: lexeme array may not contain empty strings".into());
+            }
+            words.push(Lexeme {
+                word: word.to_string(),
+                positions: vec![],
+            });
+        }
+
+        let mut vector = PgTsVector { words };
+        vector.canonicalize();
+
+        Ok(vector)
+    }
+
+    /// Same as `tsvector_to_array(tsvector)`: the lexemes without their positions
+    pub fn to_array(&self) -> Vec<String> {
+        self.words
+            .iter()
+            .map(|lexeme| lexeme.word.clone())
+            .collect()
+    }
+}
+
+/// Appends `added` shifted by `max_pos` to `positions` (add_pos in tsvector_op.c).
+/// Like the server this stops at 256 positions or once the largest position is reached.
+fn add_positions(mut positions: Vec<Position>, added: &[Position], max_pos: u16) -> Vec<Position> {
+    for position in added {
+        if positions.len() >= MAX_POSITIONS
+            || positions
+                .last()
+                .is_some_and(|last| last.pos == MAX_POSITION)
+        {
+            break;
+        }
+
+        positions.push(Position {
+            pos: position.pos.saturating_add(max_pos).min(MAX_POSITION),
+            weight: position.weight,
+        });
+    }
+
+    positions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UnnestRow;
+    use crate::pg_tsvector::{PgTsVector, Weight};
+    use std::str::FromStr;
+
+    fn vector(s: &str) -> PgTsVector {
+        PgTsVector::from_str(s).unwrap()
+    }
+
+    // (left, right, left || right) captured from PostgreSQL 15
+    const CONCAT: &[(&str, &str, &str)] = &[
+        ("a:1 b:2", "c:1 d:2 b:3", "'a':1 'b':2,5 'c':3 'd':4"),
+        ("a b:3", "a:2 c", "'a':5 'b':3 'c'"),
+        ("a:1 b:2A", "b c", "'a':1 'b':2A 'c'"),
+        ("a b", "b:1 c:2B", "'a' 'b':1 'c':2B"),
+        ("x:16380", "x:5 y:10", "'x':16380,16383 'y':16383"),
+        ("", "a:1", "'a':1"),
+        ("a:1,2 b:3", "", "'a':1,2 'b':3"),
+    ];
+
+    #[test]
+    fn concat_like_the_server() {
+        for (left, right, expected) in CONCAT {
+            let concat = vector(left).concat(&vector(right));
+            assert_eq!(concat.to_string(), *expected, "{left} || {right}");
+        }
+
+        // a lexeme keeps at most 256 positions
+        let positions: Vec<String> = (1..=255).map(|pos| pos.to_string()).collect();
+        let long = vector(&format!("a:{}", positions.join(",")));
+        let concat = long.concat(&vector("a:1,2"));
+        let positions = &concat.words[0].positions;
+        assert_eq!(positions.len(), 256);
+        assert_eq!(positions[255].pos, 256);
+    }
+
+    #[test]
+    fn functions_like_the_server() {
+        // results captured from PostgreSQL 15
+        let v = vector("a:1,2B b c:3C");
+        assert_eq!(v.setweight(Weight::A).to_string(), "'a':1A,2A 'b' 'c':3A");
+        assert_eq!(
+            v.setweight_lexemes(Weight::B, &["a", "c", "zz"])
+                .to_string(),
+            "'a':1B,2B 'b' 'c':3B"
+        );
+        assert_eq!(v.strip().to_string(), "'a' 'b' 'c'");
+        assert_eq!(
+            v.unnest(),
+            [
+                UnnestRow {
+                    lexeme: "a".to_string(),
+                    positions: Some(vec![1, 2]),
+                    weights: Some(vec![Weight::D, Weight::B]),
+                },
+                UnnestRow {
+                    lexeme: "b".to_string(),
+                    positions: None,
+                    weights: None,
+                },
+                UnnestRow {
+                    lexeme: "c".to_string(),
+                    positions: Some(vec![3]),
+                    weights: Some(vec![Weight::C]),
+                },
+            ]
+        );
+
+        let v = vector("a:1 b c:3");
+        assert_eq!(v.delete("b").to_string(), "'a':1 'c':3");
+        assert_eq!(v.delete_all(&["a", "c", "zz"]).to_string(), "'b'");
+
+        let v = vector("a:1,2B b c:3C,4 d:5D");
+        assert_eq!(v.filter(&[Weight::A, Weight::C]).to_string(), "'c':3C");
+        assert_eq!(v.filter(&[Weight::D]).to_string(), "'a':1 'c':4 'd':5");
+
+        let v = PgTsVector::from_lexemes(&["c", "a", "b", "a"]).unwrap();
+        assert_eq!(v.to_string(), "'a' 'b' 'c'");
+        assert!(PgTsVector::from_lexemes(&["a", ""]).is_err());
+        assert_eq!(vector("b:1 a c:2").to_array(), ["a", "b", "c"]);
+    }
+}
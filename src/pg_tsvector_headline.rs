@@ -0,0 +1,905 @@
+use crate::pg_tsquery::{PgTsQuery, Value};
+use crate::pg_tsquery_match::{Ternary, execute};
+use crate::pg_tsquery_tree::TsQueryNode;
+use crate::pg_tsvector::{MAX_LEXEME_LEN, MAX_POSITION, PgTsVector};
+use crate::pg_tsvector_config::TextSearchConfig;
+use crate::pg_tsvector_tokenizer::{DefaultParser, TextParser, Token, TokenType};
+use std::collections::HashMap;
+use std::error::Error;
+use std::str::FromStr;
+
+/// Options of `ts_headline`, the defaults are the ones of the server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeadlineOptions {
+    /// Put in front of every query word, `StartSel`
+    pub start_sel: String,
+    /// Put after every query word, `StopSel`
+    pub stop_sel: String,
+    /// Longest headline in words, `MaxWords`
+    pub max_words: usize,
+    /// Shortest headline in words, `MinWords`
+    pub min_words: usize,
+    /// Words of this many bytes or less aren't used to start or end a headline,
+    /// `ShortWord`
+    pub short_word: usize,
+    /// Use the whole document instead of a part of it, `HighlightAll`
+    pub highlight_all: bool,
+    /// Number of fragments to show, 0 selects a single headline instead, `MaxFragments`
+    pub max_fragments: usize,
+    /// Put between fragments, `FragmentDelimiter`
+    pub fragment_delimiter: String,
+}
+
+impl Default for HeadlineOptions {
+    fn default() -> Self {
+        HeadlineOptions {
+            start_sel: "<b>".to_string(),
+            stop_sel: "</b>".to_string(),
+            max_words: 35,
+            min_words: 15,
+            short_word: 3,
+            highlight_all: false,
+            max_fragments: 0,
+            fragment_delimiter: " ... ".to_string(),
+        }
+    }
+}
+
+impl HeadlineOptions {
+    fn validate(&self) -> Result<(), Box<dyn Error>> {
+        // the word limits don't matter when the whole document is shown
+        if self.highlight_all {
+            return Ok(());
+        }
+        if self.min_words >= self.max_words {
+            return Err("Invalid headline options: MinWords should be less than MaxWords".into());
+        }
+        if self.min_words == 0 {
+            return Err("Invalid headline options: MinWords should be positive".into());
+        }
+        Ok(())
+    }
+}
+
+/// Parses the options string of `ts_headline`, like
+/// `MaxFragments=2, StartSel="<em>", StopSel=</em>`. Names are case insensitive and
+/// values may be quoted.
+impl FromStr for HeadlineOptions {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut options = HeadlineOptions::default();
+
+        for (name, value) in parse_option_list(s)? {
+            let number = || {
+                value.trim().parse::<usize>().map_err(|_| {
+                    format!("Invalid headline options: invalid value for {name}: \"{value}\"")
+                })
+            };
+
+            match name.to_lowercase().as_str() {
+                "maxwords" => options.max_words = number()?,
+                "minwords" => options.min_words = number()?,
+                "shortword" => options.short_word = number()?,
+                "maxfragments" => options.max_fragments = number()?,
+                "startsel" => options.start_sel = value,
+                "stopsel" => options.stop_sel = value,
+                "fragmentdelimiter" => options.fragment_delimiter = value,
+                "highlightall" => {
+                    options.highlight_all = matches!(
+                        value.to_lowercase().as_str(),
+                        "1" | "on" | "true" | "t" | "y" | "yes"
+                    );
+                }
+                _ => {
+                    return Err(format!(
+                        "Invalid headline options: unrecognized headline parameter \"{name}\""
+                    )
+                    .into());
+                }
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+// `name = value` pairs separated by commas or whitespace, values in single or double
+// quotes may contain both and double the quote to escape it (deserialize_deflist)
+fn parse_option_list(s: &str) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+    let mut options = Vec::new();
+    let mut chars = s.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace() || *c == ',').is_some() {}
+        if chars.peek().is_none() {
+            break;
+        }
+
+        let mut name = String::new();
+        while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != '=' && *c != ',') {
+            name.push(c);
+        }
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.next() != Some('=') {
+            return Err(format!("Invalid headline options: missing \"=\" after \"{name}\"").into());
+        }
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+
+        let mut value = String::new();
+        match chars.next_if(|c| *c == '"' || *c == '\'') {
+            Some(quote) => loop {
+                match chars.next() {
+                    Some(c) if c == quote => {
+                        if chars.next_if_eq(&quote).is_none() {
+                            break;
+                        }
+                        value.push(quote);
+                    }
+                    Some(c) => value.push(c),
+                    None => {
+                        return Err(format!(
+                            "Invalid headline options: unterminated quoted value for \"{name}\""
+                        )
+                        .into());
+                    }
+                }
+            },
+            None => {
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != ',') {
+                    value.push(c);
+                }
+            }
+        }
+
+        options.push((name, value));
+    }
+
+    Ok(options)
+}
+
+impl TextSearchConfig {
+    /// Same as `ts_headline(config, document, query, options)`: the part of the document
+    /// that matches the query best, with the query words highlighted
+    /// (prsd_headline in wparser_def.c).
+    ///
+    /// ```
+    /// use postgres_types_extra::pg_tsquery::PgTsQuery;
+    /// use postgres_types_extra::pg_tsvector_config::TextSearchConfig;
+    /// use postgres_types_extra::pg_tsvector_headline::HeadlineOptions;
+    /// use std::str::FromStr;
+    ///
+    /// let config = TextSearchConfig::simple();
+    /// let query = PgTsQuery::from_str("fat & cat")?;
+    /// let headline = config.headline("a fat cat sat", &query, &HeadlineOptions::default())?;
+    /// assert_eq!(headline, "a <b>fat</b> <b>cat</b> sat");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn headline(
+        &self,
+        document: &str,
+        query: &PgTsQuery,
+        options: &HeadlineOptions,
+    ) -> Result<String, Box<dyn Error>> {
+        let (tokens, groups) = self.analyze(document);
+
+        // every token of a phrase gets all lexemes of it
+        let mut lexemes: Vec<Vec<(&str, u16)>> = vec![Vec::new(); tokens.len()];
+        for group in &groups {
+            for index in &group.tokens {
+                lexemes[*index] = group
+                    .words
+                    .iter()
+                    .map(|word| (word.lexeme.as_str(), word.pos))
+                    .collect();
+            }
+        }
+
+        let tokens: Vec<(&Token, Vec<(&str, u16)>)> = tokens.iter().zip(lexemes).collect();
+        headline(&tokens, query, options)
+    }
+}
+
+impl PgTsVector {
+    /// `ts_headline` for a document this vector was made from, for when the configuration
+    /// isn't available locally, e.g. one using a stemmer.
+    ///
+    /// The document is split with the default parser and its words are matched to the
+    /// lexemes by position. That assumes the configuration maps every token type except
+    /// `protocol`, `tag`, `entity` and `blank` like the built-in ones do. With a thesaurus
+    /// or other mappings the positions don't line up and the result will be off.
+    pub fn headline(
+        &self,
+        document: &str,
+        query: &PgTsQuery,
+        options: &HeadlineOptions,
+    ) -> Result<String, Box<dyn Error>> {
+        let mut by_position: HashMap<u16, Vec<&str>> = HashMap::new();
+        for lexeme in &self.words {
+            for position in &lexeme.positions {
+                by_position
+                    .entry(position.pos)
+                    .or_default()
+                    .push(lexeme.word.as_str());
+            }
+        }
+
+        let tokens = DefaultParser.parse(document);
+        let mut pos: u16 = 0;
+        let mut words = Vec::with_capacity(tokens.len());
+
+        for token in &tokens {
+            let mapped = !matches!(
+                token.kind,
+                TokenType::Protocol | TokenType::Tag | TokenType::Entity | TokenType::Blank
+            );
+            if !mapped || token.text.len() >= MAX_LEXEME_LEN {
+                words.push((token, vec![]));
+                continue;
+            }
+
+            pos = pos.saturating_add(1).min(MAX_POSITION);
+            let lexemes = by_position
+                .get(&pos)
+                .map(|lexemes| lexemes.iter().map(|lexeme| (*lexeme, pos)).collect())
+                .unwrap_or_default();
+            words.push((token, lexemes));
+        }
+
+        headline(&words, query, options)
+    }
+}
+
+// One token of the document and how it is shown (HeadlineWordEntry in ts_public.h)
+#[derive(Debug, Clone)]
+struct HeadlineWord<'a> {
+    kind: TokenType,
+    text: &'a str,
+    pos: i32,
+    // the query operand the word matches
+    item: Option<&'a Value>,
+    // a copy of the previous word for another operand it matches, not shown
+    repeated: bool,
+    selected: bool,
+    in_headline: bool,
+    // tags are shown as a space
+    replace: bool,
+    // hyphenated words and URLs are shown through their parts
+    skip: bool,
+}
+
+fn headline(
+    tokens: &[(&Token, Vec<(&str, u16)>)],
+    query: &PgTsQuery,
+    options: &HeadlineOptions,
+) -> Result<String, Box<dyn Error>> {
+    options.validate()?;
+
+    let root = query.to_tree()?;
+    let mut values = Vec::new();
+    if let Some(root) = &root {
+        operands(root, &mut values);
+    }
+
+    let mut headline = Headline {
+        words: Vec::with_capacity(tokens.len()),
+        options,
+    };
+    for (token, lexemes) in tokens {
+        // the server drops tokens of 2047 bytes or more from the headline altogether
+        if token.text.len() >= MAX_LEXEME_LEN {
+            continue;
+        }
+
+        headline.words.push(HeadlineWord {
+            kind: token.kind,
+            text: &token.text,
+            pos: 0,
+            item: None,
+            repeated: false,
+            selected: false,
+            in_headline: false,
+            replace: false,
+            skip: false,
+        });
+        for (lexeme, pos) in lexemes {
+            headline.find_items(&values, lexeme, i32::from(*pos));
+        }
+    }
+
+    if options.max_fragments == 0 {
+        headline.mark_words(root.as_ref());
+    } else {
+        headline.mark_fragments(root.as_ref());
+    }
+
+    Ok(headline.generate())
+}
+
+// The operands of the query in the order they are stored in, operator first and the
+// right operand before the left one. Words point to the operand they match, which
+// tells apart operands with the same text like the server's pointers do.
+fn operands<'a>(node: &'a TsQueryNode, values: &mut Vec<&'a Value>) {
+    match node {
+        TsQueryNode::Term(value) => values.push(value),
+        TsQueryNode::Not(operand) => operands(operand, values),
+        TsQueryNode::And(left, right)
+        | TsQueryNode::Or(left, right)
+        | TsQueryNode::Phrase { left, right, .. } => {
+            operands(right, values);
+            operands(left, values);
+        }
+    }
+}
+
+// A candidate fragment when several are shown (CoverPos in wparser_def.c)
+#[derive(Debug, Clone, Copy)]
+struct Fragment {
+    start: isize,
+    end: isize,
+    // words and query words in the fragment
+    length: usize,
+    items: usize,
+    chosen: bool,
+    excluded: bool,
+}
+
+struct Headline<'a, 'o> {
+    words: Vec<HeadlineWord<'a>>,
+    options: &'o HeadlineOptions,
+}
+
+impl<'a> Headline<'a, '_> {
+    // Marks the last word with the operands the lexeme matches, a copy of the word is
+    // added for every operand after the first (hlfinditem in ts_parse.c)
+    fn find_items(&mut self, values: &[&'a Value], lexeme: &str, pos: i32) {
+        let Some(index) = self.words.len().checked_sub(1) else {
+            return;
+        };
+        self.words[index].pos = pos;
+
+        for value in values {
+            let matches = if value.prefix != 0 {
+                lexeme.starts_with(&value.text)
+            } else {
+                lexeme == value.text
+            };
+            if !matches {
+                continue;
+            }
+
+            if self.words[index].item.is_some() {
+                let copy = HeadlineWord {
+                    item: Some(value),
+                    repeated: true,
+                    ..self.words[index].clone()
+                };
+                self.words.push(copy);
+            } else {
+                self.words[index].item = Some(value);
+            }
+        }
+    }
+
+    // Positions of the words in `range` matching an operand (checkcondition_HL)
+    fn check(
+        &self,
+        range: impl std::slice::SliceIndex<[HeadlineWord<'a>], Output = [HeadlineWord<'a>]>,
+        value: &Value,
+    ) -> (Ternary, Vec<i32>) {
+        let mut positions: Vec<i32> = Vec::new();
+
+        for word in &self.words[range] {
+            if word.item.is_some_and(|item| std::ptr::eq(item, value))
+                && positions.last().is_none_or(|last| *last < word.pos)
+            {
+                positions.push(word.pos);
+            }
+        }
+
+        if positions.is_empty() {
+            (Ternary::No, positions)
+        } else {
+            (Ternary::Yes, positions)
+        }
+    }
+
+    fn word(&self, i: isize) -> &HeadlineWord<'a> {
+        &self.words[i as usize]
+    }
+
+    fn len(&self) -> isize {
+        self.words.len() as isize
+    }
+
+    // Tokens that don't count as words (NONWORDTOKEN)
+    fn non_word(&self, i: isize) -> bool {
+        matches!(
+            self.word(i).kind,
+            TokenType::Blank
+                | TokenType::Tag
+                | TokenType::Url
+                | TokenType::NumHWord
+                | TokenType::AsciiHWord
+                | TokenType::HWord
+        )
+    }
+
+    // A query word that is shown (INTERESTINGWORD)
+    fn interesting(&self, i: isize) -> bool {
+        let word = self.word(i);
+        word.item.is_some() && !word.repeated
+    }
+
+    // Not a good word to start or end a headline with (BADENDPOINT)
+    fn bad_endpoint(&self, i: isize) -> bool {
+        let word = self.word(i);
+        let no_end = self.non_word(i)
+            || matches!(
+                word.kind,
+                TokenType::SFloat
+                    | TokenType::Float
+                    | TokenType::Int
+                    | TokenType::UInt
+                    | TokenType::Version
+                    | TokenType::Protocol
+                    | TokenType::Entity
+            );
+
+        (no_end || word.text.len() <= self.options.short_word) && !self.interesting(i)
+    }
+
+    fn counts(&self, i: isize) -> (usize, usize) {
+        (
+            usize::from(!self.non_word(i)),
+            usize::from(self.interesting(i)),
+        )
+    }
+
+    // The first query word at index `i` or later (hlFirstIndex)
+    fn first_item(&self, i: isize) -> Option<isize> {
+        (i.max(0)..self.len()).find(|&i| self.word(i).item.is_some())
+    }
+
+    // The stretch of words that satisfies the query starting earliest, at the index
+    // `next` or later, and of those the shortest. Both ends are query words (hlCover).
+    fn cover(
+        &self,
+        root: &TsQueryNode,
+        max_cover: isize,
+        next: &mut isize,
+    ) -> Option<(isize, isize)> {
+        let mut start = self.first_item(*next);
+
+        while let Some(p) = start {
+            let mut next_start = None;
+            let mut end = Some(p);
+
+            while let Some(q) = end
+                && q - p < max_cover
+            {
+                let range = p as usize..=q as usize;
+                if execute(root, &|value: &Value, _| self.check(range.clone(), value))
+                    != Ternary::No
+                {
+                    *next = p + 1;
+                    return Some((p, q));
+                }
+
+                end = self.first_item(q + 1);
+                next_start = next_start.or(end);
+            }
+
+            start = next_start;
+        }
+
+        None
+    }
+
+    // mark_fragment
+    fn mark(&mut self, start: isize, end: isize) {
+        let highlight_all = self.options.highlight_all;
+
+        for i in start.max(0)..=end {
+            let word = &mut self.words[i as usize];
+            if word.item.is_some() {
+                word.selected = true;
+            }
+
+            let hyphenated_or_url = matches!(
+                word.kind,
+                TokenType::Url | TokenType::NumHWord | TokenType::AsciiHWord | TokenType::HWord
+            );
+            if !highlight_all && word.kind == TokenType::Tag {
+                word.replace = true;
+            } else if hyphenated_or_url {
+                word.skip = true;
+            }
+
+            word.in_headline = !word.repeated;
+        }
+    }
+
+    fn max_cover(&self) -> isize {
+        self.options
+            .max_words
+            .saturating_mul(10)
+            .max(100)
+            .try_into()
+            .unwrap_or(isize::MAX)
+    }
+
+    // The first MinWords words, when nothing matches
+    fn mark_start(&mut self) {
+        let mut length = 0;
+        let mut end = -1;
+        let mut i = 0;
+
+        while i < self.len() && length < self.options.min_words {
+            length += self.counts(i).0;
+            end = i;
+            i += 1;
+        }
+
+        self.mark(0, end);
+    }
+
+    // Single headline around the best cover (mark_hl_words)
+    fn mark_words(&mut self, root: Option<&TsQueryNode>) {
+        if self.options.highlight_all {
+            self.mark(0, self.len() - 1);
+            return;
+        }
+
+        let max_words = self.options.max_words;
+        let min_words = self.options.min_words;
+        let max_cover = self.max_cover();
+
+        let mut best: Option<(isize, isize, usize, bool)> = None;
+        let mut next = 0;
+
+        while let Some((p, q)) = root.and_then(|root| self.cover(root, max_cover, &mut next)) {
+            // the cover, up to MaxWords
+            let (mut length, mut items) = (0, 0);
+            let mut begin = p;
+            let mut end = p;
+            let mut i = p;
+            while i <= q && length < max_words {
+                let (words, interesting) = self.counts(i);
+                length += words;
+                items += interesting;
+                end = i;
+                i += 1;
+            }
+
+            if length < max_words {
+                // room left, go on until a good end after MinWords
+                i -= 1;
+                while i < self.len() && length < max_words {
+                    if i > q {
+                        let (words, interesting) = self.counts(i);
+                        length += words;
+                        items += interesting;
+                    }
+                    end = i;
+                    if !self.bad_endpoint(i) && length >= min_words {
+                        break;
+                    }
+                    i += 1;
+                }
+
+                // still too short at the end of the document, so extend to the left
+                if length < min_words {
+                    i = p - 1;
+                    while i >= 0 {
+                        let (words, interesting) = self.counts(i);
+                        length += words;
+                        items += interesting;
+                        if length >= max_words || !self.bad_endpoint(i) && length >= min_words {
+                            break;
+                        }
+                        i -= 1;
+                    }
+                    begin = i.max(0);
+                }
+            } else {
+                // full, but drop bad words at the end
+                i = end;
+                while length > min_words && self.bad_endpoint(i) {
+                    let (words, interesting) = self.counts(i);
+                    length -= words;
+                    items -= interesting;
+                    end = i - 1;
+                    i -= 1;
+                }
+            }
+
+            // prefer headlines containing the whole cover, then more query words, then
+            // a good last word
+            let includes_cover = begin <= p && end >= q;
+            let better = match best {
+                None => true,
+                Some((_, best_end, best_items, best_cover)) => {
+                    (includes_cover && !best_cover)
+                        || includes_cover == best_cover
+                            && (items > best_items
+                                || items == best_items
+                                    && !self.bad_endpoint(end)
+                                    && self.bad_endpoint(best_end))
+                }
+            };
+            if better {
+                best = Some((begin, end, items, includes_cover));
+            }
+        }
+
+        match best {
+            Some((begin, end, _, _)) => self.mark(begin, end),
+            None => self.mark_start(),
+        }
+    }
+
+    // Narrows down the rest of a cover to the next fragment of at most MaxWords words
+    // that starts and ends with a query word (get_next_fragment)
+    fn next_fragment(&self, start: &mut isize, end: &mut isize) -> (usize, usize) {
+        let max_words = self.options.max_words;
+
+        let mut i = *start;
+        while i <= *end {
+            *start = i;
+            if self.interesting(i) {
+                break;
+            }
+            i += 1;
+        }
+
+        let (mut length, mut items) = (0, 0);
+        i = *start;
+        while i <= *end && length < max_words {
+            let (words, interesting) = self.counts(i);
+            length += words;
+            items += interesting;
+            i += 1;
+        }
+
+        // cut off, so go back to a query word
+        if *end > i {
+            *end = i;
+            while i >= *start {
+                *end = i;
+                if self.interesting(i) {
+                    break;
+                }
+                length = length.saturating_sub(self.counts(i).0);
+                i -= 1;
+            }
+        }
+
+        (length, items)
+    }
+
+    // The best MaxFragments fragments, each stretched to MaxWords words where the
+    // others leave room (mark_hl_fragments)
+    fn mark_fragments(&mut self, root: Option<&TsQueryNode>) {
+        let max_words = self.options.max_words;
+        let max_cover = self.max_cover();
+
+        // split every cover into fragments
+        let mut fragments: Vec<Fragment> = Vec::new();
+        let mut next = 0;
+        while let Some((p, q)) = root.and_then(|root| self.cover(root, max_cover, &mut next)) {
+            let mut start = p;
+            while start <= q {
+                let mut end = q;
+                let (length, items) = self.next_fragment(&mut start, &mut end);
+                fragments.push(Fragment {
+                    start,
+                    end,
+                    length,
+                    items,
+                    chosen: false,
+                    excluded: false,
+                });
+                start = end + 1;
+            }
+        }
+
+        let mut marked = 0;
+        for _ in 0..self.options.max_fragments {
+            // the most query words, then the fewest words
+            let Some(chosen) = fragments
+                .iter()
+                .enumerate()
+                .filter(|(_, fragment)| !fragment.chosen && !fragment.excluded)
+                .min_by_key(|(_, fragment)| (std::cmp::Reverse(fragment.items), fragment.length))
+                .map(|(index, _)| index)
+            else {
+                break;
+            };
+
+            let Fragment {
+                mut start,
+                mut end,
+                mut length,
+                ..
+            } = fragments[chosen];
+
+            if length < max_words {
+                // split what is left over both sides, stopping at fragments already shown
+                let max_stretch = (max_words - length) / 2;
+                let mut stretch = 0;
+                let mut marker = start;
+                let mut i = start - 1;
+                while i >= 0 && stretch < max_stretch && !self.word(i).in_headline {
+                    if !self.non_word(i) {
+                        length += 1;
+                        stretch += 1;
+                    }
+                    marker = i;
+                    i -= 1;
+                }
+                i = marker;
+                while i < start && self.bad_endpoint(i) {
+                    length -= self.counts(i).0;
+                    i += 1;
+                }
+                start = i;
+
+                marker = end;
+                i = end + 1;
+                while i < self.len() && length < max_words && !self.word(i).in_headline {
+                    length += self.counts(i).0;
+                    marker = i;
+                    i += 1;
+                }
+                i = marker;
+                while i > end && self.bad_endpoint(i) {
+                    length -= self.counts(i).0;
+                    i -= 1;
+                }
+                end = i;
+            }
+
+            fragments[chosen] = Fragment {
+                start,
+                end,
+                length,
+                chosen: true,
+                ..fragments[chosen]
+            };
+            self.mark(start, end);
+            marked += 1;
+
+            // fragments overlapping this one are out
+            for (index, fragment) in fragments.iter_mut().enumerate() {
+                let overlaps = (start..=end).contains(&fragment.start)
+                    || (start..=end).contains(&fragment.end)
+                    || fragment.start < start && fragment.end > end;
+                if index != chosen && overlaps {
+                    fragment.excluded = true;
+                }
+            }
+        }
+
+        if marked == 0 {
+            self.mark_start();
+        }
+    }
+
+    // The words of the headline with the query words highlighted (generateHeadline in
+    // ts_parse.c)
+    fn generate(&self) -> String {
+        let options = self.options;
+        let mut headline = String::new();
+        let mut in_fragment = false;
+        let mut fragments = 0;
+
+        for word in self.words.iter().filter(|word| !word.repeated) {
+            if !word.in_headline {
+                in_fragment = false;
+                continue;
+            }
+
+            if !in_fragment {
+                in_fragment = true;
+                fragments += 1;
+                if fragments > 1 {
+                    headline.push_str(&options.fragment_delimiter);
+                }
+            }
+
+            if word.replace {
+                headline.push(' ');
+            } else if !word.skip {
+                if word.selected {
+                    headline.push_str(&options.start_sel);
+                }
+                headline.push_str(word.text);
+                if word.selected {
+                    headline.push_str(&options.stop_sel);
+                }
+            }
+        }
+
+        headline
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HeadlineOptions;
+    use crate::pg_tsquery::PgTsQuery;
+    use crate::pg_tsvector_config::TextSearchConfig;
+    use std::str::FromStr;
+
+    const DOCUMENT: &str = "Search terms may occur
+many times in a document,
+requiring ranking of the search matches to decide which
+occurrences to display in the result.";
+
+    // (document, query, options, ts_headline('simple', ...)) captured from PostgreSQL 15
+    const CASES: &[(&str, &str, &str, &str)] = &[
+        (
+            DOCUMENT,
+            "search <-> matches",
+            "",
+            "<b>Search</b> terms may occur
+many times in a document,
+requiring ranking of the <b>search</b> <b>matches</b>",
+        ),
+        (
+            DOCUMENT,
+            "search & terms",
+            "MaxFragments=10, MaxWords=7, MinWords=3, StartSel=<<, StopSel=>>",
+            "<<Search>> <<terms>> may occur
+many times ... ranking of the <<search>> matches to decide",
+        ),
+        (
+            "the ran the a cat cat a a a a on cat rat cat bad the bad on a bad rat",
+            "the & (on & on)",
+            "MaxWords=5, MinWords=2",
+            "<b>the</b> bad <b>on</b> a",
+        ),
+        (
+            "sat rat sat a big the sat the bad a a wolf fat",
+            "fat & !fat",
+            "MaxFragments=2, MaxWords=5, MinWords=1",
+            "wolf <b>fat</b>",
+        ),
+    ];
+
+    #[test]
+    fn headline_like_the_server() {
+        let config = TextSearchConfig::simple();
+
+        for (document, query, options, expected) in CASES {
+            let query = PgTsQuery::from_str(query).unwrap();
+            let options = HeadlineOptions::from_str(options).unwrap();
+
+            let headline = config.headline(document, &query, &options).unwrap();
+            assert_eq!(headline, *expected, "{query} with {options:?}");
+
+            let vector = config.to_tsvector(document);
+            let headline = vector.headline(document, &query, &options).unwrap();
+            assert_eq!(
+                headline, *expected,
+                "{query} with {options:?} through the vector"
+            );
+        }
+    }
+
+    #[test]
+    fn headline_skips_2047_byte_words() {
+        let config = TextSearchConfig::simple();
+        let query = PgTsQuery::from_str("a & b").unwrap();
+        let options = HeadlineOptions::default();
+
+        let document = format!("a {} b", "x".repeat(2047));
+        let headline = config.headline(&document, &query, &options).unwrap();
+        assert_eq!(headline, "<b>a</b>  <b>b</b>");
+
+        let vector = config.to_tsvector(&document);
+        let headline = vector.headline(&document, &query, &options).unwrap();
+        assert_eq!(headline, "<b>a</b>  <b>b</b>");
+    }
+}
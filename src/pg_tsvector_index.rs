@@ -0,0 +1,317 @@
+use crate::pg_tsquery::PgTsQuery;
+use crate::pg_tsquery_tree::TsQueryNode;
+use crate::pg_tsvector::PgTsVector;
+use crate::pg_tsvector_rank::RankNormalization;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Bound;
+
+/// An in-memory inverted index of documents, working like a GIN index on a tsvector
+/// column.
+///
+/// Lexemes are looked up to find the documents that might match, which are then checked
+/// with [`PgTsQuery::matches`] like the server rechecks heap tuples. Results are therefore
+/// the same as `WHERE document @@ query`, phrases, prefixes and weights included.
+///
+/// ```
+/// use postgres_types_extra::pg_tsquery::PgTsQuery;
+/// use postgres_types_extra::pg_tsvector::PgTsVector;
+/// use postgres_types_extra::pg_tsvector_index::TsIndex;
+/// use std::str::FromStr;
+///
+/// let mut index = TsIndex::new();
+/// index.insert(1, PgTsVector::from_str("'fat':2 'cat':3")?);
+/// index.insert(2, PgTsVector::from_str("'fat':1 'rat':2")?);
+/// let query = PgTsQuery::from_str("fat & !rat")?;
+/// assert_eq!(index.matching(&query), vec![&1]);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct TsIndex<K> {
+    documents: BTreeMap<K, PgTsVector>,
+    postings: BTreeMap<String, BTreeSet<K>>,
+}
+
+impl<K> Default for TsIndex<K> {
+    fn default() -> Self {
+        TsIndex {
+            documents: BTreeMap::new(),
+            postings: BTreeMap::new(),
+        }
+    }
+}
+
+// Documents that may match part of a query, All when the index can't narrow them down
+enum Candidates<'a, K> {
+    All,
+    Some(BTreeSet<&'a K>),
+}
+
+impl<K: Ord + Clone> TsIndex<K> {
+    pub fn new() -> Self {
+        TsIndex::default()
+    }
+
+    /// Adds a document, returning the one it replaces
+    pub fn insert(&mut self, id: K, document: PgTsVector) -> Option<PgTsVector> {
+        let previous = self.remove(&id);
+
+        for lexeme in &document.words {
+            self.postings
+                .entry(lexeme.word.clone())
+                .or_default()
+                .insert(id.clone());
+        }
+        self.documents.insert(id, document);
+
+        previous
+    }
+
+    pub fn remove(&mut self, id: &K) -> Option<PgTsVector> {
+        let document = self.documents.remove(id)?;
+
+        for lexeme in &document.words {
+            if let Some(ids) = self.postings.get_mut(&lexeme.word) {
+                ids.remove(id);
+                if ids.is_empty() {
+                    self.postings.remove(&lexeme.word);
+                }
+            }
+        }
+
+        Some(document)
+    }
+
+    pub fn get(&self, id: &K) -> Option<&PgTsVector> {
+        self.documents.get(id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+
+    /// Ids of the documents matching the query, in order of the ids
+    pub fn matching(&self, query: &PgTsQuery) -> Vec<&K> {
+        // a malformed or empty query matches nothing, same as @@
+        let Ok(Some(root)) = query.to_tree() else {
+            return vec![];
+        };
+
+        match self.candidates(&root) {
+            Candidates::All => self
+                .documents
+                .iter()
+                .filter(|(_, document)| query.matches(document))
+                .map(|(id, _)| id)
+                .collect(),
+            Candidates::Some(ids) => ids
+                .into_iter()
+                .filter(|id| self.documents.get(*id).is_some_and(|d| query.matches(d)))
+                .collect(),
+        }
+    }
+
+    /// The matching documents with their `ts_rank`, best first and by id among equal
+    /// ranks
+    pub fn search(&self, query: &PgTsQuery, normalization: RankNormalization) -> Vec<(&K, f32)> {
+        self.search_by(query, |document| document.rank(query, normalization))
+    }
+
+    /// Same as [`TsIndex::search`] with another rank, e.g. [`PgTsVector::rank_cd`]
+    pub fn search_by<F>(&self, query: &PgTsQuery, rank: F) -> Vec<(&K, f32)>
+    where
+        F: Fn(&PgTsVector) -> f32,
+    {
+        let mut ranked: Vec<(&K, f32)> = self
+            .matching(query)
+            .into_iter()
+            .map(|id| (id, rank(&self.documents[id])))
+            .collect();
+
+        // stable, so equal ranks stay in id order
+        ranked.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        ranked
+    }
+
+    // Like the server's consistent function: AND and phrases need all their operands,
+    // OR either, and NOT has to look at every document
+    fn candidates(&self, node: &TsQueryNode) -> Candidates<'_, K> {
+        match node {
+            TsQueryNode::Term(value) if value.prefix != 0 => Candidates::Some(
+                self.postings
+                    .range::<str, _>((Bound::Included(value.text.as_str()), Bound::Unbounded))
+                    .take_while(|(word, _)| word.starts_with(&value.text))
+                    .flat_map(|(_, ids)| ids)
+                    .collect(),
+            ),
+            TsQueryNode::Term(value) => Candidates::Some(
+                self.postings
+                    .get(&value.text)
+                    .map(|ids| ids.iter().collect())
+                    .unwrap_or_default(),
+            ),
+            TsQueryNode::Not(_) => Candidates::All,
+            TsQueryNode::And(left, right) | TsQueryNode::Phrase { left, right, .. } => {
+                match (self.candidates(left), self.candidates(right)) {
+                    (Candidates::All, other) | (other, Candidates::All) => other,
+                    (Candidates::Some(left), Candidates::Some(right)) => {
+                        Candidates::Some(left.intersection(&right).copied().collect())
+                    }
+                }
+            }
+            TsQueryNode::Or(left, right) => match (self.candidates(left), self.candidates(right)) {
+                (Candidates::Some(mut left), Candidates::Some(right)) => {
+                    left.extend(right);
+                    Candidates::Some(left)
+                }
+                _ => Candidates::All,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Candidates, TsIndex};
+    use crate::pg_tsquery::PgTsQuery;
+    use crate::pg_tsvector::PgTsVector;
+    use crate::pg_tsvector_rank::RankNormalization;
+    use std::str::FromStr;
+
+    const DOCUMENTS: &[(u32, &str)] = &[
+        (1, "fat:1 cat:2"),
+        (2, "fat:1A rat:2"),
+        (3, "fatty:1 cats:3"),
+        (4, "rat:1 cat:2 fat:3"),
+        (5, "dog:1"),
+        (6, "fat cat"),
+    ];
+
+    // (query, ids WHERE v @@ query with a GIN index on v) captured from PostgreSQL 15
+    const MATCHING: &[(&str, &[u32])] = &[
+        ("fat:*", &[1, 2, 3, 4, 6]),
+        ("ca:*", &[1, 3, 4, 6]),
+        ("fa:* & ca:*", &[1, 3, 4, 6]),
+        ("f:* <-> c:*", &[1]),
+        ("!fat", &[3, 5]),
+        ("!fat & !dog", &[3]),
+        ("!(fat & cat)", &[2, 3, 5]),
+        ("fat & !rat", &[1, 6]),
+        ("!zebra", &[1, 2, 3, 4, 5, 6]),
+        ("fat <-> cat", &[1]),
+        ("cat <-> fat", &[4]),
+        ("fat <2> cat", &[]),
+        ("fat:A", &[2, 6]),
+        ("fat:B", &[6]),
+        ("fat:A | dog", &[2, 5, 6]),
+        ("zebra", &[]),
+    ];
+
+    fn index() -> TsIndex<u32> {
+        let mut index = TsIndex::new();
+        for (id, document) in DOCUMENTS {
+            index.insert(*id, PgTsVector::from_str(document).unwrap());
+        }
+        index
+    }
+
+    fn query(s: &str) -> PgTsQuery {
+        PgTsQuery::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn matches_like_the_server() {
+        let index = index();
+        for (input, expected) in MATCHING {
+            let ids: Vec<u32> = index.matching(&query(input)).into_iter().copied().collect();
+            assert_eq!(ids, *expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn narrows_down_candidates() {
+        let index = index();
+        let candidates = |input: &str| {
+            let root = query(input).to_tree().unwrap().unwrap();
+            match index.candidates(&root) {
+                Candidates::All => None,
+                Candidates::Some(ids) => Some(ids.into_iter().copied().collect::<Vec<_>>()),
+            }
+        };
+
+        // prefixes take every posting list they start
+        assert_eq!(candidates("fat:*"), Some(vec![1, 2, 3, 4, 6]));
+        assert_eq!(candidates("ca:*"), Some(vec![1, 3, 4, 6]));
+        // weights and distances are only checked on the documents
+        assert_eq!(candidates("fat:B"), Some(vec![1, 2, 4, 6]));
+        assert_eq!(candidates("cat <-> fat"), Some(vec![1, 4, 6]));
+        assert_eq!(candidates("fat & !rat"), Some(vec![1, 2, 4, 6]));
+        assert_eq!(candidates("fat | dog"), Some(vec![1, 2, 4, 5, 6]));
+        // NOT alone has to look at every document
+        assert_eq!(candidates("!fat"), None);
+        assert_eq!(candidates("!fat <-> !cat"), None);
+        assert_eq!(candidates("fat | !dog"), None);
+    }
+
+    #[test]
+    fn insert_replaces_documents() {
+        let mut index = index();
+        let previous = index.insert(1, PgTsVector::from_str("dog:1").unwrap());
+        assert_eq!(previous.unwrap().to_string(), "'cat':2 'fat':1");
+        assert_eq!(index.len(), 6);
+
+        let ids = |index: &TsIndex<u32>, input: &str| -> Vec<u32> {
+            index.matching(&query(input)).into_iter().copied().collect()
+        };
+        assert_eq!(ids(&index, "fat <-> cat"), Vec::<u32>::new());
+        assert_eq!(ids(&index, "dog"), [1, 5]);
+
+        // the postings of removed documents go away
+        assert_eq!(index.remove(&5).unwrap().to_string(), "'dog':1");
+        assert_eq!(index.remove(&1).unwrap().to_string(), "'dog':1");
+        assert!(index.remove(&1).is_none());
+        assert!(!index.postings.contains_key("dog"));
+        assert_eq!(ids(&index, "dog"), Vec::<u32>::new());
+    }
+
+    // (id, ts_rank(v, query)) ORDER BY 2 DESC, 1 captured from PostgreSQL 15
+    const SEARCH: &[(&str, &[(u32, f32)])] = &[
+        (
+            "fat | cat",
+            &[
+                (2, 0.30396354),
+                (1, 0.06079271),
+                (4, 0.06079271),
+                (6, 0.06079271),
+            ],
+        ),
+        (
+            "fat:* | cat",
+            &[
+                (2, 0.30396354),
+                (1, 0.06079271),
+                (4, 0.06079271),
+                (6, 0.06079271),
+                (3, 0.030396355),
+            ],
+        ),
+    ];
+
+    #[test]
+    fn search_orders_by_rank() {
+        let index = index();
+        for (input, expected) in SEARCH {
+            let results = index.search(&query(input), RankNormalization::default());
+            let ids: Vec<u32> = results.iter().map(|(id, _)| **id).collect();
+            let expected_ids: Vec<u32> = expected.iter().map(|(id, _)| *id).collect();
+            assert_eq!(ids, expected_ids, "{input}");
+
+            for ((_, rank), (_, expected)) in results.iter().zip(*expected) {
+                assert!((rank - expected).abs() < 1e-6, "{input}: {rank} {expected}");
+            }
+        }
+    }
+}
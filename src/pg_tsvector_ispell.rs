@@ -0,0 +1,1229 @@
+use crate::pg_tsvector_config::{DictLexeme, Dictionary, Stopwords, lowercase};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+// Flags of affixes and words (FF_* in spell.h)
+const COMPOUND_ONLY: u8 = 0x01;
+const COMPOUND_BEGIN: u8 = 0x02;
+const COMPOUND_MIDDLE: u8 = 0x04;
+const COMPOUND_LAST: u8 = 0x08;
+const COMPOUND_FLAG: u8 = COMPOUND_BEGIN | COMPOUND_MIDDLE | COMPOUND_LAST;
+const COMPOUND_MASK: u8 = 0x0f;
+const COMPOUND_PERMIT: u8 = 0x10;
+const COMPOUND_FORBID: u8 = 0x20;
+const CROSS_PRODUCT: u8 = 0x40;
+
+// Longer words aren't normalized (MAXNORMLEN in spell.h)
+const MAX_NORM_LEN: usize = 256;
+
+// The hunspell directives about compound words, matched at the start of a line
+const COMPOUND_DIRECTIVES: [(&str, u8); 8] = [
+    ("COMPOUNDFLAG", COMPOUND_FLAG),
+    ("COMPOUNDBEGIN", COMPOUND_BEGIN),
+    ("COMPOUNDLAST", COMPOUND_LAST),
+    ("COMPOUNDEND", COMPOUND_LAST),
+    ("COMPOUNDMIDDLE", COMPOUND_MIDDLE),
+    ("ONLYINCOMPOUND", COMPOUND_ONLY),
+    ("COMPOUNDPERMITFLAG", COMPOUND_PERMIT),
+    ("COMPOUNDFORBIDFLAG", COMPOUND_FORBID),
+];
+
+// How flags are written in the dictionary and affix files (FLAG in hunspell files)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FlagMode {
+    Char,
+    Long,
+    Num,
+}
+
+impl FlagMode {
+    fn split(self, flags: &str) -> Vec<String> {
+        match self {
+            FlagMode::Char => flags.chars().map(String::from).collect(),
+            FlagMode::Long => flags
+                .chars()
+                .collect::<Vec<_>>()
+                .chunks(2)
+                .map(|chunk| chunk.iter().collect())
+                .collect(),
+            FlagMode::Num => flags
+                .split(',')
+                .map(|flag| flag.trim().to_string())
+                .filter(|flag| !flag.is_empty())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Atom {
+    Any,
+    Char(char),
+    Set {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+impl Atom {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Atom::Any => true,
+            Atom::Char(expected) => c == *expected,
+            Atom::Set { negated, ranges } => {
+                ranges.iter().any(|(low, high)| (*low..=*high).contains(&c)) != *negated
+            }
+        }
+    }
+}
+
+// An atom repeated `min` up to `max` times
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Piece {
+    atom: Atom,
+    min: usize,
+    max: Option<usize>,
+}
+
+// The condition of an affix is a regular expression, anchored at the end of the base
+// word for suffixes and at its start for prefixes. Only literals, `.`, bracket
+// expressions and quantifiers are supported, which is what affix files use.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Condition(Vec<Piece>);
+
+impl Condition {
+    // `[^aeiou]y{1}`, `.` and an empty condition match any word (issimple in spell.c)
+    fn parse(s: &str) -> Result<Condition, Box<dyn Error>> {
+        let unsupported = || format!("Invalid affix: unsupported condition {s}");
+        if s == "." {
+            return Ok(Condition::default());
+        }
+
+        let mut pieces = Vec::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            let atom = match c {
+                '.' => Atom::Any,
+                '[' => {
+                    let negated = chars.next_if_eq(&'^').is_some();
+                    let mut ranges = Vec::new();
+                    loop {
+                        let low = match chars.next() {
+                            None => {
+                                return Err(
+                                    format!("Invalid affix: unterminated condition {s}").into()
+                                );
+                            }
+                            // a leading `]` is a literal
+                            Some(']') if !ranges.is_empty() => break,
+                            Some(c) => c,
+                        };
+                        let high = match chars.next_if_eq(&'-') {
+                            Some(_) if chars.peek().is_some_and(|c| *c != ']') => {
+                                chars.next().unwrap_or(low)
+                            }
+                            Some(_) => {
+                                ranges.push(('-', '-'));
+                                low
+                            }
+                            None => low,
+                        };
+                        ranges.push((low, high));
+                    }
+                    Atom::Set { negated, ranges }
+                }
+                '(' | ')' | '|' | '\\' | '^' | '$' | '*' | '+' | '?' | '{' | '}' => {
+                    return Err(unsupported().into());
+                }
+                c => Atom::Char(c),
+            };
+
+            let (min, max) = match chars.next_if(|c| matches!(c, '*' | '+' | '?' | '{')) {
+                Some('*') => (0, None),
+                Some('+') => (1, None),
+                Some('?') => (0, Some(1)),
+                Some(_) => {
+                    let mut bounds = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => bounds.push(c),
+                            None => return Err(unsupported().into()),
+                        }
+                    }
+                    let count = |n: &str| n.parse::<usize>().map_err(|_| unsupported());
+                    match bounds.split_once(',') {
+                        None => (count(&bounds)?, Some(count(&bounds)?)),
+                        Some((min, "")) => (count(min)?, None),
+                        Some((min, max)) => (count(min)?, Some(count(max)?)),
+                    }
+                }
+                None => (1, Some(1)),
+            };
+            pieces.push(Piece { atom, min, max });
+        }
+
+        Ok(Condition(pieces))
+    }
+
+    fn matches(&self, word: &str, kind: AffixKind) -> bool {
+        let chars: Vec<char> = word.chars().collect();
+        match kind {
+            AffixKind::Prefix => matches_from(&self.0, &chars, false),
+            AffixKind::Suffix => {
+                (0..=chars.len()).any(|start| matches_from(&self.0, &chars[start..], true))
+            }
+        }
+    }
+}
+
+// Backtracking match of the pieces at the start of `chars`, up to its end if `to_end`
+fn matches_from(pieces: &[Piece], chars: &[char], to_end: bool) -> bool {
+    let Some((piece, rest)) = pieces.split_first() else {
+        return !to_end || chars.is_empty();
+    };
+
+    let max = piece.max.unwrap_or(usize::MAX);
+    let count = chars
+        .iter()
+        .take(max)
+        .take_while(|c| piece.atom.matches(**c))
+        .count();
+    (piece.min..=count)
+        .rev()
+        .any(|n| matches_from(rest, &chars[n..], to_end))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum AffixKind {
+    Prefix,
+    Suffix,
+}
+
+// One affix rule: `add` replaces `strip` at the start or end of the base word when the
+// condition matches there
+#[derive(Debug, Clone)]
+struct Affix {
+    kind: AffixKind,
+    flag: String,
+    // CROSS_PRODUCT and the COMPOUND_* flags
+    flags: u8,
+    strip: String,
+    add: String,
+    condition: Condition,
+}
+
+impl Affix {
+    // The base word this affix could have produced `word` from, with `compound` telling
+    // which part of a compound word it is (CheckAffix in spell.c). `unchanged` is the
+    // length left over by a suffix removed before this prefix.
+    fn base(&self, word: &str, compound: u8, unchanged: Option<usize>) -> Option<String> {
+        let forbidden = self.flags & COMPOUND_FORBID != 0;
+        let allowed = if compound == 0 {
+            self.flags & COMPOUND_ONLY == 0
+        } else if compound & COMPOUND_BEGIN != 0 {
+            !forbidden && (self.flags & COMPOUND_BEGIN != 0 || self.kind == AffixKind::Prefix)
+        } else if compound & COMPOUND_MIDDLE != 0 {
+            !forbidden && self.flags & COMPOUND_MIDDLE != 0
+        } else {
+            !forbidden && (self.flags & COMPOUND_LAST != 0 || self.kind == AffixKind::Suffix)
+        };
+        if !allowed {
+            return None;
+        }
+
+        let base = match self.kind {
+            AffixKind::Suffix => {
+                format!("{}{}", word.strip_suffix(self.add.as_str())?, self.strip)
+            }
+            AffixKind::Prefix => {
+                // nothing of the word would be left between the prefix and the suffix
+                if unchanged.is_some_and(|len| len + self.strip.len() <= self.add.len()) {
+                    return None;
+                }
+                format!("{}{}", self.strip, word.strip_prefix(self.add.as_str())?)
+            }
+        };
+
+        self.condition.matches(&base, self.kind).then_some(base)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Word {
+    flags: HashSet<String>,
+    // the COMPOUND_* flags telling where the word may appear in a compound word
+    compound: u8,
+}
+
+/// The `ispell` dictionary template (dict_ispell.c), reducing words to the forms listed
+/// in a dictionary with the help of affix rules.
+///
+/// Both the ispell and the hunspell (`SFX`/`PFX`) affix formats are read, including the
+/// `FLAG long`, `FLAG num` and `AF` directives. Every normal form found is returned as
+/// its own variant. When the affix file allows compound words (`compoundwords
+/// controlled` or `COMPOUNDFLAG` and the like) each way of splitting a word into
+/// dictionary words is another variant, with the parts sharing its number.
+#[derive(Debug, Clone)]
+pub struct IspellDictionary {
+    // sorted, the compound splitting walks it like the server walks its word trie
+    words: Vec<(String, Word)>,
+    prefixes: Vec<Affix>,
+    suffixes: Vec<Affix>,
+    // affixes that may join the parts of a compound word, like the `s` of `ballsklubber`
+    compound_affixes: Vec<(AffixKind, String)>,
+    compounds: bool,
+    pub stopwords: Stopwords,
+}
+
+impl IspellDictionary {
+    /// Reads the contents of a `.dict` and an `.affix` file
+    pub fn parse(dictionary: &str, affixes: &str) -> Result<IspellDictionary, Box<dyn Error>> {
+        let file = if is_hunspell(affixes) {
+            parse_hunspell_affixes(affixes)?
+        } else {
+            parse_ispell_affixes(affixes)?
+        };
+
+        let mut words: HashMap<String, Word> = HashMap::new();
+        for line in dictionary.lines() {
+            let (word, flags) = line.split_once('/').unwrap_or((line, ""));
+            let word = word.split(char::is_whitespace).next().unwrap_or_default();
+            if word.is_empty() {
+                continue;
+            }
+            let flags = &flags[..flags
+                .find(|c: char| !c.is_ascii_graphic())
+                .unwrap_or(flags.len())];
+            let flags: HashSet<String> = file.flag_set(flags)?.into_iter().collect();
+            let compound = file.compound(&flags);
+
+            match words.entry(lowercase(word)) {
+                Entry::Occupied(mut entry) => {
+                    let word = entry.get_mut();
+                    if word.flags != flags {
+                        // only compound if every entry of the word says so
+                        let only = word.compound & compound & COMPOUND_ONLY;
+                        word.flags.extend(flags);
+                        word.compound = file.compound(&word.flags) & !COMPOUND_ONLY | only;
+                    }
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(Word { flags, compound });
+                }
+            }
+        }
+
+        let in_use = |flag: &str| match &file.aliases {
+            Some(aliases) => aliases.iter().any(|set| set.iter().any(|f| f == flag)),
+            None => words.values().any(|word| word.flags.contains(flag)),
+        };
+        let mut joining: Vec<&Affix> = file
+            .affixes
+            .iter()
+            .filter(|affix| {
+                affix.flags & COMPOUND_FLAG != 0 && !affix.add.is_empty() && in_use(&affix.flag)
+            })
+            .collect();
+        joining.sort_by(|a, b| {
+            a.kind.cmp(&b.kind).then_with(|| match a.kind {
+                AffixKind::Prefix => a.add.cmp(&b.add),
+                AffixKind::Suffix => a.add.bytes().rev().cmp(b.add.bytes().rev()),
+            })
+        });
+        // only the shortest of the affixes ending the same way are kept
+        let mut compound_affixes: Vec<(AffixKind, String)> = Vec::new();
+        for affix in joining {
+            if compound_affixes
+                .last()
+                .is_none_or(|(kind, add)| *kind != affix.kind || !affix.add.ends_with(add.as_str()))
+            {
+                compound_affixes.push((affix.kind, affix.add.clone()));
+            }
+        }
+
+        let mut words: Vec<(String, Word)> = words.into_iter().collect();
+        words.sort_by(|a, b| a.0.cmp(&b.0));
+
+        // shorter affixes are tried first, like the server's affix tree does
+        let mut affixes = file.affixes;
+        affixes.sort_by_key(|affix| affix.add.len());
+        let (prefixes, suffixes) = affixes
+            .into_iter()
+            .partition(|affix| affix.kind == AffixKind::Prefix);
+
+        Ok(IspellDictionary {
+            words,
+            prefixes,
+            suffixes,
+            compound_affixes,
+            compounds: !file.compound_flags.is_empty(),
+            stopwords: Stopwords::default(),
+        })
+    }
+
+    /// [`IspellDictionary::parse`] on the contents of the files
+    pub fn load(
+        dictionary: impl AsRef<Path>,
+        affixes: impl AsRef<Path>,
+    ) -> Result<IspellDictionary, Box<dyn Error>> {
+        IspellDictionary::parse(
+            &fs::read_to_string(dictionary)?,
+            &fs::read_to_string(affixes)?,
+        )
+    }
+
+    /// Same as the `StopWords` option
+    pub fn with_stopwords(mut self, stopwords: Stopwords) -> Self {
+        self.stopwords = stopwords;
+        self
+    }
+
+    fn word(&self, word: &str) -> Option<&Word> {
+        self.words
+            .binary_search_by(|(w, _)| w.as_str().cmp(word))
+            .ok()
+            .map(|i| &self.words[i].1)
+    }
+
+    // Whether some word starts with `prefix`, and is longer than it if `longer`
+    fn continues(&self, prefix: &[u8], longer: bool) -> bool {
+        let i = self.words.partition_point(|(word, _)| {
+            word.as_bytes() < prefix || (longer && word.as_bytes() == prefix)
+        });
+        self.words
+            .get(i)
+            .is_some_and(|(word, _)| word.as_bytes().starts_with(prefix))
+    }
+
+    // FindWord in spell.c
+    fn has_word(&self, word: &str, flag: Option<&str>, compound: u8) -> bool {
+        let Some(word) = self.word(word) else {
+            return false;
+        };
+        let compound = compound & COMPOUND_MASK;
+        let allowed = if compound == 0 {
+            word.compound & COMPOUND_ONLY == 0
+        } else {
+            word.compound & compound != 0
+        };
+        allowed && flag.is_none_or(|flag| word.flags.contains(flag))
+    }
+
+    /// All normal forms of a lowercase word (NormalizeSubWord in spell.c)
+    pub fn normalize(&self, word: &str) -> Vec<String> {
+        self.normalize_part(word, 0)
+    }
+
+    fn normalize_part(&self, word: &str, compound: u8) -> Vec<String> {
+        let mut forms: Vec<String> = Vec::new();
+        if word.len() > MAX_NORM_LEN {
+            return forms;
+        }
+        // like the server only a repeat of the previous form is dropped
+        let mut add = |form: String| {
+            if forms.last() != Some(&form) {
+                forms.push(form);
+            }
+        };
+
+        if self.has_word(word, None, compound) {
+            add(word.to_string());
+        }
+
+        for prefix in &self.prefixes {
+            if let Some(base) = prefix.base(word, compound, None)
+                && self.has_word(&base, Some(&prefix.flag), compound)
+            {
+                add(base);
+            }
+        }
+
+        for suffix in &self.suffixes {
+            let Some(base) = suffix.base(word, compound, None) else {
+                continue;
+            };
+            if self.has_word(&base, Some(&suffix.flag), compound) {
+                add(base.clone());
+            }
+
+            let unchanged = word.len() - suffix.add.len();
+            for prefix in &self.prefixes {
+                let Some(root) = prefix.base(&base, compound, Some(unchanged)) else {
+                    continue;
+                };
+                // the server only asks for the prefix flag unless both allow cross products
+                let flag = (prefix.flags & suffix.flags & CROSS_PRODUCT == 0)
+                    .then_some(prefix.flag.as_str());
+                if self.has_word(&root, flag, compound) {
+                    add(root);
+                }
+            }
+        }
+
+        forms
+    }
+
+    // The ways to split `word` from `start` on into words allowed in compounds, each
+    // following `stems` (SplitToVariants in spell.c). The last part of a split isn't
+    // checked yet. With `resume` the search continues past the word ending at `min`
+    // for a longer one.
+    fn split(
+        &self,
+        word: &str,
+        stems: &[String],
+        mut start: usize,
+        min: Option<usize>,
+        resume: bool,
+    ) -> Vec<Vec<String>> {
+        let bytes = word.as_bytes();
+        let len = bytes.len();
+        let mut stems = stems.to_vec();
+        let mut more = Vec::new();
+        let mut probed = vec![false; len];
+        let mut level = if resume { min.unwrap_or(start) } else { start };
+        // whether some word starts with bytes[start..level]
+        let mut node = resume || !self.words.is_empty();
+
+        while level < len {
+            // a compound affix joining the part before it to the rest
+            for (kind, affix) in self.compound_affixes.iter().filter(|_| level > start) {
+                let rest = &bytes[level..];
+                if rest.len() <= affix.len() {
+                    continue;
+                }
+                let offset = if node {
+                    rest.starts_with(affix.as_bytes()).then_some(0)
+                } else {
+                    rest.windows(affix.len())
+                        .position(|window| window == affix.as_bytes())
+                };
+                let Some(offset) = offset else {
+                    continue;
+                };
+
+                let part_len = level - start
+                    + match kind {
+                        AffixKind::Suffix => offset + affix.len(),
+                        AffixKind::Prefix => 0,
+                    };
+                if probed[start + part_len - 1]
+                    || min.is_some_and(|min| level + part_len - 1 <= min)
+                    || part_len >= MAX_NORM_LEN
+                {
+                    continue;
+                }
+                let Some(part) = word.get(start..start + part_len) else {
+                    continue;
+                };
+
+                let compound = if level == len - 1 {
+                    COMPOUND_LAST
+                } else {
+                    COMPOUND_MIDDLE
+                };
+                let forms = self.normalize_part(part, compound);
+                if !forms.is_empty() {
+                    probed[start + part_len - 1] = true;
+                    let stems: Vec<String> = stems.iter().cloned().chain(forms).collect();
+                    let next = start + part_len;
+                    more.extend(self.split(word, &stems, next, Some(next), false));
+                }
+            }
+
+            if !node {
+                break;
+            }
+
+            let prefix = &bytes[start..=level];
+            if self.continues(prefix, false) {
+                let compound = if start == 0 {
+                    COMPOUND_BEGIN
+                } else if level == len - 1 {
+                    COMPOUND_LAST
+                } else {
+                    COMPOUND_MIDDLE
+                };
+
+                if let Some(found) = word.get(start..=level).and_then(|part| self.word(part))
+                    && found.compound & compound != 0
+                    && !probed[level]
+                    && min.is_none_or(|min| level > min)
+                {
+                    if level == len - 1 {
+                        stems.push(word[start..].to_string());
+                        return [stems].into_iter().chain(more).collect();
+                    }
+
+                    // a longer word starting here is another way to split
+                    more.extend(self.split(word, &stems, start, Some(level), true));
+                    level += 1;
+                    stems.push(word[start..level].to_string());
+                    node = true;
+                    start = level;
+                    continue;
+                }
+                node = self.continues(prefix, true);
+            } else {
+                node = false;
+            }
+            level += 1;
+        }
+
+        stems.push(word[start..].to_string());
+        [stems].into_iter().chain(more).collect()
+    }
+
+    // Every normal form and every split into compound parts (NINormalizeWord in spell.c)
+    fn variants(&self, word: &str) -> Vec<Vec<String>> {
+        let mut variants: Vec<Vec<String>> = self
+            .normalize(word)
+            .into_iter()
+            .map(|form| vec![form])
+            .collect();
+
+        if self.compounds {
+            for stems in self.split(word, &[], 0, None, false) {
+                let Some((last, parts)) = stems.split_last() else {
+                    continue;
+                };
+                if parts.is_empty() {
+                    continue;
+                }
+                for form in self.normalize_part(last, COMPOUND_LAST) {
+                    variants.push(parts.iter().cloned().chain([form]).collect());
+                }
+            }
+        }
+
+        variants
+    }
+}
+
+impl Dictionary for IspellDictionary {
+    fn lexize(&self, token: &str) -> Option<Vec<DictLexeme>> {
+        let variants = self.variants(&lowercase(token));
+        if variants.is_empty() {
+            return None;
+        }
+
+        Some(
+            variants
+                .into_iter()
+                .zip(1..)
+                .flat_map(|(parts, variant)| parts.into_iter().map(move |part| (part, variant)))
+                .filter(|(part, _)| !self.stopwords.contains(part))
+                .map(|(part, variant)| DictLexeme {
+                    variant,
+                    ..DictLexeme::new(part)
+                })
+                .collect(),
+        )
+    }
+}
+
+struct AffixFile {
+    mode: FlagMode,
+    // flag sets of the AF directive, referred to by their number starting at 1
+    aliases: Option<Vec<Vec<String>>>,
+    // flags given by the compound directives and what they allow
+    compound_flags: HashMap<String, u8>,
+    affixes: Vec<Affix>,
+}
+
+impl AffixFile {
+    // The flags of a word, or the ones following an affix (getAffixFlagSet in spell.c)
+    fn flag_set(&self, flags: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let Some(aliases) = self.aliases.as_ref().filter(|_| !flags.is_empty()) else {
+            return Ok(self.mode.split(flags));
+        };
+
+        match flags.parse::<usize>() {
+            Ok(0) => Ok(Vec::new()),
+            Ok(alias) => Ok(aliases
+                .get(alias - 1)
+                .ok_or_else(|| format!("Invalid dictionary: unknown flag alias {alias}"))?
+                .clone()),
+            Err(_) => Err(format!("Invalid dictionary: invalid flag alias {flags}").into()),
+        }
+    }
+
+    // The COMPOUND_* flags a word with these flags gets (makeCompoundFlags in spell.c)
+    fn compound(&self, flags: &HashSet<String>) -> u8 {
+        let compound = flags
+            .iter()
+            .filter_map(|flag| self.compound_flags.get(flag))
+            .fold(0, |compound, value| compound | value)
+            & COMPOUND_MASK;
+        if compound & COMPOUND_ONLY != 0 && compound & COMPOUND_FLAG == 0 {
+            compound | COMPOUND_FLAG
+        } else {
+            compound
+        }
+    }
+
+    // NIAddAffix in spell.c
+    fn add(
+        &mut self,
+        kind: AffixKind,
+        flag: &str,
+        mut flags: u8,
+        condition: &str,
+        strip: String,
+        add: String,
+    ) -> Result<(), Box<dyn Error>> {
+        // affixes only allowed in compounds, or allowing them, are compound affixes
+        if flags & (COMPOUND_ONLY | COMPOUND_PERMIT) != 0 {
+            flags |= COMPOUND_FLAG;
+        }
+        self.affixes.push(Affix {
+            kind,
+            flag: flag.to_string(),
+            flags,
+            strip,
+            add,
+            condition: Condition::parse(condition)?,
+        });
+        Ok(())
+    }
+}
+
+// Where the ispell reader of the server gives up and reads the file as hunspell
+fn is_hunspell(contents: &str) -> bool {
+    contents.lines().any(|line| {
+        ["COMPOUNDFLAG", "COMPOUNDMIN", "PFX", "SFX"]
+            .iter()
+            .any(|keyword| line.starts_with(keyword))
+            || lowercase(line)
+                .strip_prefix("flag")
+                .is_some_and(|rest| parse_ispell_flag(rest).is_none())
+    })
+}
+
+// Hunspell format (NIImportOOAffixes in spell.c):
+//
+//   COMPOUNDFLAG Z
+//   SFX S Y 2
+//   SFX S y ies [^aeiou]y
+//   SFX S 0 s   [aeiou]y
+fn parse_hunspell_affixes(contents: &str) -> Result<AffixFile, Box<dyn Error>> {
+    let mut file = AffixFile {
+        mode: FlagMode::Char,
+        aliases: None,
+        compound_flags: HashMap::new(),
+        affixes: Vec::new(),
+    };
+    let lines = contents
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with(char::is_whitespace))
+        .filter(|line| !line.starts_with('#'));
+
+    // the directives apply to the whole file, wherever they are
+    for line in lines.clone() {
+        if let Some((keyword, value)) = COMPOUND_DIRECTIVES
+            .iter()
+            .find(|(keyword, _)| line.starts_with(keyword))
+        {
+            let flag = line[keyword.len()..]
+                .split_whitespace()
+                .next()
+                .ok_or_else(|| format!("Invalid affix: {line}"))?;
+            file.compound_flags.insert(flag.to_string(), *value);
+        } else if let Some(rest) = line.strip_prefix("FLAG") {
+            match rest.split_whitespace().next() {
+                Some(kind) if kind.starts_with("long") => file.mode = FlagMode::Long,
+                Some(kind) if kind.starts_with("num") => file.mode = FlagMode::Num,
+                Some(kind) if !kind.starts_with("default") && kind != "UTF-8" => {
+                    return Err(format!("Invalid affix: unrecognized FLAG {kind}").into());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut header_flags = 0;
+    for line in lines {
+        let fields: Vec<&str> = line.split_whitespace().take(5).collect();
+        let keyword = lowercase(fields[0]);
+
+        // the first AF line is the number of aliases
+        if keyword == "af" {
+            let value = fields.get(1).copied().unwrap_or_default();
+            match &mut file.aliases {
+                None => match value.parse::<usize>() {
+                    Ok(count) if count > 0 => file.aliases = Some(Vec::with_capacity(count)),
+                    _ => return Err(format!("Invalid affix: invalid alias count {line}").into()),
+                },
+                Some(aliases) => aliases.push(file.mode.split(value)),
+            }
+            continue;
+        }
+
+        let kind = match keyword.as_str() {
+            "pfx" => AffixKind::Prefix,
+            "sfx" => AffixKind::Suffix,
+            _ => continue,
+        };
+        let too_long = match file.mode {
+            FlagMode::Char => 1,
+            FlagMode::Long => 2,
+            FlagMode::Num => usize::MAX,
+        };
+        let flag = fields.get(1).copied().unwrap_or_default();
+        if fields.len() < 4 || flag.is_empty() || flag.len() > too_long {
+            continue;
+        }
+
+        match fields[2..] {
+            // header: cross product and number of rules
+            [cross, _] => {
+                header_flags = if cross.starts_with(['y', 'Y']) {
+                    CROSS_PRODUCT
+                } else {
+                    0
+                };
+            }
+            [strip, add, condition] => {
+                // flags after the added part can make the affix a compound affix
+                let (add, continuation) = add.split_once('/').unwrap_or((add, ""));
+                let continuation: HashSet<String> =
+                    file.flag_set(continuation)?.into_iter().collect();
+                let flags = header_flags | file.compound(&continuation);
+
+                let empty_if_zero = |s: &str| {
+                    if s.starts_with('0') {
+                        String::new()
+                    } else {
+                        lowercase(s)
+                    }
+                };
+                let (strip, add) = (empty_if_zero(strip), empty_if_zero(add));
+                file.add(kind, flag, flags, &lowercase(condition), strip, add)?;
+            }
+            _ => return Err(format!("Invalid affix: {line}").into()),
+        }
+    }
+
+    Ok(file)
+}
+
+// `*S:` after the flag keyword of the ispell format: a single ASCII character, with `*`
+// for cross products and `~` for affixes only allowed in compound words
+fn parse_ispell_flag(s: &str) -> Option<(String, u8)> {
+    let s = s.trim_start();
+    let (flags, s) = if let Some(s) = s.strip_prefix('*') {
+        (CROSS_PRODUCT, s)
+    } else if let Some(s) = s.strip_prefix('~') {
+        (COMPOUND_ONLY, s)
+    } else {
+        (0, s)
+    };
+    let s = s.strip_prefix('\\').unwrap_or(s);
+
+    let mut chars = s.chars();
+    let flag = chars.next().filter(char::is_ascii)?;
+    chars
+        .next()
+        .is_none_or(|c| c == ':' || c == '#' || c.is_whitespace())
+        .then(|| (flag.to_string(), flags))
+}
+
+// The parts of an ispell affix line
+struct IspellEntry {
+    condition: String,
+    strip: String,
+    add: String,
+}
+
+// `[^AEIOU]Y > -Y,IES` into its condition, the stripped and the added part
+// (parse_affentry in spell.c)
+fn parse_ispell_entry(line: &str) -> Result<Option<IspellEntry>, Box<dyn Error>> {
+    enum State {
+        WaitCondition,
+        Condition,
+        WaitStrip,
+        Strip,
+        WaitAdd,
+        Add,
+    }
+
+    let (mut condition, mut strip, mut add) = (String::new(), String::new(), String::new());
+    let mut state = State::WaitCondition;
+    for c in line.chars() {
+        match state {
+            State::WaitCondition if c == '#' => return Ok(None),
+            State::WaitCondition | State::Condition if c == '>' && !condition.is_empty() => {
+                state = State::WaitStrip;
+            }
+            State::WaitCondition | State::Condition if !c.is_whitespace() => {
+                condition.push(c);
+                state = State::Condition;
+            }
+            State::WaitStrip if c == '-' => state = State::Strip,
+            State::WaitStrip if c.is_alphabetic() || c == '\'' => {
+                add.push(c);
+                state = State::Add;
+            }
+            State::Strip if c == ',' => state = State::WaitAdd,
+            State::Strip if c.is_alphabetic() => strip.push(c),
+            State::WaitAdd if c == '-' => break,
+            State::WaitAdd | State::Add if c.is_alphabetic() => {
+                add.push(c);
+                state = State::Add;
+            }
+            State::Add if c == '#' => break,
+            State::WaitStrip | State::Strip | State::WaitAdd | State::Add if !c.is_whitespace() => {
+                return Err(format!("Invalid affix: {line}").into());
+            }
+            _ => {}
+        }
+    }
+
+    if condition.is_empty() || strip.is_empty() && add.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(IspellEntry {
+        condition,
+        strip,
+        add,
+    }))
+}
+
+// Ispell format (NIImportAffixes in spell.c):
+//
+//   compoundwords controlled Z
+//   suffixes
+//   flag *S:
+//       [^AEIOU]Y   >   -Y,IES
+//       [AEIOU]Y    >   S
+fn parse_ispell_affixes(contents: &str) -> Result<AffixFile, Box<dyn Error>> {
+    let mut file = AffixFile {
+        mode: FlagMode::Char,
+        aliases: None,
+        compound_flags: HashMap::new(),
+        affixes: Vec::new(),
+    };
+    let mut kind = None;
+    let mut flag: Option<(String, u8)> = None;
+
+    for line in contents.lines() {
+        let lower = lowercase(line);
+        if lower.starts_with('#') {
+            continue;
+        }
+
+        if lower.starts_with("compoundwords") {
+            // `controlled` is followed by the flag of words allowed in compounds
+            let compound_flag = line
+                .find(['l', 'L'])
+                .and_then(|at| line[at..].split_whitespace().nth(1))
+                .filter(|value| value.starts_with(|c: char| c.is_ascii()));
+            if let Some(compound_flag) = compound_flag {
+                file.compound_flags
+                    .insert(compound_flag.to_string(), COMPOUND_FLAG);
+            }
+            continue;
+        }
+        if lower.starts_with("prefixes") {
+            kind = Some(AffixKind::Prefix);
+            continue;
+        }
+        if lower.starts_with("suffixes") {
+            kind = Some(AffixKind::Suffix);
+            continue;
+        }
+        if lower.starts_with("flag") {
+            // the flag keeps its case, only the keyword is case insensitive
+            flag = line.get(4..).and_then(parse_ispell_flag);
+            continue;
+        }
+
+        let (Some(kind), Some((flag, flags))) = (kind, &flag) else {
+            continue;
+        };
+        if let Some(entry) = parse_ispell_entry(&lower)? {
+            file.add(kind, flag, *flags, &entry.condition, entry.strip, entry.add)?;
+        }
+    }
+
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IspellDictionary;
+    use crate::pg_tsvector_config::Dictionary;
+
+    // the sample files of PostgreSQL 15 (src/backend/tsearch/dicts)
+    const ISPELL_DICT: &str = r"book/GJUS
+booking/SB
+footballklubber
+foot/ZS
+football/Z
+ball/SZ\
+klubber/Z
+sky/A
+";
+
+    const ISPELL_AFFIX: &str = r"compoundwords controlled Z
+
+prefixes
+
+flag *B:
+	.       >   RE      # As in enter > reenter
+
+flag U:
+    .       >   UN      # As in natural > unnatural
+
+suffixes
+
+flag *J:
+	[^E]    >   INGS        # As in cross > crossings
+
+flag *G:
+	[^E]    >   ING     # As in cross > crossing
+
+flag *S:
+	[^SXZHY]    >   S       # As in bat > bats
+
+flag *A:
+	[^AEIOU]Y   >   -Y,IES      # As in imply > implies
+
+flag ~\\:
+	[^Y]        >   Y              #~ advarsel > advarsely-
+";
+
+    const HUNSPELL_AFFIX: &str = r"COMPOUNDFLAG Z
+ONLYINCOMPOUND L
+
+PFX B Y 1
+PFX B   0	re	.
+
+PFX U N 1
+PFX U   0	un	.
+
+SFX J Y 1
+SFX J   0	INGS	[^E]
+
+SFX G Y 1
+SFX G   0	ING		[^E]
+
+SFX S Y 1
+SFX S   0	S	[^SXZHY]
+
+SFX A Y 1
+SFX A   Y	IES	[^AEIOU]Y
+
+SFX \ N 1
+SFX \   0	Y/L	[^Y]
+";
+
+    const HUNSPELL_LONG_DICT: &str = r"book/3
+book/11
+booking/4
+footballklubber
+foot/5
+football/1
+ball/6
+klubber/1
+sky/7
+ex-/8
+machina/9
+";
+
+    const HUNSPELL_LONG_AFFIX: &str = r"FLAG long
+
+AF 11
+AF cZ		#1
+AF cL		#2
+AF sGsJpUsS	#3
+AF sSpB		#4
+AF cZsS		#5
+AF sScZs\sE	#6
+AF sA		#7
+AF CaCp		#8
+AF CcCp		#9
+AF sD		#10
+AF sB		#11
+
+COMPOUNDFLAG cZ
+COMPOUNDBEGIN Ca
+COMPOUNDMIDDLE Cb
+COMPOUNDEND Cc
+COMPOUNDPERMITFLAG Cp
+ONLYINCOMPOUND cL
+
+PFX pB Y 1
+PFX pB   0	re	.
+
+PFX pU N 1
+PFX pU   0	un	.
+
+SFX sJ Y 1
+SFX sJ   0	INGS	[^E]
+
+SFX sG Y 1
+SFX sG   0	ING		[^E]
+
+SFX sS Y 1
+SFX sS   0	S	[^SXZHY]
+
+SFX sA Y 1
+SFX sA   Y	IES	[^AEIOU]Y{1}
+
+SFX sB Y 1
+SFX sB   0	ED	K{1}
+
+# Affixes with compound flags
+SFX s\ N 1
+SFX s\   0	Y/2	[^Y]
+
+SFX sE N 1
+SFX sE   0	S/2	[^S]
+
+# Check duplicate affixes
+SFX sD N 1
+SFX sD   0	S/2	[^S]
+";
+
+    const HUNSPELL_NUM_DICT: &str = r"book/302,301,202,303
+book/306
+booking/303,201
+footballklubber
+foot/101,303
+football/101
+ball/303,101,305
+klubber/101
+sky/304,307
+";
+
+    const HUNSPELL_NUM_AFFIX: &str = r"FLAG num
+
+COMPOUNDFLAG 101
+ONLYINCOMPOUND 102
+
+PFX 201 Y 1
+PFX 201   0	re	.
+
+PFX 202 N 1
+PFX 202   0	un	.
+
+SFX 301 Y 1
+SFX 301   0	INGS	[^E]
+
+SFX 302 Y 1
+SFX 302   0	ING		[^E]
+
+SFX 303 Y 1
+SFX 303   0	S	[^SXZHY]
+
+# Remove ED suffix from lexeme for base words with K ending
+SFX 306 Y 1
+SFX 306   0	ED	K{1}
+
+# Just add Y to lexeme for base words with Y ending
+SFX 307 Y 1
+SFX 307   Y	0	Y*
+
+SFX 304 Y 1
+SFX 304   Y	IES	[^AEIOU]Y
+
+SFX 305 N 1
+SFX 305   0	Y/102	[^Y]
+";
+
+    fn lexize(dictionary: &IspellDictionary, word: &str) -> Option<Vec<String>> {
+        dictionary
+            .lexize(word)
+            .map(|lexemes| lexemes.into_iter().map(|lexeme| lexeme.lexeme).collect())
+    }
+
+    type Lexemes = Option<&'static [&'static str]>;
+
+    const FOOTBALLKLUBBER: Lexemes = Some(&[
+        "footballklubber",
+        "foot",
+        "ball",
+        "klubber",
+        "football",
+        "klubber",
+    ]);
+
+    // (word, ts_lexize with the ispell, hunspell, hunspell_long and hunspell_num
+    // dictionaries) captured from PostgreSQL 15
+    const LEXIZE: &[(&str, [Lexemes; 4])] = &[
+        ("books", [Some(&["book"]); 4]),
+        ("bookings", [Some(&["booking", "book"]); 4]),
+        ("booked", [None, None, Some(&["book"]), Some(&["book"])]),
+        ("skies", [Some(&["sky"]); 4]),
+        ("sk", [None, None, None, Some(&["sky"])]),
+        ("rebook", [None; 4]),
+        ("unbookings", [Some(&["book"]); 4]),
+        ("footklubber", [Some(&["foot", "klubber"]); 4]),
+        ("ballyklubber", [Some(&["ball", "klubber"]); 4]),
+        (
+            "ballsklubber",
+            [None, None, Some(&["ball", "klubber"]), None],
+        ),
+        ("footballklubber", [FOOTBALLKLUBBER; 4]),
+        ("ex-machina", [None, None, Some(&["ex-", "machina"]), None]),
+        ("bally", [None; 4]),
+    ];
+
+    #[test]
+    fn lexize_like_the_server() {
+        let dictionaries = [
+            ("ispell", IspellDictionary::parse(ISPELL_DICT, ISPELL_AFFIX)),
+            (
+                "hunspell",
+                IspellDictionary::parse(ISPELL_DICT, HUNSPELL_AFFIX),
+            ),
+            (
+                "hunspell_long",
+                IspellDictionary::parse(HUNSPELL_LONG_DICT, HUNSPELL_LONG_AFFIX),
+            ),
+            (
+                "hunspell_num",
+                IspellDictionary::parse(HUNSPELL_NUM_DICT, HUNSPELL_NUM_AFFIX),
+            ),
+        ]
+        .map(|(name, dictionary)| (name, dictionary.unwrap()));
+
+        for (word, expected) in LEXIZE {
+            for ((name, dictionary), lexemes) in dictionaries.iter().zip(expected) {
+                let lexemes =
+                    lexemes.map(|lexemes| lexemes.iter().map(|s| s.to_string()).collect());
+                assert_eq!(lexize(dictionary, word), lexemes, "{name}: {word}");
+            }
+        }
+    }
+
+    #[test]
+    fn compound_parts_share_a_variant() {
+        let dictionary = IspellDictionary::parse(ISPELL_DICT, ISPELL_AFFIX).unwrap();
+        let variants: Vec<(String, u16)> = dictionary
+            .lexize("footballklubber")
+            .unwrap()
+            .into_iter()
+            .map(|lexeme| (lexeme.lexeme, lexeme.variant))
+            .collect();
+
+        // to_tsquery('ispell', 'footballklubber') is
+        // 'footballklubber' | 'foot' & 'ball' & 'klubber' | 'football' & 'klubber'
+        let expected = [
+            ("footballklubber", 1),
+            ("foot", 2),
+            ("ball", 2),
+            ("klubber", 2),
+            ("football", 3),
+            ("klubber", 3),
+        ];
+        assert_eq!(
+            variants,
+            expected.map(|(lexeme, variant)| (lexeme.to_string(), variant))
+        );
+    }
+}
@@ -0,0 +1,363 @@
+use crate::pg_tsvector::{MAX_POSITION, PgTsVector, Weight};
+use crate::pg_tsvector_config::{ParsedWord, TextSearchConfig, make_tsvector};
+use bitflags::bitflags;
+use serde_json::{Number, Value};
+use std::error::Error;
+use std::str::FromStr;
+
+bitflags! {
+    /// Which parts of a JSON document are indexed, the filter of `jsonb_to_tsvector`
+    /// (JsonToIndex in jsonfuncs.h)
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+    pub struct JsonFilter: u32 {
+        const KEY = 0x01;
+        const STRING = 0x02;
+        const NUMERIC = 0x04;
+        const BOOLEAN = 0x08;
+        const ALL = Self::KEY.bits() | Self::STRING.bits() | Self::NUMERIC.bits() | Self::BOOLEAN.bits();
+    }
+}
+
+impl JsonFilter {
+    /// Reads a filter like `["string", "numeric"]`, a single flag works as well
+    /// (parse_jsonb_index_flags in jsonfuncs.c)
+    pub fn from_json(filter: &Value) -> Result<JsonFilter, Box<dyn Error>> {
+        let flags = match filter {
+            Value::Array(flags) => flags.iter().collect(),
+            Value::Object(_) => {
+                return Err(
+                    "Invalid JSON filter: wrong flag type, only arrays and scalars are allowed"
+                        .into(),
+                );
+            }
+            // jsonb stores scalars as an array of one element
+            _ => vec![filter],
+        };
+
+        flags
+            .into_iter()
+            .try_fold(JsonFilter::empty(), |filter, flag| {
+                let flag = match flag {
+                    Value::String(flag) => flag,
+                    Value::Array(_) | Value::Object(_) => {
+                        return Err("Invalid JSON filter: unexpected end of flag array".into());
+                    }
+                    _ => {
+                        return Err(
+                            "Invalid JSON filter: flag array element is not a string".into()
+                        );
+                    }
+                };
+                let flag = match flag.to_ascii_lowercase().as_str() {
+                    "all" => JsonFilter::ALL,
+                    "key" => JsonFilter::KEY,
+                    "string" => JsonFilter::STRING,
+                    "numeric" => JsonFilter::NUMERIC,
+                    "boolean" => JsonFilter::BOOLEAN,
+                    _ => {
+                        return Err(format!(
+                            "Invalid JSON filter: wrong flag in flag array: \"{flag}\""
+                        )
+                        .into());
+                    }
+                };
+                Ok(filter | flag)
+            })
+    }
+}
+
+impl FromStr for JsonFilter {
+    type Err = Box<dyn Error>;
+
+    /// Parses the filter as JSON text, e.g. `'["string", "key"]'`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        JsonFilter::from_json(&serde_json::from_str(s)?)
+    }
+}
+
+impl TextSearchConfig {
+    /// Same as `jsonb_to_tsvector(config, document, filter)`.
+    ///
+    /// Every key and value the filter selects is parsed on its own, one position apart
+    /// from the words of the previous one so phrases don't match across them. Objects
+    /// are walked in the order jsonb keeps keys in: shorter keys first, then bytewise.
+    /// Numbers are written like `numeric_out` does, so `1.50` stays `1.50` and `1e3`
+    /// becomes `1000`; keeping the scale needs the `arbitrary_precision` feature, without
+    /// it `1.50` is read as `1.5`. `to_tsvector(config, jsonb)` is the same with
+    /// [`JsonFilter::STRING`].
+    pub fn jsonb_to_tsvector(&self, document: &Value, filter: JsonFilter) -> PgTsVector {
+        let mut elements = Vec::new();
+        json_elements(document, filter, &mut elements);
+
+        let mut words = Vec::new();
+        let mut pos: u32 = 0;
+
+        // add_to_tsvector in to_tsany.c
+        for text in elements {
+            let (_, groups) = self.analyze(&text);
+            let mut end = pos;
+            let mut added = false;
+
+            for group in groups {
+                for word in group.words {
+                    words.push(ParsedWord {
+                        pos: clamp_position(pos + u32::from(word.pos)),
+                        ..word
+                    });
+                    added = true;
+                }
+                end = pos + u32::from(group.end);
+            }
+
+            pos = end + u32::from(added);
+        }
+
+        make_tsvector(words, Weight::D)
+    }
+}
+
+fn clamp_position(pos: u32) -> u16 {
+    pos.min(u32::from(MAX_POSITION)) as u16
+}
+
+// The texts the filter selects in document order (iterate_jsonb_values in jsonfuncs.c)
+fn json_elements(value: &Value, filter: JsonFilter, elements: &mut Vec<String>) {
+    match value {
+        Value::Null => {}
+        Value::Bool(b) if filter.contains(JsonFilter::BOOLEAN) => elements.push(b.to_string()),
+        Value::Number(n) if filter.contains(JsonFilter::NUMERIC) => {
+            elements.push(numeric_text(&number_text(n)))
+        }
+        Value::String(s) if filter.contains(JsonFilter::STRING) => elements.push(s.clone()),
+        Value::Bool(_) | Value::Number(_) | Value::String(_) => {}
+        Value::Array(values) => {
+            for value in values {
+                json_elements(value, filter, elements);
+            }
+        }
+        Value::Object(object) => {
+            let mut keys: Vec<&String> = object.keys().collect();
+            keys.sort_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+
+            for key in keys {
+                if filter.contains(JsonFilter::KEY) {
+                    elements.push(key.clone());
+                }
+                json_elements(&object[key], filter, elements);
+            }
+        }
+    }
+}
+
+// The text of the number as written in the JSON document, which serde_json only keeps with
+// its `arbitrary_precision` feature
+#[cfg(feature = "arbitrary_precision")]
+pub(crate) fn number_text(n: &Number) -> String {
+    n.as_str().to_string()
+}
+
+// Without `arbitrary_precision` the number went through f64, written without exponent so
+// `1e3` becomes `1000` and not `1000.0`
+#[cfg(not(feature = "arbitrary_precision"))]
+pub(crate) fn number_text(n: &Number) -> String {
+    match n.as_f64() {
+        Some(f) if n.is_f64() => f.to_string(),
+        _ => n.to_string(),
+    }
+}
+
+// The number the way jsonb prints it: the exponent is written out and the scale is the
+// number of fraction digits less the exponent (numeric_in and numeric_out in numeric.c).
+// Numbers the server rejects as out of range keep their JSON text.
+pub(crate) fn numeric_text(text: &str) -> String {
+    let (negative, unsigned) = match text.strip_prefix('-') {
+        Some(unsigned) => (true, unsigned),
+        None => (false, text),
+    };
+    let (mantissa, exponent) = match unsigned.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => match exponent.parse::<i64>() {
+            Ok(exponent) if exponent.unsigned_abs() <= 200_000 => (mantissa, exponent),
+            _ => return text.to_string(),
+        },
+        None => (unsigned, 0),
+    };
+    let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+
+    let digits = format!("{int}{frac}");
+    let point = int.len() as i64 + exponent;
+    let (int, frac) = if point < 0 {
+        (
+            String::new(),
+            "0".repeat(point.unsigned_abs() as usize) + &digits,
+        )
+    } else if point as usize > digits.len() {
+        (
+            digits.clone() + &"0".repeat(point as usize - digits.len()),
+            String::new(),
+        )
+    } else {
+        let (int, frac) = digits.split_at(point as usize);
+        (int.to_string(), frac.to_string())
+    };
+    let int = match int.trim_start_matches('0') {
+        "" => "0",
+        int => int,
+    };
+
+    // NUMERIC_WEIGHT_MAX and NUMERIC_DSCALE_MAX in decimal digits
+    if int.len() > 131_072 || frac.len() > 16_383 {
+        return text.to_string();
+    }
+
+    let sign = if negative && digits.bytes().any(|b| b != b'0') {
+        "-"
+    } else {
+        ""
+    };
+    if frac.is_empty() {
+        format!("{sign}{int}")
+    } else {
+        format!("{sign}{int}.{frac}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::JsonFilter;
+    use crate::pg_tsquery::PgTsQuery;
+    use crate::pg_tsvector_config::TextSearchConfig;
+    use std::str::FromStr;
+
+    // (jsonb, jsonb_to_tsvector('simple', jsonb, '"numeric"')) captured from PostgreSQL 15
+    const NUMBERS: &[(&str, &str)] = &[
+        ("[1e3]", "'1000':1"),
+        ("[1E+2]", "'100':1"),
+        ("[12.5e-1]", "'1.25':1"),
+        ("[1.5e-3]", "'0.0015':1"),
+        ("[-1.5E-2]", "'-0.015':1"),
+        ("[-0]", "'0':1"),
+        ("[0e5]", "'0':1"),
+        ("[-7, 1.2345e2]", "'-7':1 '123.45':3"),
+    ];
+
+    // The same for numbers whose scale or digits f64 doesn't keep
+    #[cfg(feature = "arbitrary_precision")]
+    const EXACT_NUMBERS: &[(&str, &str)] = &[
+        ("[1.50]", "'1.50':1"),
+        ("[100e-2]", "'1.00':1"),
+        ("[0.0e-3]", "'0.0000':1"),
+        ("[-0.0]", "'0.0':1"),
+        (
+            "[123456789012345678901234567890]",
+            "'123456789012345678901234567890':1",
+        ),
+    ];
+
+    #[test]
+    fn prints_numbers_like_numeric_out() {
+        let config = TextSearchConfig::simple();
+        #[cfg(feature = "arbitrary_precision")]
+        let numbers = NUMBERS.iter().chain(EXACT_NUMBERS);
+        #[cfg(not(feature = "arbitrary_precision"))]
+        let numbers = NUMBERS.iter();
+
+        for (json, expected) in numbers {
+            let json = serde_json::from_str(json).unwrap();
+            let vector = config.jsonb_to_tsvector(&json, JsonFilter::NUMERIC);
+            assert_eq!(vector.to_string(), *expected, "{json}");
+        }
+    }
+
+    // (filter, jsonb, jsonb_to_tsvector('simple', jsonb, filter)) captured from
+    // PostgreSQL 15
+    const FILTERS: &[(&str, &str, &str)] = &[
+        (
+            r#"["key"]"#,
+            r#"{"bb": "x y", "a": {"ccc": 1, "dd": true}, "e": [1, "z"]}"#,
+            "'a':1 'bb':9 'ccc':5 'dd':3 'e':7",
+        ),
+        (
+            r#"["string"]"#,
+            r#"{"bb": "x y", "a": {"ccc": 1, "dd": true}, "e": [1, "z"]}"#,
+            "'x':3 'y':4 'z':1",
+        ),
+        (
+            r#"["boolean"]"#,
+            r#"{"bb": "x y", "a": {"ccc": 1, "dd": true}, "e": [false, "z"]}"#,
+            "'false':3 'true':1",
+        ),
+        (
+            r#""all""#,
+            r#"{"bb": "x y", "a": {"ccc": 1, "dd": true}, "e": [1, "z", null]}"#,
+            "'1':9,13 'a':1 'bb':17 'ccc':7 'dd':3 'e':11 'true':5 'x':19 'y':20 'z':15",
+        ),
+        (
+            r#"["string", "numeric"]"#,
+            r#"["a b", 5, "c"]"#,
+            "'5':4 'a':1 'b':2 'c':6",
+        ),
+        // shorter keys first, then bytewise
+        (
+            r#"["ALL"]"#,
+            r#"{"abc": 1, "b": 2, "ab": 3, "ba": 4, "aa": 5}"#,
+            "'1':19 '2':3 '3':11 '4':15 '5':7 'aa':5 'ab':9 'abc':17 'b':1 'ba':13",
+        ),
+        // elements without words don't add a gap
+        (
+            r#""string""#,
+            r#"["a", "", "b", "!!!", "c d", "  ", "e"]"#,
+            "'a':1 'b':3 'c':5 'd':6 'e':8",
+        ),
+        (
+            r#""key""#,
+            r#"{"the cat": 1, "a": {"b c": 2}}"#,
+            "'a':1 'b':3 'c':4 'cat':7 'the':6",
+        ),
+        (r#""all""#, "null", ""),
+        (r#""all""#, r#""x""#, "'x':1"),
+        (r#""all""#, "[]", ""),
+        ("[]", r#"{"a": "b"}"#, ""),
+    ];
+
+    #[test]
+    fn filters_like_the_server() {
+        let config = TextSearchConfig::simple();
+
+        for (filter, json, expected) in FILTERS {
+            let filter = JsonFilter::from_str(filter).unwrap();
+            let vector = config.jsonb_to_tsvector(&serde_json::from_str(json).unwrap(), filter);
+            assert_eq!(vector.to_string(), *expected, "{json}");
+        }
+
+        // one position between elements, so phrases don't match across them
+        let json = serde_json::from_str(r#"["fat rat", "cat"]"#).unwrap();
+        let vector = config.jsonb_to_tsvector(&json, JsonFilter::STRING);
+        assert!(!vector.matches(&PgTsQuery::from_str("rat <-> cat").unwrap()));
+        assert!(vector.matches(&PgTsQuery::from_str("rat <2> cat").unwrap()));
+    }
+
+    // (filter, error) captured from PostgreSQL 15
+    const FILTER_ERRORS: &[(&str, &str)] = &[
+        ("1", "flag array element is not a string"),
+        ("null", "flag array element is not a string"),
+        ("true", "flag array element is not a string"),
+        (r#"["key", 1]"#, "flag array element is not a string"),
+        ("{}", "wrong flag type, only arrays and scalars are allowed"),
+        (r#"["nope"]"#, r#"wrong flag in flag array: "nope""#),
+        (r#"[["key"]]"#, "unexpected end of flag array"),
+    ];
+
+    #[test]
+    fn rejects_what_the_server_rejects() {
+        for (filter, expected) in FILTER_ERRORS {
+            let error = JsonFilter::from_str(filter).unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                format!("Invalid JSON filter: {expected}"),
+                "{filter}"
+            );
+        }
+
+        assert_eq!(JsonFilter::from_str(r#""Key""#).unwrap(), JsonFilter::KEY);
+    }
+}
@@ -0,0 +1,286 @@
+use crate::pg_tsvector::{
+    Lexeme, MAX_LEXEME_LEN, MAX_POSITION, MAX_TOTAL_LEN, PgTsVector, Position, Weight,
+};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// Error returned when a tsvector string cannot be parsed.
+/// `position` is the byte offset in the input where the problem was found.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TsVectorParseError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for TsVectorParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "syntax error in tsvector at position {}: {}",
+            self.position, self.message
+        )
+    }
+}
+
+impl Error for TsVectorParseError {}
+
+impl FromStr for PgTsVector {
+    type Err = TsVectorParseError;
+
+    /// Parse the text form of a tsvector the same way `tsvector_in` does.
+    ///
+    /// Lexemes are either unquoted or quoted with `'`, where `''` stands for a quote.
+    /// Backslash escapes the next character in both. Positions follow after a colon,
+    /// separated by commas and each with an optional weight letter, e.g. `'cat':3A,5`.
+    /// `*` is accepted as weight A and positions above 16383 are cut off.
+    ///
+    /// The result is in canonical form, see [`PgTsVector::canonicalize`].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { input: s, pos: 0 };
+        let mut words = Vec::new();
+        let mut total_len = 0;
+
+        while let Some(lexeme) = parser.next_lexeme()? {
+            total_len += lexeme.word.len();
+            if total_len > MAX_TOTAL_LEN {
+                return error(
+                    parser.pos,
+                    format!(
+                        "string is too long for tsvector ({total_len} bytes, max {MAX_TOTAL_LEN} bytes)"
+                    ),
+                );
+            }
+            words.push(lexeme);
+        }
+
+        let mut vector = PgTsVector { words };
+        vector.canonicalize();
+
+        // the positions count as well once the lexemes are merged (uniqueentry)
+        let data_len = vector.data_len();
+        if data_len > MAX_TOTAL_LEN {
+            return error(
+                parser.pos,
+                format!(
+                    "string is too long for tsvector ({data_len} bytes, max {MAX_TOTAL_LEN} bytes)"
+                ),
+            );
+        }
+
+        Ok(vector)
+    }
+}
+
+fn error<T>(position: usize, message: impl Into<String>) -> Result<T, TsVectorParseError> {
+    Err(TsVectorParseError {
+        position,
+        message: message.into(),
+    })
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn next_char(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn read_escaped(&mut self) -> Result<char, TsVectorParseError> {
+        match self.next_char() {
+            Some(c) => Ok(c),
+            None => error(self.pos, "there is no escaped character"),
+        }
+    }
+
+    /// Reads the next lexeme with its positions (gettoken_tsvector in tsvector_parser.c)
+    fn next_lexeme(&mut self) -> Result<Option<Lexeme>, TsVectorParseError> {
+        while let Some(c) = self.peek().filter(|c| c.is_whitespace()) {
+            self.pos += c.len_utf8();
+        }
+
+        let start = self.pos;
+        let mut word = String::new();
+
+        match self.peek() {
+            None => return Ok(None),
+            Some('\'') => {
+                self.pos += 1;
+                loop {
+                    match self.next_char() {
+                        None => return error(start, "unterminated quoted lexeme"),
+                        Some('\'') if self.peek() == Some('\'') => {
+                            self.pos += 1;
+                            word.push('\'');
+                        }
+                        Some('\'') if word.is_empty() => {
+                            return error(start, "empty quoted lexeme");
+                        }
+                        Some('\'') => break,
+                        Some('\\') => word.push(self.read_escaped()?),
+                        Some(c) => word.push(c),
+                    }
+                }
+            }
+            Some(_) => {
+                while let Some(c) = self.peek() {
+                    if c.is_whitespace() {
+                        break;
+                    }
+                    // a leading colon is part of the lexeme, `:a` is the lexeme ':a'
+                    if c == ':' && !word.is_empty() {
+                        break;
+                    }
+                    self.pos += c.len_utf8();
+                    if c == '\\' {
+                        word.push(self.read_escaped()?);
+                    } else {
+                        word.push(c);
+                    }
+                }
+            }
+        }
+
+        // tsvector_in is one byte stricter than the binary format (toklen >= MAXSTRLEN)
+        if word.len() >= MAX_LEXEME_LEN {
+            return error(
+                start,
+                format!(
+                    "word is too long ({} bytes, max {} bytes)",
+                    word.len(),
+                    MAX_LEXEME_LEN - 1
+                ),
+            );
+        }
+
+        let positions = if self.peek() == Some(':') {
+            self.pos += 1;
+            self.read_positions()?
+        } else {
+            vec![]
+        };
+
+        Ok(Some(Lexeme { word, positions }))
+    }
+
+    /// `3A,5,7b`: positions separated by commas, each with an optional weight.
+    ///
+    /// Like the server, digits after the weight are skipped and a weight letter may
+    /// only follow weight D, so `1b2` is `1B` while `1bd` is an error.
+    fn read_positions(&mut self) -> Result<Vec<Position>, TsVectorParseError> {
+        let mut positions = Vec::new();
+
+        'positions: loop {
+            let start = self.pos;
+            let digits = self.input[self.pos..]
+                .bytes()
+                .take_while(u8::is_ascii_digit)
+                .count();
+            if digits == 0 {
+                return match self.peek() {
+                    Some(c) => error(start, format!("unexpected '{c}', expected position")),
+                    None => error(start, "unexpected end of input, expected position"),
+                };
+            }
+            self.pos += digits;
+
+            // LIMITPOS: anything too large becomes the largest position
+            let pos = self.input[start..self.pos]
+                .parse::<u64>()
+                .map_or(MAX_POSITION, |pos| pos.min(u64::from(MAX_POSITION)) as u16);
+            if pos == 0 {
+                return error(start, "wrong position info in tsvector");
+            }
+
+            let mut weight = Weight::D;
+
+            // WAITPOSDELIM in gettoken_tsvector
+            loop {
+                let c = match self.peek() {
+                    Some(',') => {
+                        self.pos += 1;
+                        positions.push(Position { pos, weight });
+                        continue 'positions;
+                    }
+                    Some(c) if !c.is_whitespace() => c,
+                    _ => {
+                        positions.push(Position { pos, weight });
+                        return Ok(positions);
+                    }
+                };
+
+                if !c.is_ascii_digit() {
+                    let letter = if c == '*' {
+                        Ok(Weight::A)
+                    } else {
+                        Weight::try_from(c)
+                    };
+                    match letter {
+                        Ok(letter) if weight == Weight::D => weight = letter,
+                        _ => return error(self.pos, format!("unexpected '{c}' in position list")),
+                    }
+                }
+                self.pos += c.len_utf8();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pg_tsvector::PgTsVector;
+    use std::str::FromStr;
+
+    // (input, SELECT input::tsvector::text) captured from PostgreSQL 15
+    const CORPUS: &[(&str, &str)] = &[
+        ("''''", "''''"),
+        (r"'\'':1", "'''':1"),
+        ("b:3 a:1,2B 'a':4 c", "'a':1,2B,4 'b':3 'c'"),
+        (r"'it''s' back\ slash:2*", "'back slash':2A 'it''s'"),
+        ("x:20000", "'x':16383"),
+        (" :", "':'"),
+        (":a", "':a'"),
+        (":1", "':1'"),
+        (r"a\:b:1", "'a:b':1"),
+        ("b:1b2", "'b':1B"),
+        ("a:1b2,3", "'a':1B,3"),
+        ("a:1b 2", "'2' 'a':1B"),
+        ("a:1dd", "'a':1"),
+        ("a:1db", "'a':1B"),
+        ("a:1d*", "'a':1A"),
+    ];
+
+    #[test]
+    fn parses_like_tsvectorin() {
+        for (input, expected) in CORPUS {
+            let vector = PgTsVector::from_str(input).unwrap();
+            assert_eq!(vector.to_string(), *expected, "input: {input}");
+        }
+    }
+
+    #[test]
+    fn rejects_what_the_server_rejects() {
+        for input in [
+            "''", "a '':1", "x:0", "::", "a::1", "a:1:2", "a:", "a:,1", "a:1,", "'x':", "a:1x",
+            "a:1,2x", "a:1bb", "a:1bd", "a:1Ab", "a:12b3c",
+        ] {
+            assert!(PgTsVector::from_str(input).is_err(), "input: {input}");
+        }
+    }
+
+    #[test]
+    fn lexeme_length_limit() {
+        assert!(PgTsVector::from_str(&"a".repeat(2046)).is_ok());
+        let error = PgTsVector::from_str(&"a".repeat(2047)).unwrap_err();
+        assert!(error.message.contains("max 2046 bytes"), "{error}");
+    }
+}
@@ -0,0 +1,787 @@
+use crate::pg_tsquery::{Entry, Operators, PgTsQuery, Value};
+use crate::pg_tsquery_match::{Ternary, execute};
+use crate::pg_tsvector::{Lexeme, MAX_POSITION, PgTsVector, Position, Weight};
+use bitflags::bitflags;
+use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+
+/// Weights of D, C, B and A positions the server uses when none are given
+pub const DEFAULT_RANK_WEIGHTS: [f32; 4] = [0.1, 0.2, 0.4, 1.0];
+
+bitflags! {
+    /// How the rank is adjusted for the document, the `normalization` argument of
+    /// `ts_rank` and `ts_rank_cd`. Several flags are applied in the order listed.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+    pub struct RankNormalization: u32 {
+        /// Divides by 1 + the logarithm of the document length
+        const LOG_LENGTH = 0x01;
+        /// Divides by the document length
+        const LENGTH = 0x02;
+        /// Divides by the mean harmonic distance between extents, only `ts_rank_cd`
+        const EXTENT_DISTANCE = 0x04;
+        /// Divides by the number of unique words
+        const UNIQUE = 0x08;
+        /// Divides by 1 + the logarithm of the number of unique words
+        const LOG_UNIQUE = 0x10;
+        /// Divides the rank by itself + 1
+        const RANK_PLUS_ONE = 0x20;
+    }
+}
+
+impl PgTsVector {
+    /// Same as `ts_rank(vector, query, normalization)`
+    pub fn rank(&self, query: &PgTsQuery, normalization: RankNormalization) -> f32 {
+        rank(&DEFAULT_RANK_WEIGHTS, self, query, normalization)
+    }
+
+    /// Same as `ts_rank(weights, vector, query, normalization)`, with the weights given
+    /// as `{D, C, B, A}`. Negative weights fall back to the default ones, weights above
+    /// 1 are an error like on the server.
+    pub fn rank_weighted(
+        &self,
+        weights: &[f32; 4],
+        query: &PgTsQuery,
+        normalization: RankNormalization,
+    ) -> Result<f32, Box<dyn Error>> {
+        Ok(rank(&check_weights(weights)?, self, query, normalization))
+    }
+
+    /// Same as `ts_rank_cd(vector, query, normalization)`: ranks by cover density, how
+    /// close together the matching words are. Lexemes without positions are ignored.
+    pub fn rank_cd(&self, query: &PgTsQuery, normalization: RankNormalization) -> f32 {
+        rank_cd(&DEFAULT_RANK_WEIGHTS, self, query, normalization)
+    }
+
+    /// Same as `ts_rank_cd(weights, vector, query, normalization)`, see
+    /// [`PgTsVector::rank_weighted`] for the weights
+    pub fn rank_cd_weighted(
+        &self,
+        weights: &[f32; 4],
+        query: &PgTsQuery,
+        normalization: RankNormalization,
+    ) -> Result<f32, Box<dyn Error>> {
+        Ok(rank_cd(
+            &check_weights(weights)?,
+            self,
+            query,
+            normalization,
+        ))
+    }
+}
+
+// getWeights in tsrank.c
+fn check_weights(weights: &[f32; 4]) -> Result<[f32; 4], Box<dyn Error>> {
+    let mut checked = DEFAULT_RANK_WEIGHTS;
+
+    for (weight, given) in checked.iter_mut().zip(weights) {
+        if *given >= 0.0 {
+            *weight = *given;
+        }
+        if *weight > 1.0 {
+            return Err("Invalid weight: weight out of range".into());
+        }
+    }
+
+    Ok(checked)
+}
+
+fn weight_of(weights: &[f32; 4], position: &Position) -> f32 {
+    weights[position.weight as usize]
+}
+
+// Weight of two words `distance` apart
+fn word_distance(distance: i32) -> f32 {
+    if distance > 100 {
+        return 1e-30;
+    }
+
+    (1.0 / (1.005 + 0.05 * (f64::from(distance) / 1.5 - 2.0).exp())) as f32
+}
+
+// Number of positions, lexemes without any count as one (cnt_length)
+fn document_length(vector: &PgTsVector) -> usize {
+    vector
+        .words
+        .iter()
+        .map(|lexeme| lexeme.positions.len().max(1))
+        .sum()
+}
+
+// Lexemes an operand matches, all those starting with it for a prefix (find_wordentry)
+fn find_lexemes<'a>(vector: &'a PgTsVector, value: &'a Value) -> impl Iterator<Item = &'a Lexeme> {
+    vector.words.iter().filter(move |lexeme| {
+        if value.prefix != 0 {
+            lexeme.word.starts_with(&value.text)
+        } else {
+            lexeme.word == value.text
+        }
+    })
+}
+
+// The operands of the query sorted by text, each text only once (SortAndUniqItems)
+fn unique_operands(query: &PgTsQuery) -> Vec<&Value> {
+    let mut operands: Vec<&Value> = query
+        .entries
+        .iter()
+        .filter_map(|entry| match entry {
+            Entry::Value(value) => Some(value),
+            Entry::Operator(_) => None,
+        })
+        .collect();
+
+    // which of the operands with the same text is kept, say `a` or `a:*`, depends on
+    // the order the server's sort leaves them in
+    pg_qsort(&mut operands, &mut |a, b| {
+        a.text.as_bytes().cmp(b.text.as_bytes())
+    });
+    operands.dedup_by(|a, b| a.text == b.text);
+    operands
+}
+
+// The quicksort of the server (sort_template.h), which isn't stable: insertion sort for
+// less than 7 elements, otherwise a median pivot and a three-way partition
+pub(crate) fn pg_qsort<T, F>(data: &mut [T], compare: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut a = 0;
+    let mut n = data.len();
+
+    loop {
+        if n < 7 {
+            for m in a + 1..a + n {
+                let mut l = m;
+                while l > a && compare(&data[l - 1], &data[l]) == Ordering::Greater {
+                    data.swap(l, l - 1);
+                    l -= 1;
+                }
+            }
+            return;
+        }
+        if (a + 1..a + n).all(|m| compare(&data[m - 1], &data[m]) != Ordering::Greater) {
+            return;
+        }
+
+        let mut med3 = |data: &[T], x: usize, y: usize, z: usize| {
+            if compare(&data[x], &data[y]) == Ordering::Less {
+                if compare(&data[y], &data[z]) == Ordering::Less {
+                    y
+                } else if compare(&data[x], &data[z]) == Ordering::Less {
+                    z
+                } else {
+                    x
+                }
+            } else if compare(&data[y], &data[z]) == Ordering::Greater {
+                y
+            } else if compare(&data[x], &data[z]) == Ordering::Less {
+                x
+            } else {
+                z
+            }
+        };
+        let mut m = a + n / 2;
+        if n > 7 {
+            let mut l = a;
+            let mut last = a + n - 1;
+            if n > 40 {
+                let d = n / 8;
+                l = med3(data, l, l + d, l + 2 * d);
+                m = med3(data, m - d, m, m + d);
+                last = med3(data, last - 2 * d, last - d, last);
+            }
+            m = med3(data, l, m, last);
+        }
+        data.swap(a, m);
+
+        // elements equal to the pivot are gathered at both ends first
+        let (mut pa, mut pb) = (a + 1, a + 1);
+        let (mut pc, mut pd) = (a + n - 1, a + n - 1);
+        loop {
+            while pb <= pc {
+                let order = compare(&data[pb], &data[a]);
+                if order == Ordering::Greater {
+                    break;
+                }
+                if order == Ordering::Equal {
+                    data.swap(pa, pb);
+                    pa += 1;
+                }
+                pb += 1;
+            }
+            while pb <= pc {
+                let order = compare(&data[pc], &data[a]);
+                if order == Ordering::Less {
+                    break;
+                }
+                if order == Ordering::Equal {
+                    data.swap(pc, pd);
+                    pd -= 1;
+                }
+                pc -= 1;
+            }
+            if pb > pc {
+                break;
+            }
+            data.swap(pb, pc);
+            pb += 1;
+            pc -= 1;
+        }
+
+        let end = a + n;
+        let d1 = (pa - a).min(pb - pa);
+        for i in 0..d1 {
+            data.swap(a + i, pb - d1 + i);
+        }
+        let d1 = (pd - pc).min(end - pd - 1);
+        for i in 0..d1 {
+            data.swap(pb + i, end - d1 + i);
+        }
+
+        // recurse into the smaller part, continue with the larger one
+        let (d1, d2) = (pb - pa, pd - pc);
+        if d1 <= d2 {
+            if d1 > 1 {
+                pg_qsort(&mut data[a..a + d1], compare);
+            }
+            if d2 <= 1 {
+                return;
+            }
+            a = end - d2;
+            n = d2;
+        } else {
+            if d2 > 1 {
+                pg_qsort(&mut data[end - d2..end], compare);
+            }
+            if d1 <= 1 {
+                return;
+            }
+            n = d1;
+        }
+    }
+}
+
+// calc_rank in tsrank.c. The arithmetic follows the server's mix of float and double
+// so the results come out the same.
+fn rank(
+    weights: &[f32; 4],
+    vector: &PgTsVector,
+    query: &PgTsQuery,
+    normalization: RankNormalization,
+) -> f32 {
+    if vector.words.is_empty() || query.entries.is_empty() {
+        return 0.0;
+    }
+
+    let operands = unique_operands(query);
+    let and = matches!(
+        query.entries.first(),
+        Some(Entry::Operator(operator))
+            if matches!(operator.operator, Operators::And | Operators::Phrase)
+    );
+
+    let mut res = if and && operands.len() >= 2 {
+        rank_and(weights, vector, &operands)
+    } else {
+        rank_or(weights, vector, &operands)
+    };
+
+    if res < 0.0 {
+        res = 1e-20;
+    }
+
+    let length = document_length(vector);
+    let unique = vector.words.len();
+
+    if normalization.contains(RankNormalization::LOG_LENGTH) {
+        res = (f64::from(res) / ((length as f64 + 1.0).ln() / 2f64.ln())) as f32;
+    }
+    if normalization.contains(RankNormalization::LENGTH) && length > 0 {
+        res /= length as f32;
+    }
+    // EXTENT_DISTANCE doesn't apply
+    if normalization.contains(RankNormalization::UNIQUE) {
+        res /= unique as f32;
+    }
+    if normalization.contains(RankNormalization::LOG_UNIQUE) {
+        res = (f64::from(res) / ((unique as f64 + 1.0).ln() / 2f64.ln())) as f32;
+    }
+    if normalization.contains(RankNormalization::RANK_PLUS_ONE) {
+        res /= res + 1.0;
+    }
+
+    res
+}
+
+// Every pair of positions of different operands adds to the rank, the closer the more
+// (calc_rank_and)
+fn rank_and(weights: &[f32; 4], vector: &PgTsVector, operands: &[&Value]) -> f32 {
+    // lexemes without positions count as being far away
+    let missing = [Position {
+        pos: MAX_POSITION,
+        weight: Weight::D,
+    }];
+
+    let mut res: f32 = -1.0;
+    let mut found: Vec<Option<&[Position]>> = vec![None; operands.len()];
+
+    for (i, operand) in operands.iter().enumerate() {
+        for lexeme in find_lexemes(vector, operand) {
+            let positions: &[Position] = if lexeme.positions.is_empty() {
+                &missing
+            } else {
+                &lexeme.positions
+            };
+            found[i] = Some(positions);
+
+            for other in found[..i].iter().flatten() {
+                let either_missing =
+                    std::ptr::eq(positions, &missing[..]) || std::ptr::eq(*other, &missing[..]);
+
+                for position in positions {
+                    for other_position in *other {
+                        let mut distance =
+                            (i32::from(position.pos) - i32::from(other_position.pos)).abs();
+                        if distance == 0 && !either_missing {
+                            continue;
+                        }
+                        if distance == 0 {
+                            distance = i32::from(MAX_POSITION) + 1;
+                        }
+
+                        let current = f64::from(
+                            weight_of(weights, position)
+                                * weight_of(weights, other_position)
+                                * word_distance(distance),
+                        )
+                        .sqrt() as f32;
+
+                        res = if res < 0.0 {
+                            current
+                        } else {
+                            (1.0 - (1.0 - f64::from(res)) * (1.0 - f64::from(current))) as f32
+                        };
+                    }
+                }
+            }
+        }
+    }
+
+    res
+}
+
+// Every occurrence of an operand adds to the rank, with diminishing returns
+// (calc_rank_or)
+fn rank_or(weights: &[f32; 4], vector: &PgTsVector, operands: &[&Value]) -> f32 {
+    let missing = [Position {
+        pos: 0,
+        weight: Weight::D,
+    }];
+    let mut res: f32 = 0.0;
+
+    for operand in operands {
+        for lexeme in find_lexemes(vector, operand) {
+            let positions: &[Position] = if lexeme.positions.is_empty() {
+                &missing
+            } else {
+                &lexeme.positions
+            };
+
+            let mut resj: f32 = 0.0;
+            let mut wjm: f32 = -1.0;
+            let mut jm = 0;
+            for (j, position) in positions.iter().enumerate() {
+                let weight = weight_of(weights, position);
+                resj += weight / ((j + 1) * (j + 1)) as f32;
+                if weight > wjm {
+                    wjm = weight;
+                    jm = j;
+                }
+            }
+
+            // the sum of 1/i^2 approaches pi^2/6
+            let sum = wjm + resj - wjm / ((jm + 1) * (jm + 1)) as f32;
+            res = (f64::from(res) + f64::from(sum) / 1.64493406685) as f32;
+        }
+    }
+
+    if !operands.is_empty() {
+        res /= operands.len() as f32;
+    }
+
+    res
+}
+
+// One position of the document where operands of the query occur (DocRepresentation)
+struct DocEntry<'a> {
+    position: Position,
+    lexeme: usize,
+    operands: Vec<&'a Value>,
+}
+
+// The positions of every operand found so far while looking for a cover
+// (QueryRepresentation)
+#[derive(Default)]
+struct OperandPositions<'a> {
+    positions: HashMap<&'a Value, VecDeque<i32>>,
+    reverse: bool,
+}
+
+impl<'a> OperandPositions<'a> {
+    fn reset(&mut self, reverse: bool) {
+        self.positions.clear();
+        self.reverse = reverse;
+    }
+
+    // fillQueryRepresentationData, going backwards the positions are added in front
+    fn add(&mut self, entry: &DocEntry<'a>) {
+        let pos = i32::from(entry.position.pos);
+
+        for operand in &entry.operands {
+            let positions = self.positions.entry(operand).or_default();
+            let last = if self.reverse {
+                positions.front()
+            } else {
+                positions.back()
+            };
+
+            if last != Some(&pos) {
+                if self.reverse {
+                    positions.push_front(pos);
+                } else {
+                    positions.push_back(pos);
+                }
+            }
+        }
+    }
+
+    fn check(&self, value: &Value, want_positions: bool) -> (Ternary, Vec<i32>) {
+        match self.positions.get(value) {
+            Some(positions) if want_positions => {
+                (Ternary::Yes, positions.iter().copied().collect())
+            }
+            Some(_) => (Ternary::Yes, vec![]),
+            None => (Ternary::No, vec![]),
+        }
+    }
+}
+
+// The positions of the operands in document order, operands at the same position of the
+// same lexeme are combined (get_docrep)
+fn doc_entries<'a>(vector: &PgTsVector, query: &'a PgTsQuery) -> Vec<DocEntry<'a>> {
+    let mut entries = Vec::new();
+
+    for entry in &query.entries {
+        let Entry::Value(value) = entry else {
+            continue;
+        };
+
+        for (index, lexeme) in vector.words.iter().enumerate() {
+            let matches = if value.prefix != 0 {
+                lexeme.word.starts_with(&value.text)
+            } else {
+                lexeme.word == value.text
+            };
+            if !matches {
+                continue;
+            }
+
+            for position in &lexeme.positions {
+                if value.weight == 0 || value.weights().contains(position.weight.into()) {
+                    entries.push(DocEntry {
+                        position: *position,
+                        lexeme: index,
+                        operands: vec![value],
+                    });
+                }
+            }
+        }
+    }
+
+    entries.sort_by_key(|entry| (entry.position.pos, entry.position.weight, entry.lexeme));
+
+    let mut merged: Vec<DocEntry> = Vec::with_capacity(entries.len());
+    for entry in entries {
+        match merged.last_mut() {
+            Some(last) if last.position == entry.position && last.lexeme == entry.lexeme => {
+                last.operands.extend(entry.operands);
+            }
+            _ => merged.push(entry),
+        }
+    }
+
+    merged
+}
+
+// The next extent of the document that satisfies the query (CoverExt)
+#[derive(Default)]
+struct Extent {
+    // where to start looking for the next cover
+    next: usize,
+    begin: usize,
+    end: usize,
+    p: i32,
+    q: i32,
+}
+
+// Finds the next shortest stretch of the document matching the query (Cover in tsrank.c)
+fn cover<'a, F>(
+    doc: &[DocEntry<'a>],
+    operands: &mut OperandPositions<'a>,
+    satisfied: &F,
+    extent: &mut Extent,
+) -> bool
+where
+    F: Fn(&OperandPositions<'a>) -> bool,
+{
+    loop {
+        operands.reset(false);
+        extent.p = i32::MAX;
+        extent.q = 0;
+
+        // upper bound: the first position from where the query is satisfied
+        let mut last = None;
+        for (i, entry) in doc.iter().enumerate().skip(extent.next) {
+            operands.add(entry);
+            if satisfied(operands) {
+                extent.q = i32::from(entry.position.pos);
+                extent.end = i;
+                last = Some(i);
+                break;
+            }
+        }
+        let Some(last) = last else {
+            return false;
+        };
+
+        // lower bound: going back from there until it is satisfied again
+        operands.reset(true);
+        let mut begin = None;
+        for i in (extent.next..=last).rev() {
+            operands.add(&doc[i]);
+            if satisfied(operands) {
+                let pos = i32::from(doc[i].position.pos);
+                if pos < extent.p {
+                    extent.begin = i;
+                    extent.p = pos;
+                }
+                begin = Some(i);
+                break;
+            }
+        }
+
+        if let Some(begin) = begin
+            && extent.p <= extent.q
+        {
+            extent.next = begin + 1;
+            return true;
+        }
+
+        extent.next += 1;
+    }
+}
+
+// calc_rank_cd in tsrank.c
+fn rank_cd(
+    weights: &[f32; 4],
+    vector: &PgTsVector,
+    query: &PgTsQuery,
+    normalization: RankNormalization,
+) -> f32 {
+    let inverse_weights = weights.map(|weight| 1.0 / f64::from(weight));
+
+    let Ok(Some(root)) = query.to_tree() else {
+        return 0.0;
+    };
+    let doc = doc_entries(vector, query);
+    if doc.is_empty() {
+        return 0.0;
+    }
+
+    let satisfied = |operands: &OperandPositions| {
+        let check = |value: &Value, want_positions| operands.check(value, want_positions);
+        execute(&root, &check) != Ternary::No
+    };
+
+    let mut operands = OperandPositions::default();
+    let mut extent = Extent::default();
+    let mut rank = 0.0;
+    let mut sum_distance = 0.0;
+    let mut previous_center = 0.0;
+    let mut extents = 0;
+
+    while cover(&doc, &mut operands, &satisfied, &mut extent) {
+        let covered = &doc[extent.begin..=extent.end];
+        let inverse_sum: f64 = covered
+            .iter()
+            .map(|entry| inverse_weights[entry.position.weight as usize])
+            .sum();
+        let density = covered.len() as f64 / inverse_sum;
+
+        // words in the extent that aren't part of the match. Positions are capped, so
+        // this can come out negative for large documents, then half the extent is used.
+        let span = (extent.end - extent.begin) as i32;
+        let mut noise = (extent.q - extent.p) - span;
+        if noise < 0 {
+            noise = span / 2;
+        }
+        rank += density / f64::from(1 + noise);
+
+        let center = f64::from(extent.q + extent.p) / 2.0;
+        if extents > 0 && center > previous_center {
+            sum_distance += 1.0 / (center - previous_center);
+        }
+        previous_center = center;
+        extents += 1;
+    }
+
+    let length = document_length(vector);
+    let unique = vector.words.len();
+
+    // unlike ts_rank this uses the natural logarithm
+    if normalization.contains(RankNormalization::LOG_LENGTH) {
+        rank /= (length as f64 + 1.0).ln();
+    }
+    if normalization.contains(RankNormalization::LENGTH) && length > 0 {
+        rank /= length as f64;
+    }
+    if normalization.contains(RankNormalization::EXTENT_DISTANCE)
+        && extents > 0
+        && sum_distance > 0.0
+    {
+        rank /= f64::from(extents) / sum_distance;
+    }
+    if normalization.contains(RankNormalization::UNIQUE) {
+        rank /= unique as f64;
+    }
+    if normalization.contains(RankNormalization::LOG_UNIQUE) {
+        rank /= (unique as f64 + 1.0).ln() / 2f64.ln();
+    }
+    if normalization.contains(RankNormalization::RANK_PLUS_ONE) {
+        rank /= rank + 1.0;
+    }
+
+    rank as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RankNormalization;
+    use crate::pg_tsquery::PgTsQuery;
+    use crate::pg_tsvector::PgTsVector;
+    use std::str::FromStr;
+
+    const FAT_CATS: &str =
+        "'and':8 'ate':12 'cat':3,14 'fat':2,10 'mat':7 'on':5 'rat':11 'sat':4 'the':1,6,9,13";
+    const WEIGHTED: &str = "'a':7B,9,12 'and':11 'cat':4B,14 'cats':2A 'fat':1A,13 'mat':8B \
+                            'on':6B 'rat':10 'sat':5B 'the':3B";
+    const MIXED: &str = "'cat':2A,9 'catalog':7 'fat':1,5C 'rat':3B 'sat':4";
+    const NO_POSITIONS: &str = "'cat' 'fat' 'rat' 'sat'";
+    const CATS_AND_DOGS: &str = "'cat':1,3,5,8 'dog':2,4,6 'fat':7";
+
+    // (vector, query, normalization, ts_rank, ts_rank_cd) captured from PostgreSQL 15
+    const CASES: &[(&str, &str, u32, &str, &str)] = &[
+        (FAT_CATS, "fat & cat", 0, "0.26333845", "0.13928571"),
+        (FAT_CATS, "fat & cat", 1, "0.06740359", "0.05143395"),
+        (FAT_CATS, "fat & cat", 2, "0.018809889", "0.00994898"),
+        (FAT_CATS, "fat & cat", 4, "0.26333845", "0.020048702"),
+        (FAT_CATS, "fat & cat", 8, "0.029259827", "0.0154761905"),
+        (FAT_CATS, "fat & cat", 16, "0.07927277", "0.041929178"),
+        (FAT_CATS, "fat & cat", 32, "0.20844647", "0.122257054"),
+        (FAT_CATS, "fat & cat", 5, "0.06740359", "0.0074033714"),
+        (FAT_CATS, "fat & cat", 63, "0.0001610098", "1.7687282e-05"),
+        (WEIGHTED, "cat:AB & fat", 0, "0.7051147", "0.20825396"),
+        (MIXED, "cat:*", 0, "0.683918", "1.2"),
+        (MIXED, "!dog & cat", 0, "1e-20", "1.1"),
+        (NO_POSITIONS, "fat | rat", 0, "0.06079271", "0"),
+        (NO_POSITIONS, "cat:*", 0, "0.06079271", "0"),
+        (NO_POSITIONS, "fat & cat", 0, "1e-16", "0"),
+        (CATS_AND_DOGS, "fat <-> cat", 0, "0.32787153", "0.1"),
+        (CATS_AND_DOGS, "fat <2> rat | sat", 0, "0.020264236", "0"),
+        (FAT_CATS, "fat <-> cat", 4, "0.26333845", "0.1"),
+        // operands with the same text, the server's sort decides which one is kept
+        (
+            "'a':4,10 'ab':4 'ba':1 'c':5",
+            "!((a:* | ab:*) | (a <-> c:*)) & !(ab & (ab | abc:*))",
+            0,
+            "0.188385",
+            "0",
+        ),
+        (
+            "'a':4 'ab':13,17,18 'b':2,14 'c':19",
+            "((abc & c) | !(c & a:*)) & ((ba & a) & abc)",
+            0,
+            "0.25711715",
+            "0",
+        ),
+    ];
+
+    // Same with the {D, C, B, A} weights below
+    const WEIGHTS: [f32; 4] = [0.5, 0.25, 1.0, 0.05];
+    const WEIGHTED_CASES: &[(&str, &str, u32, &str, &str)] = &[
+        (FAT_CATS, "fat & cat", 0, "0.854145", "0.6964286"),
+        (FAT_CATS, "fat & cat", 63, "0.0005220508", "8.843015e-05"),
+        (WEIGHTED, "cat:AB & fat", 0, "0.7559754", "0.105820104"),
+        (WEIGHTED, "fat <2> rat | sat", 5, "0.10632928", "0.36926937"),
+        (MIXED, "cat:*", 0, "0.6383235", "1.05"),
+    ];
+
+    fn check(
+        case: (&str, &str, u32, &str, &str),
+        rank: impl Fn(&PgTsVector, &PgTsQuery, RankNormalization) -> (f32, f32),
+    ) {
+        let (vector, query, normalization, expected, expected_cd) = case;
+        let vector = PgTsVector::from_str(vector).unwrap();
+        let query = PgTsQuery::from_str(query).unwrap();
+        let normalization = RankNormalization::from_bits(normalization).unwrap();
+        let (ours, ours_cd) = rank(&vector, &query, normalization);
+        let case = (vector.to_string(), query.to_string(), normalization);
+        assert_eq!(ours, expected.parse::<f32>().unwrap(), "ts_rank {case:?}");
+        assert_eq!(
+            ours_cd,
+            expected_cd.parse::<f32>().unwrap(),
+            "ts_rank_cd {case:?}"
+        );
+    }
+
+    #[test]
+    fn rank_like_the_server() {
+        for &case in CASES {
+            check(case, |vector, query, normalization| {
+                (
+                    vector.rank(query, normalization),
+                    vector.rank_cd(query, normalization),
+                )
+            });
+        }
+        for &case in WEIGHTED_CASES {
+            check(case, |vector, query, normalization| {
+                (
+                    vector
+                        .rank_weighted(&WEIGHTS, query, normalization)
+                        .unwrap(),
+                    vector
+                        .rank_cd_weighted(&WEIGHTS, query, normalization)
+                        .unwrap(),
+                )
+            });
+        }
+    }
+
+    #[test]
+    fn rejects_weights_above_one() {
+        let vector = PgTsVector::from_str(FAT_CATS).unwrap();
+        let query = PgTsQuery::from_str("fat").unwrap();
+        let weights = [0.1, 0.2, 0.4, 1.5];
+        assert!(
+            vector
+                .rank_weighted(&weights, &query, RankNormalization::empty())
+                .is_err()
+        );
+        assert!(
+            vector
+                .rank_cd_weighted(&weights, &query, RankNormalization::empty())
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,198 @@
+use crate::pg_tsquery_builder::Weights;
+use crate::pg_tsvector::PgTsVector;
+use std::collections::BTreeMap;
+
+/// Counts of one lexeme, a row of `ts_stat`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LexemeStat {
+    /// Number of documents the lexeme occurs in
+    pub ndoc: u32,
+    /// Total number of occurrences
+    pub nentry: u32,
+}
+
+/// Statistics about the lexemes of a collection of documents, like
+/// `ts_stat('SELECT vector FROM ...', weights)`.
+///
+/// ```
+/// use postgres_types_extra::pg_tsvector::PgTsVector;
+/// use postgres_types_extra::pg_tsvector_stat::{LexemeStat, TsStat};
+/// use std::str::FromStr;
+///
+/// let vectors = [
+///     PgTsVector::from_str("'fat':2 'cat':3,5")?,
+///     PgTsVector::from_str("'fat':1 'rat':2")?,
+/// ];
+/// let stat: TsStat = vectors.iter().collect();
+/// let mut common: Vec<_> = stat.iter().collect();
+/// common.sort_by_key(|(_, counts)| std::cmp::Reverse(counts.ndoc));
+/// assert_eq!(common[0], ("fat", LexemeStat { ndoc: 2, nentry: 2 }));
+/// assert_eq!(stat.get("cat"), Some(LexemeStat { ndoc: 1, nentry: 2 }));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TsStat {
+    weights: Weights,
+    words: BTreeMap<String, LexemeStat>,
+}
+
+impl TsStat {
+    pub fn new() -> Self {
+        TsStat::default()
+    }
+
+    /// Only counts positions with one of the given weights. Lexemes without positions
+    /// aren't counted then.
+    pub fn with_weights(weights: Weights) -> Self {
+        TsStat {
+            weights,
+            words: BTreeMap::new(),
+        }
+    }
+
+    /// Adds the lexemes of one document (ts_accum in tsvector_op.c)
+    pub fn add(&mut self, vector: &PgTsVector) {
+        for lexeme in &vector.words {
+            let entries = if self.weights.is_empty() {
+                // a lexeme without positions still occurs once
+                lexeme.positions.len().max(1)
+            } else {
+                lexeme
+                    .positions
+                    .iter()
+                    .filter(|position| self.weights.contains(position.weight.into()))
+                    .count()
+            };
+            if entries == 0 {
+                continue;
+            }
+
+            let stat = self.words.entry(lexeme.word.clone()).or_default();
+            stat.ndoc += 1;
+            stat.nentry += entries as u32;
+        }
+    }
+
+    pub fn get(&self, word: &str) -> Option<LexemeStat> {
+        self.words.get(word).copied()
+    }
+
+    /// Every lexeme with its counts, in the order of the lexemes
+    pub fn iter(&self) -> impl Iterator<Item = (&str, LexemeStat)> {
+        self.words.iter().map(|(word, stat)| (word.as_str(), *stat))
+    }
+
+    /// Number of distinct lexemes
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+}
+
+impl<'a> Extend<&'a PgTsVector> for TsStat {
+    fn extend<I: IntoIterator<Item = &'a PgTsVector>>(&mut self, iter: I) {
+        for vector in iter {
+            self.add(vector);
+        }
+    }
+}
+
+impl Extend<PgTsVector> for TsStat {
+    fn extend<I: IntoIterator<Item = PgTsVector>>(&mut self, iter: I) {
+        for vector in iter {
+            self.add(&vector);
+        }
+    }
+}
+
+impl<'a> FromIterator<&'a PgTsVector> for TsStat {
+    fn from_iter<I: IntoIterator<Item = &'a PgTsVector>>(iter: I) -> Self {
+        let mut stat = TsStat::new();
+        stat.extend(iter);
+        stat
+    }
+}
+
+impl FromIterator<PgTsVector> for TsStat {
+    fn from_iter<I: IntoIterator<Item = PgTsVector>>(iter: I) -> Self {
+        let mut stat = TsStat::new();
+        stat.extend(iter);
+        stat
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LexemeStat, TsStat};
+    use crate::pg_tsquery_builder::Weights;
+    use crate::pg_tsvector::PgTsVector;
+    use std::str::FromStr;
+
+    const DOCUMENTS: &[&str] = &[
+        "fat:1A,2B,3 cat:4C",
+        "fat:1 rat:2A,3A dog",
+        "cat dog:5B rat",
+        "bird:1D",
+    ];
+
+    // (word, ndoc, nentry)
+    type Row = (&'static str, u32, u32);
+
+    // (weights, rows of ts_stat('SELECT v FROM docs', weights) ORDER BY word) captured
+    // from PostgreSQL 15, without weights for the first one
+    const STATS: &[(&str, &[Row])] = &[
+        (
+            "",
+            &[
+                ("bird", 1, 1),
+                ("cat", 2, 2),
+                ("dog", 2, 2),
+                ("fat", 2, 4),
+                ("rat", 2, 3),
+            ],
+        ),
+        ("ab", &[("dog", 1, 1), ("fat", 1, 2), ("rat", 1, 2)]),
+        ("d", &[("bird", 1, 1), ("fat", 2, 2)]),
+        ("cA", &[("cat", 1, 1), ("fat", 1, 1), ("rat", 1, 2)]),
+        // lexemes without positions are skipped once there are weights
+        (
+            "abcd",
+            &[
+                ("bird", 1, 1),
+                ("cat", 1, 1),
+                ("dog", 1, 1),
+                ("fat", 2, 4),
+                ("rat", 1, 2),
+            ],
+        ),
+    ];
+
+    #[test]
+    fn counts_like_the_server() {
+        let documents: Vec<PgTsVector> = DOCUMENTS
+            .iter()
+            .map(|document| PgTsVector::from_str(document).unwrap())
+            .collect();
+
+        for (weights, expected) in STATS {
+            let mut stat = TsStat::with_weights(Weights::from_str(weights).unwrap());
+            stat.extend(&documents);
+
+            let rows: Vec<(&str, LexemeStat)> = stat.iter().collect();
+            let expected: Vec<(&str, LexemeStat)> = expected
+                .iter()
+                .map(|(word, ndoc, nentry)| {
+                    let stat = LexemeStat {
+                        ndoc: *ndoc,
+                        nentry: *nentry,
+                    };
+                    (*word, stat)
+                })
+                .collect();
+            assert_eq!(rows, expected, "{weights}");
+        }
+    }
+}
@@ -0,0 +1,1555 @@
+use std::fmt;
+
+use unicode_properties::{GeneralCategory, UnicodeGeneralCategory};
+
+/// Token types of the server's default text search parser, as listed by
+/// `ts_token_type('default')`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum TokenType {
+    AsciiWord = 1,
+    Word = 2,
+    NumWord = 3,
+    Email = 4,
+    Url = 5,
+    Host = 6,
+    SFloat = 7,
+    Version = 8,
+    HWordNumPart = 9,
+    HWordPart = 10,
+    HWordAsciiPart = 11,
+    Blank = 12,
+    Tag = 13,
+    Protocol = 14,
+    NumHWord = 15,
+    AsciiHWord = 16,
+    HWord = 17,
+    UrlPath = 18,
+    File = 19,
+    Float = 20,
+    Int = 21,
+    UInt = 22,
+    Entity = 23,
+}
+
+impl TokenType {
+    /// Every token type in the order of their ids
+    pub const ALL: [TokenType; 23] = [
+        TokenType::AsciiWord,
+        TokenType::Word,
+        TokenType::NumWord,
+        TokenType::Email,
+        TokenType::Url,
+        TokenType::Host,
+        TokenType::SFloat,
+        TokenType::Version,
+        TokenType::HWordNumPart,
+        TokenType::HWordPart,
+        TokenType::HWordAsciiPart,
+        TokenType::Blank,
+        TokenType::Tag,
+        TokenType::Protocol,
+        TokenType::NumHWord,
+        TokenType::AsciiHWord,
+        TokenType::HWord,
+        TokenType::UrlPath,
+        TokenType::File,
+        TokenType::Float,
+        TokenType::Int,
+        TokenType::UInt,
+        TokenType::Entity,
+    ];
+
+    /// Name used in `ALTER TEXT SEARCH CONFIGURATION ... ADD MAPPING FOR`
+    pub fn alias(self) -> &'static str {
+        match self {
+            TokenType::AsciiWord => "asciiword",
+            TokenType::Word => "word",
+            TokenType::NumWord => "numword",
+            TokenType::Email => "email",
+            TokenType::Url => "url",
+            TokenType::Host => "host",
+            TokenType::SFloat => "sfloat",
+            TokenType::Version => "version",
+            TokenType::HWordNumPart => "hword_numpart",
+            TokenType::HWordPart => "hword_part",
+            TokenType::HWordAsciiPart => "hword_asciipart",
+            TokenType::Blank => "blank",
+            TokenType::Tag => "tag",
+            TokenType::Protocol => "protocol",
+            TokenType::NumHWord => "numhword",
+            TokenType::AsciiHWord => "asciihword",
+            TokenType::HWord => "hword",
+            TokenType::UrlPath => "url_path",
+            TokenType::File => "file",
+            TokenType::Float => "float",
+            TokenType::Int => "int",
+            TokenType::UInt => "uint",
+            TokenType::Entity => "entity",
+        }
+    }
+}
+
+impl fmt::Display for TokenType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.alias())
+    }
+}
+
+/// A piece of the document, like a row of `ts_debug`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenType,
+    pub text: String,
+}
+
+/// Splits a document into tokens, the counterpart of a text search parser
+pub trait TextParser: Send + Sync {
+    fn parse(&self, text: &str) -> Vec<Token>;
+}
+
+/// The server's `default` parser (wparser_def.c).
+///
+/// Recognizes words and hyphenated words along with their parts, numbers, versions,
+/// e-mail addresses, hosts, URLs, file paths, XML tags and entities. Everything else is
+/// returned as `blank`. This is a port of the server's state machine, so it agrees with
+/// `ts_debug` on odd input like `-1a` or `b.a_b` too. Letters are what Unicode considers
+/// alphabetic plus digits other than `0-9`, which is how the C library of a UTF8 database
+/// sees them.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultParser;
+
+impl TextParser for DefaultParser {
+    fn parse(&self, text: &str) -> Vec<Token> {
+        let mut parser = Parser::new(text);
+        let mut tokens = Vec::new();
+
+        while let Some(kind) = parser.next_token() {
+            tokens.push(Token {
+                kind,
+                text: parser.token().to_string(),
+            });
+        }
+
+        tokens
+    }
+}
+
+fn is_alpha(c: char) -> bool {
+    c.is_alphabetic() || (!c.is_ascii() && c.general_category() == GeneralCategory::DecimalNumber)
+}
+
+fn is_alnum(c: char) -> bool {
+    is_alpha(c) || c.is_ascii_digit()
+}
+
+// iswspace leaves out the no-break spaces
+fn is_space(c: char) -> bool {
+    c.is_whitespace() && !matches!(c, '\u{85}' | '\u{a0}' | '\u{2007}' | '\u{202f}')
+}
+
+// Marks of zero width and some spacing marks, which don't break a word (p_isspecial)
+fn is_special(c: char) -> bool {
+    matches!(
+        c.general_category(),
+        GeneralCategory::NonspacingMark | GeneralCategory::EnclosingMark
+    ) || STRANGE_LETTERS.binary_search(&u32::from(c)).is_ok()
+}
+
+// Characters allowed in URLs by RFC 3986
+fn is_url_char(c: char) -> bool {
+    c.is_ascii_graphic() && !matches!(c, '"' | '<' | '>' | '\\' | '^' | '`' | '{' | '|' | '}')
+}
+
+/// States of the parser (TParserState), `Null` keeps the current one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Base,
+    InNumWord,
+    InAsciiWord,
+    InWord,
+    InUnsignedInt,
+    InSignedIntFirst,
+    InSignedInt,
+    InSpace,
+    InUDecimalFirst,
+    InUDecimal,
+    InDecimalFirst,
+    InDecimal,
+    InVerVersion,
+    InSVerVersion,
+    InVersionFirst,
+    InVersion,
+    InMantissaFirst,
+    InMantissaSign,
+    InMantissa,
+    InXmlEntityFirst,
+    InXmlEntity,
+    InXmlEntityNumFirst,
+    InXmlEntityNum,
+    InXmlEntityHexNumFirst,
+    InXmlEntityHexNum,
+    InXmlEntityEnd,
+    InTagFirst,
+    InXmlBegin,
+    InTagCloseFirst,
+    InTagName,
+    InTagBeginEnd,
+    InTag,
+    InTagEscapeK,
+    InTagEscapeKK,
+    InTagBackSleshed,
+    InTagEnd,
+    InCommentFirst,
+    InCommentLast,
+    InComment,
+    InCloseCommentFirst,
+    InCloseCommentLast,
+    InCommentEnd,
+    InHostFirstDomain,
+    InHostDomainSecond,
+    InHostDomain,
+    InPortFirst,
+    InPort,
+    InHostFirstAn,
+    InHost,
+    InEmail,
+    InFileFirst,
+    InFileTwiddle,
+    InPathFirst,
+    InPathFirstFirst,
+    InPathSecond,
+    InFile,
+    InFileNext,
+    InUrlPathFirst,
+    InUrlPathStart,
+    InUrlPath,
+    InFurl,
+    InProtocolFirst,
+    InProtocolSecond,
+    InProtocolEnd,
+    InHyphenAsciiWordFirst,
+    InHyphenAsciiWord,
+    InHyphenWordFirst,
+    InHyphenWord,
+    InHyphenNumWordFirst,
+    InHyphenNumWord,
+    InHyphenDigitLookahead,
+    InParseHyphen,
+    InParseHyphenHyphen,
+    InHyphenWordPart,
+    InHyphenAsciiWordPart,
+    InHyphenNumWordPart,
+    InHyphenUnsignedInt,
+    Null,
+}
+
+/// What an action tests the current character for, `Any` always matches
+#[derive(Debug, Clone, Copy)]
+enum Class {
+    Eof,
+    Eq(u8),
+    Alnum,
+    NotAlnum,
+    Alpha,
+    Digit,
+    Space,
+    XDigit,
+    AsciiLetter,
+    Special,
+    UrlChar,
+    Ignore,
+    StopHost,
+    // a host or URL path starts here, which is taken as a whole
+    Host,
+    UrlPath,
+    Any,
+}
+
+/// Special handlers of the server, run before the flags are looked at
+#[derive(Debug, Clone, Copy)]
+enum Handler {
+    Tags,
+    FUrl,
+    Hyphen,
+    VerVersion,
+}
+
+// Flags of an action, a token is found on BINGO. PUSH saves the position to get back to
+// with POP, CLEAR drops the last saved position and CLRALL all of them; MERGE drops it
+// but keeps the current place. RERUN tests the same character again in the new state.
+const BINGO: u8 = 1;
+const POP: u8 = 2;
+const PUSH: u8 = 4;
+const RERUN: u8 = 8;
+const CLEAR: u8 = 16;
+const MERGE: u8 = 32;
+const CLRALL: u8 = 64;
+
+/// The first action of a state whose class matches is taken (TParserStateActionItem)
+#[derive(Debug, Clone, Copy)]
+struct Action {
+    class: Class,
+    flags: u8,
+    to: State,
+    kind: Option<TokenType>,
+    handler: Option<Handler>,
+}
+
+const fn act(class: Class, flags: u8, to: State) -> Action {
+    Action {
+        class,
+        flags,
+        to,
+        kind: None,
+        handler: None,
+    }
+}
+
+impl Action {
+    const fn token(self, kind: TokenType) -> Action {
+        Action {
+            kind: Some(kind),
+            ..self
+        }
+    }
+
+    const fn handler(self, handler: Handler) -> Action {
+        Action {
+            handler: Some(handler),
+            ..self
+        }
+    }
+}
+
+/// Where the parser is in the text (TParserPosition)
+#[derive(Debug, Clone, Copy)]
+struct Position {
+    pos: usize,
+    char_len: usize,
+    // length of the token so far
+    token_len: usize,
+    state: State,
+    // the action that saved this position, the parser goes on with the next one once it
+    // gets back here
+    pushed_at: Option<usize>,
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    // saved positions, the current one last
+    stack: Vec<Position>,
+    // inside <script> or <style>, where everything is blank
+    ignore: bool,
+    // stop after the host, for the host of an URL or e-mail address
+    want_host: bool,
+    token_start: usize,
+    token_len: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str) -> Self {
+        Parser {
+            text,
+            stack: vec![Position {
+                pos: 0,
+                char_len: 0,
+                token_len: 0,
+                state: State::Base,
+                pushed_at: None,
+            }],
+            ignore: false,
+            want_host: false,
+            token_start: 0,
+            token_len: 0,
+        }
+    }
+
+    fn current(&self) -> &Position {
+        self.stack.last().expect("the parser always has a position")
+    }
+
+    fn current_mut(&mut self) -> &mut Position {
+        self.stack
+            .last_mut()
+            .expect("the parser always has a position")
+    }
+
+    /// Text of the last token found
+    fn token(&self) -> &'a str {
+        &self.text[self.token_start..self.token_start + self.token_len]
+    }
+
+    /// Runs the state machine up to the next token (TParserGet)
+    fn next_token(&mut self) -> Option<TokenType> {
+        let text = self.text;
+        if self.current().pos >= text.len() {
+            return None;
+        }
+
+        self.token_start = self.current().pos;
+        self.current_mut().pushed_at = None;
+
+        loop {
+            let position = self.current_mut();
+            position.char_len = text[position.pos..]
+                .chars()
+                .next()
+                .map_or(0, char::len_utf8);
+
+            let actions = ACTIONS[position.state as usize];
+            let mut index = position.pushed_at.take().map_or(0, |index| index + 1);
+            while !self.test(actions[index].class) {
+                index += 1;
+            }
+            let action = actions[index];
+
+            if let Some(handler) = action.handler {
+                self.handle(handler);
+            }
+
+            if action.flags & BINGO != 0 {
+                let position = self.current_mut();
+                self.token_len = std::mem::take(&mut position.token_len);
+            }
+
+            if action.flags & POP != 0 {
+                self.stack.pop();
+            } else if action.flags & PUSH != 0 {
+                let position = self.current_mut();
+                position.pushed_at = Some(index);
+                let pushed = Position {
+                    pushed_at: None,
+                    ..*position
+                };
+                self.stack.push(pushed);
+            } else if action.flags & CLEAR != 0 {
+                self.stack.remove(self.stack.len() - 2);
+            } else if action.flags & CLRALL != 0 {
+                let saved = self.stack.len() - 1;
+                self.stack.drain(..saved);
+            } else if action.flags & MERGE != 0 {
+                let merged = self.stack.pop().expect("MERGE needs a saved position");
+                let position = self.current_mut();
+                position.pos = merged.pos;
+                position.char_len = merged.char_len;
+                position.token_len = merged.token_len;
+            }
+
+            if action.to != State::Null {
+                self.current_mut().state = action.to;
+            }
+
+            if action.flags & BINGO != 0 {
+                return action.kind;
+            }
+            if self.current().pos >= text.len() && action.flags & RERUN == 0 {
+                return None;
+            }
+            if action.flags & (RERUN | POP) != 0 {
+                continue;
+            }
+
+            let position = self.current_mut();
+            position.pos += position.char_len;
+            position.token_len += position.char_len;
+        }
+    }
+
+    fn test(&mut self, class: Class) -> bool {
+        let position = *self.current();
+        let c = self.text[position.pos..].chars().next();
+
+        match class {
+            Class::Eof => position.pos == self.text.len() || position.char_len == 0,
+            Class::Eq(byte) => position.char_len == 1 && self.text.as_bytes()[position.pos] == byte,
+            Class::Alnum => c.is_some_and(is_alnum),
+            Class::NotAlnum => !c.is_some_and(is_alnum),
+            Class::Alpha => c.is_some_and(is_alpha),
+            Class::Digit => c.is_some_and(|c| c.is_ascii_digit()),
+            Class::Space => c.is_some_and(is_space),
+            Class::XDigit => c.is_some_and(|c| c.is_ascii_hexdigit()),
+            Class::AsciiLetter => c.is_some_and(|c| c.is_ascii_alphabetic()),
+            // the end of the text has no width either
+            Class::Special => c.is_none_or(is_special),
+            Class::UrlChar => c.is_some_and(is_url_char),
+            Class::Ignore => self.ignore,
+            Class::StopHost => std::mem::take(&mut self.want_host),
+            Class::Host => {
+                let mut parser = Parser::new(&self.text[position.pos..]);
+                parser.want_host = true;
+                self.skip(parser, TokenType::Host)
+            }
+            Class::UrlPath => {
+                let mut parser = Parser::new(&self.text[position.pos..]);
+                parser.stack.push(Position {
+                    state: State::InUrlPathFirst,
+                    ..parser.stack[0]
+                });
+                self.skip(parser, TokenType::UrlPath)
+            }
+            Class::Any => true,
+        }
+    }
+
+    // Adds the first token of a parser that starts at the current position to the
+    // current one, if it has the right type (p_ishost and p_isURLPath)
+    fn skip(&mut self, mut parser: Parser<'_>, kind: TokenType) -> bool {
+        if parser.next_token() != Some(kind) {
+            return false;
+        }
+
+        let char_len = parser.current().char_len;
+        let position = self.current_mut();
+        position.pos += parser.token_len;
+        position.token_len += parser.token_len;
+        position.char_len = char_len;
+        true
+    }
+
+    fn handle(&mut self, handler: Handler) {
+        match handler {
+            // <script> and <style> start ignoring everything up to their closing tag
+            Handler::Tags => {
+                let end = self.token_start + self.current().token_len;
+                let token = &self.text[self.token_start..end];
+                match token.chars().count() {
+                    8 if token.eq_ignore_ascii_case("</script") => self.ignore = false,
+                    7 if token.eq_ignore_ascii_case("</style") => self.ignore = false,
+                    7 if token.eq_ignore_ascii_case("<script") => self.ignore = true,
+                    6 if token.eq_ignore_ascii_case("<style") => self.ignore = true,
+                    _ => {}
+                }
+            }
+            // the host and path of an URL are tokens of their own as well
+            Handler::FUrl => {
+                self.want_host = true;
+                self.rewind();
+            }
+            // as are the parts of a hyphenated word
+            Handler::Hyphen => self.rewind(),
+            // `1.2.3` is a version and not a decimal followed by `.3`
+            Handler::VerVersion => {
+                self.rewind();
+                self.current_mut().token_len = 0;
+            }
+        }
+    }
+
+    // Back to the start of the token, to parse it again
+    fn rewind(&mut self) {
+        let position = self.current_mut();
+        position.pos -= position.token_len;
+    }
+}
+
+/// Actions of every state in the order of `State` (actionTPS_* in wparser_def.c)
+static ACTIONS: [&[Action]; 77] = {
+    use Class::*;
+    use State::*;
+
+    [
+        // Base
+        &[
+            act(Eof, 0, Null),
+            act(Eq(b'<'), PUSH, InTagFirst),
+            act(Ignore, 0, InSpace),
+            act(AsciiLetter, 0, InAsciiWord),
+            act(Alpha, 0, InWord),
+            act(Digit, 0, InUnsignedInt),
+            act(Eq(b'-'), PUSH, InSignedIntFirst),
+            act(Eq(b'+'), PUSH, InSignedIntFirst),
+            act(Eq(b'&'), PUSH, InXmlEntityFirst),
+            act(Eq(b'~'), PUSH, InFileTwiddle),
+            act(Eq(b'/'), PUSH, InFileFirst),
+            act(Eq(b'.'), PUSH, InPathFirstFirst),
+            act(Any, 0, InSpace),
+        ],
+        // InNumWord
+        &[
+            act(Eof, BINGO, Base).token(TokenType::NumWord),
+            act(Alnum, 0, InNumWord),
+            act(Special, 0, InNumWord),
+            act(Eq(b'@'), PUSH, InEmail),
+            act(Eq(b'/'), PUSH, InFileFirst),
+            act(Eq(b'.'), PUSH, InFileNext),
+            act(Eq(b'-'), PUSH, InHyphenNumWordFirst),
+            act(Any, BINGO, Base).token(TokenType::NumWord),
+        ],
+        // InAsciiWord
+        &[
+            act(Eof, BINGO, Base).token(TokenType::AsciiWord),
+            act(AsciiLetter, 0, Null),
+            act(Eq(b'.'), PUSH, InHostFirstDomain),
+            act(Eq(b'.'), PUSH, InFileNext),
+            act(Eq(b'-'), PUSH, InHostFirstAn),
+            act(Eq(b'-'), PUSH, InHyphenAsciiWordFirst),
+            act(Eq(b'_'), PUSH, InHostFirstAn),
+            act(Eq(b'@'), PUSH, InEmail),
+            act(Eq(b':'), PUSH, InProtocolFirst),
+            act(Eq(b'/'), PUSH, InFileFirst),
+            act(Digit, PUSH, InHost),
+            act(Digit, 0, InNumWord),
+            act(Alpha, 0, InWord),
+            act(Special, 0, InWord),
+            act(Any, BINGO, Base).token(TokenType::AsciiWord),
+        ],
+        // InWord
+        &[
+            act(Eof, BINGO, Base).token(TokenType::Word),
+            act(Alpha, 0, Null),
+            act(Special, 0, Null),
+            act(Digit, 0, InNumWord),
+            act(Eq(b'-'), PUSH, InHyphenWordFirst),
+            act(Any, BINGO, Base).token(TokenType::Word),
+        ],
+        // InUnsignedInt
+        &[
+            act(Eof, BINGO, Base).token(TokenType::UInt),
+            act(Digit, 0, Null),
+            act(Eq(b'.'), PUSH, InHostFirstDomain),
+            act(Eq(b'.'), PUSH, InUDecimalFirst),
+            act(Eq(b'e'), PUSH, InMantissaFirst),
+            act(Eq(b'E'), PUSH, InMantissaFirst),
+            act(Eq(b'-'), PUSH, InHostFirstAn),
+            act(Eq(b'_'), PUSH, InHostFirstAn),
+            act(Eq(b'@'), PUSH, InEmail),
+            act(AsciiLetter, PUSH, InHost),
+            act(Alpha, 0, InNumWord),
+            act(Special, 0, InNumWord),
+            act(Eq(b'/'), PUSH, InFileFirst),
+            act(Any, BINGO, Base).token(TokenType::UInt),
+        ],
+        // InSignedIntFirst
+        &[
+            act(Eof, POP, Null),
+            act(Digit, CLEAR, InSignedInt),
+            act(Any, POP, Null),
+        ],
+        // InSignedInt
+        &[
+            act(Eof, BINGO, Base).token(TokenType::Int),
+            act(Digit, 0, Null),
+            act(Eq(b'.'), PUSH, InDecimalFirst),
+            act(Eq(b'e'), PUSH, InMantissaFirst),
+            act(Eq(b'E'), PUSH, InMantissaFirst),
+            act(Any, BINGO, Base).token(TokenType::Int),
+        ],
+        // InSpace
+        &[
+            act(Eof, BINGO, Base).token(TokenType::Blank),
+            act(Eq(b'<'), BINGO, Base).token(TokenType::Blank),
+            act(Ignore, 0, Null),
+            act(Eq(b'-'), BINGO, Base).token(TokenType::Blank),
+            act(Eq(b'+'), BINGO, Base).token(TokenType::Blank),
+            act(Eq(b'&'), BINGO, Base).token(TokenType::Blank),
+            act(Eq(b'/'), BINGO, Base).token(TokenType::Blank),
+            act(NotAlnum, 0, InSpace),
+            act(Any, BINGO, Base).token(TokenType::Blank),
+        ],
+        // InUDecimalFirst
+        &[
+            act(Eof, POP, Null),
+            act(Digit, CLEAR, InUDecimal),
+            act(Any, POP, Null),
+        ],
+        // InUDecimal
+        &[
+            act(Eof, BINGO, Base).token(TokenType::Float),
+            act(Digit, 0, InUDecimal),
+            act(Eq(b'.'), PUSH, InVersionFirst),
+            act(Eq(b'e'), PUSH, InMantissaFirst),
+            act(Eq(b'E'), PUSH, InMantissaFirst),
+            act(Any, BINGO, Base).token(TokenType::Float),
+        ],
+        // InDecimalFirst
+        &[
+            act(Eof, POP, Null),
+            act(Digit, CLEAR, InDecimal),
+            act(Any, POP, Null),
+        ],
+        // InDecimal
+        &[
+            act(Eof, BINGO, Base).token(TokenType::Float),
+            act(Digit, 0, InDecimal),
+            act(Eq(b'.'), PUSH, InVerVersion),
+            act(Eq(b'e'), PUSH, InMantissaFirst),
+            act(Eq(b'E'), PUSH, InMantissaFirst),
+            act(Any, BINGO, Base).token(TokenType::Float),
+        ],
+        // InVerVersion
+        &[
+            act(Eof, POP, Null),
+            act(Digit, RERUN, InSVerVersion).handler(Handler::VerVersion),
+            act(Any, POP, Null),
+        ],
+        // InSVerVersion
+        &[
+            act(Eof, POP, Null),
+            act(Digit, BINGO | CLRALL, InUnsignedInt).token(TokenType::Blank),
+            act(Any, 0, Null),
+        ],
+        // InVersionFirst
+        &[
+            act(Eof, POP, Null),
+            act(Digit, CLEAR, InVersion),
+            act(Any, POP, Null),
+        ],
+        // InVersion
+        &[
+            act(Eof, BINGO, Base).token(TokenType::Version),
+            act(Digit, 0, InVersion),
+            act(Eq(b'.'), PUSH, InVersionFirst),
+            act(Any, BINGO, Base).token(TokenType::Version),
+        ],
+        // InMantissaFirst
+        &[
+            act(Eof, POP, Null),
+            act(Digit, CLEAR, InMantissa),
+            act(Eq(b'+'), 0, InMantissaSign),
+            act(Eq(b'-'), 0, InMantissaSign),
+            act(Any, POP, Null),
+        ],
+        // InMantissaSign
+        &[
+            act(Eof, POP, Null),
+            act(Digit, CLEAR, InMantissa),
+            act(Any, POP, Null),
+        ],
+        // InMantissa
+        &[
+            act(Eof, BINGO, Base).token(TokenType::SFloat),
+            act(Digit, 0, InMantissa),
+            act(Any, BINGO, Base).token(TokenType::SFloat),
+        ],
+        // InXmlEntityFirst
+        &[
+            act(Eof, POP, Null),
+            act(Eq(b'#'), 0, InXmlEntityNumFirst),
+            act(AsciiLetter, 0, InXmlEntity),
+            act(Eq(b':'), 0, InXmlEntity),
+            act(Eq(b'_'), 0, InXmlEntity),
+            act(Any, POP, Null),
+        ],
+        // InXmlEntity
+        &[
+            act(Eof, POP, Null),
+            act(Alnum, 0, InXmlEntity),
+            act(Eq(b':'), 0, InXmlEntity),
+            act(Eq(b'_'), 0, InXmlEntity),
+            act(Eq(b'.'), 0, InXmlEntity),
+            act(Eq(b'-'), 0, InXmlEntity),
+            act(Eq(b';'), 0, InXmlEntityEnd),
+            act(Any, POP, Null),
+        ],
+        // InXmlEntityNumFirst
+        &[
+            act(Eof, POP, Null),
+            act(Eq(b'x'), 0, InXmlEntityHexNumFirst),
+            act(Eq(b'X'), 0, InXmlEntityHexNumFirst),
+            act(Digit, 0, InXmlEntityNum),
+            act(Any, POP, Null),
+        ],
+        // InXmlEntityNum
+        &[
+            act(Eof, POP, Null),
+            act(Digit, 0, InXmlEntityNum),
+            act(Eq(b';'), 0, InXmlEntityEnd),
+            act(Any, POP, Null),
+        ],
+        // InXmlEntityHexNumFirst
+        &[
+            act(Eof, POP, Null),
+            act(XDigit, 0, InXmlEntityHexNum),
+            act(Any, POP, Null),
+        ],
+        // InXmlEntityHexNum
+        &[
+            act(Eof, POP, Null),
+            act(XDigit, 0, InXmlEntityHexNum),
+            act(Eq(b';'), 0, InXmlEntityEnd),
+            act(Any, POP, Null),
+        ],
+        // InXmlEntityEnd
+        &[act(Any, BINGO | CLEAR, Base).token(TokenType::Entity)],
+        // InTagFirst
+        &[
+            act(Eof, POP, Null),
+            act(Eq(b'/'), PUSH, InTagCloseFirst),
+            act(Eq(b'!'), PUSH, InCommentFirst),
+            act(Eq(b'?'), PUSH, InXmlBegin),
+            act(AsciiLetter, PUSH, InTagName),
+            act(Eq(b':'), PUSH, InTagName),
+            act(Eq(b'_'), PUSH, InTagName),
+            act(Any, POP, Null),
+        ],
+        // InXmlBegin
+        &[
+            act(Eof, POP, Null),
+            act(Eq(b'x'), 0, InTag),
+            act(Any, POP, Null),
+        ],
+        // InTagCloseFirst
+        &[
+            act(Eof, POP, Null),
+            act(AsciiLetter, 0, InTagName),
+            act(Any, POP, Null),
+        ],
+        // InTagName
+        &[
+            act(Eof, POP, Null),
+            act(Eq(b'/'), 0, InTagBeginEnd),
+            act(Eq(b'>'), 0, InTagEnd).handler(Handler::Tags),
+            act(Space, 0, InTag).handler(Handler::Tags),
+            act(Alnum, 0, Null),
+            act(Eq(b':'), 0, Null),
+            act(Eq(b'_'), 0, Null),
+            act(Eq(b'.'), 0, Null),
+            act(Eq(b'-'), 0, Null),
+            act(Any, POP, Null),
+        ],
+        // InTagBeginEnd
+        &[
+            act(Eof, POP, Null),
+            act(Eq(b'>'), 0, InTagEnd),
+            act(Any, POP, Null),
+        ],
+        // InTag
+        &[
+            act(Eof, POP, Null),
+            act(Eq(b'>'), 0, InTagEnd).handler(Handler::Tags),
+            act(Eq(b'\''), 0, InTagEscapeK),
+            act(Eq(b'"'), 0, InTagEscapeKK),
+            act(AsciiLetter, 0, Null),
+            act(Digit, 0, Null),
+            act(Eq(b'='), 0, Null),
+            act(Eq(b'-'), 0, Null),
+            act(Eq(b'_'), 0, Null),
+            act(Eq(b'#'), 0, Null),
+            act(Eq(b'/'), 0, Null),
+            act(Eq(b':'), 0, Null),
+            act(Eq(b'.'), 0, Null),
+            act(Eq(b'&'), 0, Null),
+            act(Eq(b'?'), 0, Null),
+            act(Eq(b'%'), 0, Null),
+            act(Eq(b'~'), 0, Null),
+            act(Space, 0, Null).handler(Handler::Tags),
+            act(Any, POP, Null),
+        ],
+        // InTagEscapeK
+        &[
+            act(Eof, POP, Null),
+            act(Eq(b'\\'), PUSH, InTagBackSleshed),
+            act(Eq(b'\''), 0, InTag),
+            act(Any, 0, InTagEscapeK),
+        ],
+        // InTagEscapeKK
+        &[
+            act(Eof, POP, Null),
+            act(Eq(b'\\'), PUSH, InTagBackSleshed),
+            act(Eq(b'"'), 0, InTag),
+            act(Any, 0, InTagEscapeKK),
+        ],
+        // InTagBackSleshed
+        &[act(Eof, POP, Null), act(Any, MERGE, Null)],
+        // InTagEnd
+        &[act(Any, BINGO | CLRALL, Base).token(TokenType::Tag)],
+        // InCommentFirst
+        &[
+            act(Eof, POP, Null),
+            act(Eq(b'-'), 0, InCommentLast),
+            act(Eq(b'D'), 0, InTag),
+            act(Eq(b'd'), 0, InTag),
+            act(Any, POP, Null),
+        ],
+        // InCommentLast
+        &[
+            act(Eof, POP, Null),
+            act(Eq(b'-'), 0, InComment),
+            act(Any, POP, Null),
+        ],
+        // InComment
+        &[
+            act(Eof, POP, Null),
+            act(Eq(b'-'), 0, InCloseCommentFirst),
+            act(Any, 0, Null),
+        ],
+        // InCloseCommentFirst
+        &[
+            act(Eof, POP, Null),
+            act(Eq(b'-'), 0, InCloseCommentLast),
+            act(Any, 0, InComment),
+        ],
+        // InCloseCommentLast
+        &[
+            act(Eof, POP, Null),
+            act(Eq(b'-'), 0, Null),
+            act(Eq(b'>'), 0, InCommentEnd),
+            act(Any, 0, InComment),
+        ],
+        // InCommentEnd
+        &[act(Any, BINGO | CLRALL, Base).token(TokenType::Tag)],
+        // InHostFirstDomain
+        &[
+            act(Eof, POP, Null),
+            act(AsciiLetter, 0, InHostDomainSecond),
+            act(Digit, 0, InHost),
+            act(Any, POP, Null),
+        ],
+        // InHostDomainSecond
+        &[
+            act(Eof, POP, Null),
+            act(AsciiLetter, 0, InHostDomain),
+            act(Digit, PUSH, InHost),
+            act(Eq(b'-'), PUSH, InHostFirstAn),
+            act(Eq(b'_'), PUSH, InHostFirstAn),
+            act(Eq(b'.'), PUSH, InHostFirstDomain),
+            act(Eq(b'@'), PUSH, InEmail),
+            act(Any, POP, Null),
+        ],
+        // InHostDomain
+        &[
+            act(Eof, BINGO | CLRALL, Base).token(TokenType::Host),
+            act(AsciiLetter, 0, InHostDomain),
+            act(Digit, PUSH, InHost),
+            act(Eq(b':'), PUSH, InPortFirst),
+            act(Eq(b'-'), PUSH, InHostFirstAn),
+            act(Eq(b'_'), PUSH, InHostFirstAn),
+            act(Eq(b'.'), PUSH, InHostFirstDomain),
+            act(Eq(b'@'), PUSH, InEmail),
+            act(Digit, POP, Null),
+            act(StopHost, BINGO | CLRALL, InUrlPathStart).token(TokenType::Host),
+            act(Eq(b'/'), PUSH, InFurl),
+            act(Any, BINGO | CLRALL, Base).token(TokenType::Host),
+        ],
+        // InPortFirst
+        &[
+            act(Eof, POP, Null),
+            act(Digit, 0, InPort),
+            act(Any, POP, Null),
+        ],
+        // InPort
+        &[
+            act(Eof, BINGO | CLRALL, Base).token(TokenType::Host),
+            act(Digit, 0, InPort),
+            act(StopHost, BINGO | CLRALL, InUrlPathStart).token(TokenType::Host),
+            act(Eq(b'/'), PUSH, InFurl),
+            act(Any, BINGO | CLRALL, Base).token(TokenType::Host),
+        ],
+        // InHostFirstAn
+        &[
+            act(Eof, POP, Null),
+            act(Digit, 0, InHost),
+            act(AsciiLetter, 0, InHost),
+            act(Any, POP, Null),
+        ],
+        // InHost
+        &[
+            act(Eof, POP, Null),
+            act(Digit, 0, InHost),
+            act(AsciiLetter, 0, InHost),
+            act(Eq(b'@'), PUSH, InEmail),
+            act(Eq(b'.'), PUSH, InHostFirstDomain),
+            act(Eq(b'-'), PUSH, InHostFirstAn),
+            act(Eq(b'_'), PUSH, InHostFirstAn),
+            act(Any, POP, Null),
+        ],
+        // InEmail
+        &[
+            act(StopHost, POP, Null),
+            act(Host, BINGO | CLRALL, Base).token(TokenType::Email),
+            act(Any, POP, Null),
+        ],
+        // InFileFirst
+        &[
+            act(Eof, POP, Null),
+            act(AsciiLetter, 0, InFile),
+            act(Digit, 0, InFile),
+            act(Eq(b'.'), 0, InPathFirst),
+            act(Eq(b'_'), 0, InFile),
+            act(Eq(b'~'), PUSH, InFileTwiddle),
+            act(Any, POP, Null),
+        ],
+        // InFileTwiddle
+        &[
+            act(Eof, POP, Null),
+            act(AsciiLetter, 0, InFile),
+            act(Digit, 0, InFile),
+            act(Eq(b'_'), 0, InFile),
+            act(Eq(b'/'), 0, InFileFirst),
+            act(Any, POP, Null),
+        ],
+        // InPathFirst
+        &[
+            act(Eof, POP, Null),
+            act(AsciiLetter, 0, InFile),
+            act(Digit, 0, InFile),
+            act(Eq(b'_'), 0, InFile),
+            act(Eq(b'.'), 0, InPathSecond),
+            act(Eq(b'/'), 0, InFileFirst),
+            act(Any, POP, Null),
+        ],
+        // InPathFirstFirst
+        &[
+            act(Eof, POP, Null),
+            act(Eq(b'.'), 0, InPathSecond),
+            act(Eq(b'/'), 0, InFileFirst),
+            act(Any, POP, Null),
+        ],
+        // InPathSecond
+        &[
+            act(Eof, BINGO | CLEAR, Base).token(TokenType::File),
+            act(Eq(b'/'), PUSH, InFileFirst),
+            act(Eq(b'/'), BINGO | CLEAR, Base).token(TokenType::File),
+            act(Space, BINGO | CLEAR, Base).token(TokenType::File),
+            act(Any, POP, Null),
+        ],
+        // InFile
+        &[
+            act(Eof, BINGO, Base).token(TokenType::File),
+            act(AsciiLetter, 0, InFile),
+            act(Digit, 0, InFile),
+            act(Eq(b'.'), PUSH, InFileNext),
+            act(Eq(b'_'), 0, InFile),
+            act(Eq(b'-'), 0, InFile),
+            act(Eq(b'/'), PUSH, InFileFirst),
+            act(Any, BINGO, Base).token(TokenType::File),
+        ],
+        // InFileNext
+        &[
+            act(Eof, POP, Null),
+            act(AsciiLetter, CLEAR, InFile),
+            act(Digit, CLEAR, InFile),
+            act(Eq(b'_'), CLEAR, InFile),
+            act(Any, POP, Null),
+        ],
+        // InUrlPathFirst
+        &[
+            act(Eof, POP, Null),
+            act(UrlChar, 0, InUrlPath),
+            act(Any, POP, Null),
+        ],
+        // InUrlPathStart
+        &[act(Any, 0, InUrlPath)],
+        // InUrlPath
+        &[
+            act(Eof, BINGO, Base).token(TokenType::UrlPath),
+            act(UrlChar, 0, InUrlPath),
+            act(Any, BINGO, Base).token(TokenType::UrlPath),
+        ],
+        // InFurl
+        &[
+            act(Eof, POP, Null),
+            act(UrlPath, BINGO | CLRALL, Base)
+                .token(TokenType::Url)
+                .handler(Handler::FUrl),
+            act(Any, POP, Null),
+        ],
+        // InProtocolFirst
+        &[
+            act(Eof, POP, Null),
+            act(Eq(b'/'), 0, InProtocolSecond),
+            act(Any, POP, Null),
+        ],
+        // InProtocolSecond
+        &[
+            act(Eof, POP, Null),
+            act(Eq(b'/'), 0, InProtocolEnd),
+            act(Any, POP, Null),
+        ],
+        // InProtocolEnd
+        &[act(Any, BINGO | CLRALL, Base).token(TokenType::Protocol)],
+        // InHyphenAsciiWordFirst
+        &[
+            act(Eof, POP, Null),
+            act(AsciiLetter, 0, InHyphenAsciiWord),
+            act(Alpha, 0, InHyphenWord),
+            act(Digit, 0, InHyphenDigitLookahead),
+            act(Any, POP, Null),
+        ],
+        // InHyphenAsciiWord
+        &[
+            act(Eof, BINGO | CLRALL, InParseHyphen)
+                .token(TokenType::AsciiHWord)
+                .handler(Handler::Hyphen),
+            act(AsciiLetter, 0, InHyphenAsciiWord),
+            act(Alpha, 0, InHyphenWord),
+            act(Special, 0, InHyphenWord),
+            act(Digit, 0, InHyphenNumWord),
+            act(Eq(b'-'), PUSH, InHyphenAsciiWordFirst),
+            act(Any, BINGO | CLRALL, InParseHyphen)
+                .token(TokenType::AsciiHWord)
+                .handler(Handler::Hyphen),
+        ],
+        // InHyphenWordFirst
+        &[
+            act(Eof, POP, Null),
+            act(Alpha, 0, InHyphenWord),
+            act(Digit, 0, InHyphenDigitLookahead),
+            act(Any, POP, Null),
+        ],
+        // InHyphenWord
+        &[
+            act(Eof, BINGO | CLRALL, InParseHyphen)
+                .token(TokenType::HWord)
+                .handler(Handler::Hyphen),
+            act(Alpha, 0, InHyphenWord),
+            act(Special, 0, InHyphenWord),
+            act(Digit, 0, InHyphenNumWord),
+            act(Eq(b'-'), PUSH, InHyphenWordFirst),
+            act(Any, BINGO | CLRALL, InParseHyphen)
+                .token(TokenType::HWord)
+                .handler(Handler::Hyphen),
+        ],
+        // InHyphenNumWordFirst
+        &[
+            act(Eof, POP, Null),
+            act(Alpha, 0, InHyphenNumWord),
+            act(Digit, 0, InHyphenDigitLookahead),
+            act(Any, POP, Null),
+        ],
+        // InHyphenNumWord
+        &[
+            act(Eof, BINGO | CLRALL, InParseHyphen)
+                .token(TokenType::NumHWord)
+                .handler(Handler::Hyphen),
+            act(Alnum, 0, InHyphenNumWord),
+            act(Special, 0, InHyphenNumWord),
+            act(Eq(b'-'), PUSH, InHyphenNumWordFirst),
+            act(Any, BINGO | CLRALL, InParseHyphen)
+                .token(TokenType::NumHWord)
+                .handler(Handler::Hyphen),
+        ],
+        // InHyphenDigitLookahead
+        &[
+            act(Eof, POP, Null),
+            act(Digit, 0, InHyphenDigitLookahead),
+            act(Alpha, 0, InHyphenNumWord),
+            act(Special, 0, InHyphenNumWord),
+            act(Any, POP, Null),
+        ],
+        // InParseHyphen
+        &[
+            act(Eof, RERUN, Base),
+            act(AsciiLetter, 0, InHyphenAsciiWordPart),
+            act(Alpha, 0, InHyphenWordPart),
+            act(Digit, PUSH, InHyphenUnsignedInt),
+            act(Eq(b'-'), PUSH, InParseHyphenHyphen),
+            act(Any, RERUN, Base),
+        ],
+        // InParseHyphenHyphen
+        &[
+            act(Eof, POP, Null),
+            act(Alnum, BINGO | CLEAR, InParseHyphen).token(TokenType::Blank),
+            act(Special, BINGO | CLEAR, InParseHyphen).token(TokenType::Blank),
+            act(Any, POP, Null),
+        ],
+        // InHyphenWordPart
+        &[
+            act(Eof, BINGO, Base).token(TokenType::HWordPart),
+            act(Alpha, 0, InHyphenWordPart),
+            act(Special, 0, InHyphenWordPart),
+            act(Digit, 0, InHyphenNumWordPart),
+            act(Any, BINGO, InParseHyphen).token(TokenType::HWordPart),
+        ],
+        // InHyphenAsciiWordPart
+        &[
+            act(Eof, BINGO, Base).token(TokenType::HWordAsciiPart),
+            act(AsciiLetter, 0, InHyphenAsciiWordPart),
+            act(Alpha, 0, InHyphenWordPart),
+            act(Special, 0, InHyphenWordPart),
+            act(Digit, 0, InHyphenNumWordPart),
+            act(Any, BINGO, InParseHyphen).token(TokenType::HWordAsciiPart),
+        ],
+        // InHyphenNumWordPart
+        &[
+            act(Eof, BINGO, Base).token(TokenType::HWordNumPart),
+            act(Alnum, 0, InHyphenNumWordPart),
+            act(Special, 0, InHyphenNumWordPart),
+            act(Any, BINGO, InParseHyphen).token(TokenType::HWordNumPart),
+        ],
+        // InHyphenUnsignedInt
+        &[
+            act(Eof, POP, Null),
+            act(Digit, 0, Null),
+            act(Alpha, CLEAR, InHyphenNumWordPart),
+            act(Special, CLEAR, InHyphenNumWordPart),
+            act(Any, POP, Null),
+        ],
+    ]
+};
+
+// Spacing marks that are part of a word, in the order of their code points
+const STRANGE_LETTERS: [u32; 228] = [
+    0x0903, 0x093E, 0x093F, 0x0940, 0x0949, 0x094A, 0x094B, 0x094C, 0x0982, 0x0983, 0x09BE, 0x09BF,
+    0x09C0, 0x09C7, 0x09C8, 0x09CB, 0x09CC, 0x09D7, 0x0A03, 0x0A3E, 0x0A3F, 0x0A40, 0x0A83, 0x0ABE,
+    0x0ABF, 0x0AC0, 0x0AC9, 0x0ACB, 0x0ACC, 0x0B02, 0x0B03, 0x0B3E, 0x0B40, 0x0B47, 0x0B48, 0x0B4B,
+    0x0B4C, 0x0B57, 0x0BBE, 0x0BBF, 0x0BC1, 0x0BC2, 0x0BC6, 0x0BC7, 0x0BC8, 0x0BCA, 0x0BCB, 0x0BCC,
+    0x0BD7, 0x0C01, 0x0C02, 0x0C03, 0x0C41, 0x0C42, 0x0C43, 0x0C44, 0x0C82, 0x0C83, 0x0CBE, 0x0CC0,
+    0x0CC1, 0x0CC2, 0x0CC3, 0x0CC4, 0x0CC7, 0x0CC8, 0x0CCA, 0x0CCB, 0x0CD5, 0x0CD6, 0x0D02, 0x0D03,
+    0x0D3E, 0x0D3F, 0x0D40, 0x0D46, 0x0D47, 0x0D48, 0x0D4A, 0x0D4B, 0x0D4C, 0x0D57, 0x0D82, 0x0D83,
+    0x0DCF, 0x0DD0, 0x0DD1, 0x0DD8, 0x0DD9, 0x0DDA, 0x0DDB, 0x0DDC, 0x0DDD, 0x0DDE, 0x0DDF, 0x0DF2,
+    0x0DF3, 0x0F3E, 0x0F3F, 0x0F7F, 0x102B, 0x102C, 0x1031, 0x1038, 0x103B, 0x103C, 0x1056, 0x1057,
+    0x1062, 0x1063, 0x1064, 0x1067, 0x1068, 0x1069, 0x106A, 0x106B, 0x106C, 0x106D, 0x1083, 0x1084,
+    0x1087, 0x1088, 0x1089, 0x108A, 0x108B, 0x108C, 0x108F, 0x17B6, 0x17BE, 0x17BF, 0x17C0, 0x17C1,
+    0x17C2, 0x17C3, 0x17C4, 0x17C5, 0x17C7, 0x17C8, 0x1923, 0x1924, 0x1925, 0x1926, 0x1929, 0x192A,
+    0x192B, 0x1930, 0x1931, 0x1933, 0x1934, 0x1935, 0x1936, 0x1937, 0x1938, 0x19B0, 0x19B1, 0x19B2,
+    0x19B3, 0x19B4, 0x19B5, 0x19B6, 0x19B7, 0x19B8, 0x19B9, 0x19BA, 0x19BB, 0x19BC, 0x19BD, 0x19BE,
+    0x19BF, 0x19C0, 0x19C8, 0x19C9, 0x1A19, 0x1A1A, 0x1A1B, 0x1B04, 0x1B35, 0x1B3B, 0x1B3D, 0x1B3E,
+    0x1B3F, 0x1B40, 0x1B41, 0x1B43, 0x1B44, 0x1B82, 0x1BA1, 0x1BA6, 0x1BA7, 0x1BAA, 0x1C24, 0x1C25,
+    0x1C26, 0x1C27, 0x1C28, 0x1C29, 0x1C2A, 0x1C2B, 0x1C34, 0x1C35, 0xA823, 0xA824, 0xA827, 0xA880,
+    0xA881, 0xA8B4, 0xA8B5, 0xA8B6, 0xA8B7, 0xA8B8, 0xA8B9, 0xA8BA, 0xA8BB, 0xA8BC, 0xA8BD, 0xA8BE,
+    0xA8BF, 0xA8C0, 0xA8C1, 0xA8C2, 0xA8C3, 0xA952, 0xA953, 0xAA2F, 0xAA30, 0xAA33, 0xAA34, 0xAA4D,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::{DefaultParser, TextParser};
+
+    // (document, alias and token of every row of ts_debug) captured from PostgreSQL 15
+    // in a UTF8 database
+    const CORPUS: &[(&str, &[(&str, &str)])] = &[
+        (
+            "a fat  cat sat on a mat - it ate a fat rats",
+            &[
+                ("asciiword", "a"),
+                ("blank", " "),
+                ("asciiword", "fat"),
+                ("blank", "  "),
+                ("asciiword", "cat"),
+                ("blank", " "),
+                ("asciiword", "sat"),
+                ("blank", " "),
+                ("asciiword", "on"),
+                ("blank", " "),
+                ("asciiword", "a"),
+                ("blank", " "),
+                ("asciiword", "mat"),
+                ("blank", " "),
+                ("blank", "- "),
+                ("asciiword", "it"),
+                ("blank", " "),
+                ("asciiword", "ate"),
+                ("blank", " "),
+                ("asciiword", "a"),
+                ("blank", " "),
+                ("asciiword", "fat"),
+                ("blank", " "),
+                ("asciiword", "rats"),
+            ],
+        ),
+        (
+            "foo-bar-beta1 self-contained",
+            &[
+                ("numhword", "foo-bar-beta1"),
+                ("hword_asciipart", "foo"),
+                ("blank", "-"),
+                ("hword_asciipart", "bar"),
+                ("blank", "-"),
+                ("hword_numpart", "beta1"),
+                ("blank", " "),
+                ("asciihword", "self-contained"),
+                ("hword_asciipart", "self"),
+                ("blank", "-"),
+                ("hword_asciipart", "contained"),
+            ],
+        ),
+        (
+            "supernovaes qué-tal über",
+            &[
+                ("asciiword", "supernovaes"),
+                ("blank", " "),
+                ("hword", "qué-tal"),
+                ("hword_part", "qué"),
+                ("blank", "-"),
+                ("hword_asciipart", "tal"),
+                ("blank", " "),
+                ("word", "über"),
+            ],
+        ),
+        (
+            "email me at foo@example.com or see http://www.example.com/stuff/index.html",
+            &[
+                ("asciiword", "email"),
+                ("blank", " "),
+                ("asciiword", "me"),
+                ("blank", " "),
+                ("asciiword", "at"),
+                ("blank", " "),
+                ("email", "foo@example.com"),
+                ("blank", " "),
+                ("asciiword", "or"),
+                ("blank", " "),
+                ("asciiword", "see"),
+                ("blank", " "),
+                ("protocol", "http://"),
+                ("url", "www.example.com/stuff/index.html"),
+                ("host", "www.example.com"),
+                ("url_path", "/stuff/index.html"),
+            ],
+        ),
+        (
+            "version 8.3.0 costs -12.5 or 1e3 and 1.5e-3 or 42",
+            &[
+                ("asciiword", "version"),
+                ("blank", " "),
+                ("version", "8.3.0"),
+                ("blank", " "),
+                ("asciiword", "costs"),
+                ("blank", " "),
+                ("float", "-12.5"),
+                ("blank", " "),
+                ("asciiword", "or"),
+                ("blank", " "),
+                ("sfloat", "1e3"),
+                ("blank", " "),
+                ("asciiword", "and"),
+                ("blank", " "),
+                ("sfloat", "1.5e-3"),
+                ("blank", " "),
+                ("asciiword", "or"),
+                ("blank", " "),
+                ("uint", "42"),
+            ],
+        ),
+        (
+            "<b>bold</b> &amp; /usr/local/foo.txt",
+            &[
+                ("tag", "<b>"),
+                ("asciiword", "bold"),
+                ("tag", "</b>"),
+                ("blank", " "),
+                ("entity", "&amp;"),
+                ("blank", " "),
+                ("file", "/usr/local/foo.txt"),
+            ],
+        ),
+        (
+            "a < b & c + d / e -- f--g ... h !? i",
+            &[
+                ("asciiword", "a"),
+                ("blank", " "),
+                ("blank", "< "),
+                ("asciiword", "b"),
+                ("blank", " "),
+                ("blank", "& "),
+                ("asciiword", "c"),
+                ("blank", " "),
+                ("blank", "+ "),
+                ("asciiword", "d"),
+                ("blank", " "),
+                ("blank", "/ "),
+                ("asciiword", "e"),
+                ("blank", " "),
+                ("blank", "-"),
+                ("blank", "- "),
+                ("asciiword", "f"),
+                ("blank", "-"),
+                ("blank", "-"),
+                ("asciiword", "g"),
+                ("blank", " ... "),
+                ("asciiword", "h"),
+                ("blank", " !? "),
+                ("asciiword", "i"),
+            ],
+        ),
+        (
+            "x-1 -y 3-4 a+b a/b <tag attr=\"v\"> &#123; && --",
+            &[
+                ("asciiword", "x"),
+                ("int", "-1"),
+                ("blank", " "),
+                ("blank", "-"),
+                ("asciiword", "y"),
+                ("blank", " "),
+                ("uint", "3"),
+                ("int", "-4"),
+                ("blank", " "),
+                ("asciiword", "a"),
+                ("blank", "+"),
+                ("asciiword", "b"),
+                ("blank", " "),
+                ("file", "a/b"),
+                ("blank", " "),
+                ("tag", "<tag attr=\"v\">"),
+                ("blank", " "),
+                ("entity", "&#123;"),
+                ("blank", " "),
+                ("blank", "&"),
+                ("blank", "& "),
+                ("blank", "-"),
+                ("blank", "-"),
+            ],
+        ),
+        (
+            "foo-bar-1 foo-1bar 1-foo é-1 x-1.5 über-alles-2x a1-b2 2x-y",
+            &[
+                ("asciihword", "foo-bar"),
+                ("hword_asciipart", "foo"),
+                ("blank", "-"),
+                ("hword_asciipart", "bar"),
+                ("blank", "-"),
+                ("uint", "1"),
+                ("blank", " "),
+                ("numhword", "foo-1bar"),
+                ("hword_asciipart", "foo"),
+                ("blank", "-"),
+                ("hword_numpart", "1bar"),
+                ("blank", " "),
+                ("uint", "1"),
+                ("blank", "-"),
+                ("asciiword", "foo"),
+                ("blank", " "),
+                ("word", "é"),
+                ("int", "-1"),
+                ("blank", " "),
+                ("asciiword", "x"),
+                ("float", "-1.5"),
+                ("blank", " "),
+                ("numhword", "über-alles-2x"),
+                ("hword_part", "über"),
+                ("blank", "-"),
+                ("hword_asciipart", "alles"),
+                ("blank", "-"),
+                ("hword_numpart", "2x"),
+                ("blank", " "),
+                ("numhword", "a1-b2"),
+                ("hword_numpart", "a1"),
+                ("blank", "-"),
+                ("hword_numpart", "b2"),
+                ("blank", " "),
+                ("numhword", "2x-y"),
+                ("hword_numpart", "2x"),
+                ("blank", "-"),
+                ("hword_asciipart", "y"),
+            ],
+        ),
+        (
+            "foo-bar-1.5 foo-bar -1 foo-bar-1x 1.5-foo v-2.0 a-b-c-d",
+            &[
+                ("asciihword", "foo-bar"),
+                ("hword_asciipart", "foo"),
+                ("blank", "-"),
+                ("hword_asciipart", "bar"),
+                ("blank", "-"),
+                ("float", "1.5"),
+                ("blank", " "),
+                ("asciihword", "foo-bar"),
+                ("hword_asciipart", "foo"),
+                ("blank", "-"),
+                ("hword_asciipart", "bar"),
+                ("blank", " "),
+                ("int", "-1"),
+                ("blank", " "),
+                ("numhword", "foo-bar-1x"),
+                ("hword_asciipart", "foo"),
+                ("blank", "-"),
+                ("hword_asciipart", "bar"),
+                ("blank", "-"),
+                ("hword_numpart", "1x"),
+                ("blank", " "),
+                ("float", "1.5"),
+                ("blank", "-"),
+                ("asciiword", "foo"),
+                ("blank", " "),
+                ("asciiword", "v"),
+                ("float", "-2.0"),
+                ("blank", " "),
+                ("asciihword", "a-b-c-d"),
+                ("hword_asciipart", "a"),
+                ("blank", "-"),
+                ("hword_asciipart", "b"),
+                ("blank", "-"),
+                ("hword_asciipart", "c"),
+                ("blank", "-"),
+                ("hword_asciipart", "d"),
+            ],
+        ),
+        (
+            "+1 -1a 11.b .1.B b.a_b aa..",
+            &[
+                ("int", "+1"),
+                ("blank", " "),
+                ("int", "-1"),
+                ("asciiword", "a"),
+                ("blank", " "),
+                ("uint", "11"),
+                ("blank", "."),
+                ("asciiword", "b"),
+                ("blank", " ."),
+                ("uint", "1"),
+                ("blank", "."),
+                ("asciiword", "B"),
+                ("blank", " "),
+                ("file", "b.a_b"),
+                ("blank", " "),
+                ("asciiword", "aa"),
+                ("file", ".."),
+            ],
+        ),
+        (
+            "<& / <a-/ 1>=",
+            &[
+                ("blank", "<"),
+                ("blank", "& "),
+                ("blank", "/ "),
+                ("blank", "<"),
+                ("asciiword", "a"),
+                ("blank", "-"),
+                ("blank", "/ "),
+                ("uint", "1"),
+                ("blank", ">="),
+            ],
+        ),
+        (
+            "ÄΣ 1.2.3.4 -1.2.3 1.5e+3x a@b.c",
+            &[
+                ("word", "ÄΣ"),
+                ("blank", " "),
+                ("version", "1.2.3.4"),
+                ("blank", " "),
+                ("blank", "-"),
+                ("version", "1.2.3"),
+                ("blank", " "),
+                ("sfloat", "1.5e+3"),
+                ("asciiword", "x"),
+                ("blank", " "),
+                ("asciiword", "a"),
+                ("blank", "@"),
+                ("file", "b.c"),
+            ],
+        ),
+        (
+            "http://a.b/c?d=1 www.x.org:80/p?q <script>a b</script> c",
+            &[
+                ("protocol", "http://"),
+                ("file", "a.b/c"),
+                ("blank", "?"),
+                ("asciiword", "d"),
+                ("blank", "="),
+                ("uint", "1"),
+                ("blank", " "),
+                ("url", "www.x.org:80/p?q"),
+                ("host", "www.x.org:80"),
+                ("url_path", "/p?q"),
+                ("blank", " "),
+                ("tag", "<script>"),
+                ("blank", "a b"),
+                ("tag", "</script>"),
+                ("blank", " "),
+                ("asciiword", "c"),
+            ],
+        ),
+    ];
+
+    #[test]
+    fn tokens_like_ts_debug() {
+        for (document, expected) in CORPUS {
+            let tokens = DefaultParser.parse(document);
+            let tokens: Vec<(&str, &str)> = tokens
+                .iter()
+                .map(|token| (token.kind.alias(), token.text.as_str()))
+                .collect();
+            assert_eq!(tokens, *expected, "document: {document}");
+        }
+    }
+}
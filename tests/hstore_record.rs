@@ -0,0 +1,102 @@
+#![cfg(feature = "derive")]
+
+use postgres_types_extra::pg_hstore::PgHstore;
+use postgres_types_extra::pg_hstore_record::{HstoreRecord, RecordKey};
+use std::str::FromStr;
+
+#[derive(Debug, PartialEq, HstoreRecord)]
+struct Limits {
+    max_rows: Option<u32>,
+    timeout: Option<f32>,
+}
+
+#[derive(Debug, PartialEq, HstoreRecord)]
+struct Settings {
+    #[hstore(rename = "page-size")]
+    page_size: Option<u32>,
+    theme: Option<String>,
+    #[hstore(flatten)]
+    limits: Limits,
+}
+
+#[derive(Debug, PartialEq, HstoreRecord)]
+#[hstore(deny_unknown_keys)]
+struct Strict {
+    theme: Option<String>,
+}
+
+#[test]
+fn round_trip() {
+    let settings = Settings {
+        page_size: Some(50),
+        theme: Some("dark".to_string()),
+        limits: Limits {
+            max_rows: None,
+            timeout: Some(1.5),
+        },
+    };
+
+    // hstore(row(50, 'dark', NULL, 1.5)::settings) captured from PostgreSQL 15
+    let hstore = settings.to_hstore();
+    assert_eq!(
+        hstore.to_string(),
+        r#""theme"=>"dark", "timeout"=>"1.5", "max_rows"=>NULL, "page-size"=>"50""#
+    );
+    assert_eq!(Settings::from_hstore(&hstore).unwrap(), settings);
+}
+
+#[test]
+fn reads_like_populate_record() {
+    let hstore =
+        PgHstore::from_str(r#""page-size"=>"20", theme=>NULL, max_rows=>"7", extra=>"x""#).unwrap();
+    let settings = Settings::from_hstore(&hstore).unwrap();
+    assert_eq!(
+        settings,
+        Settings {
+            page_size: Some(20),
+            theme: None,
+            limits: Limits {
+                max_rows: Some(7),
+                timeout: None,
+            },
+        }
+    );
+
+    assert_eq!(
+        Settings::keys(),
+        ["page-size", "theme", "max_rows", "timeout"]
+    );
+    assert!(Strict::from_hstore(&hstore).is_err());
+    assert!(Settings::from_hstore(&PgHstore::from_str("max_rows=>x").unwrap()).is_err());
+}
+
+#[derive(Debug, PartialEq, HstoreRecord)]
+struct Named<T: HstoreRecord> {
+    name: Option<String>,
+    #[hstore(flatten)]
+    inner: T,
+}
+
+#[test]
+fn lists_keys_in_field_order() {
+    assert_eq!(
+        Named::<Limits>::KEYS,
+        [
+            RecordKey::Field("name"),
+            RecordKey::Flattened(&[RecordKey::Field("max_rows"), RecordKey::Field("timeout")]),
+        ]
+    );
+    assert_eq!(
+        Named::<Settings>::keys(),
+        ["name", "page-size", "theme", "max_rows", "timeout"]
+    );
+
+    let named = Named {
+        name: Some("x".to_string()),
+        inner: Strict { theme: None },
+    };
+    assert_eq!(
+        named.to_hstore().to_string(),
+        r#""name"=>"x", "theme"=>NULL"#
+    );
+}